@@ -6,57 +6,168 @@ use std::collections::HashMap;
 use crate::ast::*;
 use crate::error::{CompileError, CompileResult};
 
+/// Default cap on the number of elements in any one matrix (16M)
+pub const DEFAULT_MAX_ELEMENTS: usize = 1 << 24;
+
 /// Analyzer for type checking and shape inference
+#[derive(Clone)]
 pub struct Analyzer {
-    /// Known matrix shapes: name -> (rows, cols)
-    shapes: HashMap<String, (usize, usize)>,
+    /// Known variable shapes: name -> shape
+    shapes: HashMap<String, Shape>,
+    /// Quantization scales given with `define_scaled_matrix`
+    input_scales: HashMap<String, f64>,
+    /// Contents given with `define_matrix_with_data`, as rows
+    input_data: HashMap<String, Vec<Vec<f64>>>,
+    /// Largest matrix, in elements, the analyzer will accept
+    max_elements: usize,
+    /// Treat shape redefinitions as errors
+    strict: bool,
+    /// Problems tolerated in lenient mode during the last `analyze`
+    warnings: Vec<String>,
+    /// User functions from `def`: name -> (parameters, body)
+    functions: HashMap<String, (Vec<String>, Expr)>,
+    /// Functions currently being inlined, outermost first
+    expanding: Vec<String>,
 }
 
 impl Analyzer {
     pub fn new() -> Self {
         Self {
             shapes: HashMap::new(),
+            input_scales: HashMap::new(),
+            input_data: HashMap::new(),
+            max_elements: DEFAULT_MAX_ELEMENTS,
+            strict: false,
+            warnings: Vec::new(),
+            functions: HashMap::new(),
+            expanding: Vec::new(),
         }
     }
     
+    /// Override the maximum number of elements allowed in any matrix
+    pub fn with_max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+    
+    /// Reject shape redefinitions instead of warning
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+    
+    /// Warnings collected by the last `analyze` in lenient mode
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+    
     /// Define a matrix with known shape
     pub fn define_matrix(&mut self, name: &str, shape: (usize, usize)) {
-        self.shapes.insert(name.to_string(), shape);
+        self.shapes.insert(name.to_string(), Shape::matrix(shape.0, shape.1));
+    }
+    
+    /// Define a matrix whose values are multiplied by `scale` when quantized
+    ///
+    /// E.g. a scale of 127.0 spreads weights in `[-1, 1]` across the whole int8 range.
+    pub fn define_scaled_matrix(&mut self, name: &str, shape: (usize, usize), scale: f64) {
+        self.define_matrix(name, shape);
+        self.input_scales.insert(name.to_string(), scale);
+    }
+    
+    /// Define a matrix with known row-major contents, compiled in as if it were a literal
+    pub fn define_matrix_with_data(&mut self, name: &str, shape: (usize, usize), data: &[f64]) -> CompileResult<()> {
+        let (rows, cols) = shape;
+        self.check_size(name, rows, cols)?;
+        if data.len() != rows * cols {
+            return Err(CompileError::ShapeMismatch {
+                expected: format!("{} values for '{}' ({}x{})", rows * cols, name, rows, cols),
+                got: format!("{} values", data.len()),
+            });
+        }
+        self.define_matrix(name, shape);
+        let rows = (0..rows).map(|i| data[i * cols..(i + 1) * cols].to_vec()).collect();
+        self.input_data.insert(name.to_string(), rows);
+        Ok(())
+    }
+    
+    /// Shapes of every variable defined so far
+    pub fn shapes(&self) -> &HashMap<String, Shape> {
+        &self.shapes
     }
     
     /// Analyze a program and produce typed AST
     pub fn analyze(&mut self, program: Program) -> CompileResult<TypedProgram> {
         let mut statements = Vec::new();
+        self.warnings.clear();
         
         for stmt in program.statements {
-            statements.push(self.analyze_statement(stmt)?);
+            statements.extend(self.analyze_statement(stmt)?);
         }
         
-        Ok(TypedProgram { statements })
+        Ok(TypedProgram {
+            statements,
+            input_scales: self.input_scales.clone(),
+            input_data: self.input_data.clone(),
+        })
     }
     
-    /// Analyze a statement
-    fn analyze_statement(&mut self, stmt: Statement) -> CompileResult<TypedStatement> {
+    /// Analyze a statement; a `def` only defines a function and yields nothing
+    fn analyze_statement(&mut self, stmt: Statement) -> CompileResult<Option<TypedStatement>> {
         match stmt {
+            Statement::Def { name, params, body } => {
+                if let Some(duplicate) = params.iter().enumerate().find(|(i, p)| params[..*i].contains(p)) {
+                    return Err(CompileError::type_error(format!(
+                        "Duplicate parameter '{}' in definition of {}",
+                        duplicate.1, name
+                    )));
+                }
+                self.functions.insert(name, (params, body));
+                Ok(None)
+            }
             Statement::Assignment { target, value } => {
+                // `A = A @ B` is an update of a prior A; without one it has no base case
+                if !self.shapes.contains_key(&target) && value.references(&target) {
+                    return Err(CompileError::invalid_op(format!(
+                        "'{}' is used in its own definition but has no prior value",
+                        target
+                    )));
+                }
+                
                 let typed_value = self.analyze_expr(&value)?;
+                if let Shape::Matrix { rows, cols } = typed_value.shape {
+                    self.check_size(&target, rows, cols)?;
+                }
+                
+                if let Some(previous) = self.shapes.get(&target) {
+                    let both_known = *previous != Shape::Unknown && typed_value.shape != Shape::Unknown;
+                    if both_known && *previous != typed_value.shape {
+                        let message = format!(
+                            "'{}' redefined with shape {} (was {})",
+                            target, typed_value.shape, previous
+                        );
+                        self.strict_check(CompileError::invalid_op(message.clone()), message)?;
+                    }
+                }
                 
-                // Record the shape of the target variable
-                if let Some((rows, cols)) = typed_value.shape.dimensions() {
-                    self.shapes.insert(target.clone(), (rows, cols));
+                // Record the shape of the target variable; an unknown shape still
+                // defines the name, but never hides a shape already known
+                if typed_value.shape != Shape::Unknown {
+                    self.shapes.insert(target.clone(), typed_value.shape.clone());
+                } else {
+                    self.shapes.entry(target.clone()).or_insert(Shape::Unknown);
                 }
                 
-                Ok(TypedStatement {
+                Ok(Some(TypedStatement {
                     target,
                     value: typed_value,
-                })
+                }))
             }
             Statement::Expression(expr) => {
                 let typed_value = self.analyze_expr(&expr)?;
-                Ok(TypedStatement {
+                Ok(Some(TypedStatement {
                     target: "_".to_string(),
                     value: typed_value,
-                })
+                }))
             }
         }
     }
@@ -65,9 +176,13 @@ impl Analyzer {
     fn analyze_expr(&mut self, expr: &Expr) -> CompileResult<TypedExpr> {
         match expr {
             Expr::Variable(name) => {
-                let shape = self.shapes.get(name)
-                    .map(|(r, c)| Shape::matrix(*r, *c))
-                    .unwrap_or(Shape::Unknown);
+                let shape = match self.shapes.get(name) {
+                    Some(shape) => shape.clone(),
+                    None => return Err(CompileError::undefined(name.clone())),
+                };
+                if let Shape::Matrix { rows, cols } = shape {
+                    self.check_size(name, rows, cols)?;
+                }
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::Variable(name.clone()),
@@ -90,24 +205,17 @@ impl Analyzer {
                 })
             }
             
+            // Stored as a single row, like every vector
+            Expr::Vector(values) => Ok(TypedExpr {
+                expr: TypedExprKind::Matrix(vec![values.clone()]),
+                shape: Shape::Vector(values.len()),
+            }),
+            
             Expr::MatMul(left, right) => {
                 let left_typed = self.analyze_expr(left)?;
                 let right_typed = self.analyze_expr(right)?;
                 
-                // Check dimension compatibility
-                let shape = match (&left_typed.shape, &right_typed.shape) {
-                    (Shape::Matrix { rows: m, cols: k1 }, Shape::Matrix { rows: k2, cols: n }) => {
-                        if k1 != k2 {
-                            return Err(CompileError::ShapeMismatch {
-                                expected: format!("inner dimensions to match, got {} and {}", k1, k2),
-                                got: format!("left: ({}, {}), right: ({}, {})", m, k1, k2, n),
-                            });
-                        }
-                        Shape::matrix(*m, *n)
-                    }
-                    (Shape::Unknown, _) | (_, Shape::Unknown) => Shape::Unknown,
-                    _ => return Err(CompileError::type_error("MatMul requires matrix operands")),
-                };
+                let shape = left_typed.shape.matmul_with(&right_typed.shape)?;
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::MatMul(Box::new(left_typed), Box::new(right_typed)),
@@ -115,11 +223,16 @@ impl Analyzer {
                 })
             }
             
+            Expr::Paren(inner) => {
+                let inner = self.analyze_expr(inner)?;
+                Ok(TypedExpr { shape: inner.shape.clone(), expr: TypedExprKind::Grouped(Box::new(inner)) })
+            }
+            
             Expr::Add(left, right) => {
                 let left_typed = self.analyze_expr(left)?;
                 let right_typed = self.analyze_expr(right)?;
                 
-                let shape = self.check_broadcast_compatible(&left_typed.shape, &right_typed.shape)?;
+                let shape = left_typed.shape.broadcast_with(&right_typed.shape)?;
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::Add(Box::new(left_typed), Box::new(right_typed)),
@@ -131,7 +244,7 @@ impl Analyzer {
                 let left_typed = self.analyze_expr(left)?;
                 let right_typed = self.analyze_expr(right)?;
                 
-                let shape = self.check_broadcast_compatible(&left_typed.shape, &right_typed.shape)?;
+                let shape = left_typed.shape.broadcast_with(&right_typed.shape)?;
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::Sub(Box::new(left_typed), Box::new(right_typed)),
@@ -144,19 +257,7 @@ impl Analyzer {
                 let right_typed = self.analyze_expr(right)?;
                 
                 // Element-wise or scalar multiplication
-                let shape = match (&left_typed.shape, &right_typed.shape) {
-                    (Shape::Scalar, other) | (other, Shape::Scalar) => other.clone(),
-                    (Shape::Matrix { rows: r1, cols: c1 }, Shape::Matrix { rows: r2, cols: c2 }) => {
-                        if r1 != r2 || c1 != c2 {
-                            return Err(CompileError::ShapeMismatch {
-                                expected: format!("same shape for element-wise mul"),
-                                got: format!("({}, {}) and ({}, {})", r1, c1, r2, c2),
-                            });
-                        }
-                        Shape::matrix(*r1, *c1)
-                    }
-                    (Shape::Unknown, _) | (_, Shape::Unknown) => Shape::Unknown,
-                };
+                let shape = left_typed.shape.broadcast_with(&right_typed.shape)?;
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::Mul(Box::new(left_typed), Box::new(right_typed)),
@@ -164,6 +265,19 @@ impl Analyzer {
                 })
             }
             
+            Expr::Div(left, right) => {
+                let left_typed = self.analyze_expr(left)?;
+                let right_typed = self.analyze_expr(right)?;
+                
+                // Element-wise or by a scalar, broadcasting like `*`
+                let shape = left_typed.shape.broadcast_with(&right_typed.shape)?;
+                
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Div(Box::new(left_typed), Box::new(right_typed)),
+                    shape,
+                })
+            }
+            
             Expr::ScalarMul(scalar, matrix) => {
                 let scalar_typed = self.analyze_expr(scalar)?;
                 let matrix_typed = self.analyze_expr(matrix)?;
@@ -175,14 +289,30 @@ impl Analyzer {
                 })
             }
             
+            Expr::MatPow(base, exponent) => {
+                let base = self.analyze_expr(base)?;
+                match (&base.shape, *exponent) {
+                    (Shape::Matrix { rows, cols }, _) if rows != cols => Err(CompileError::ShapeMismatch {
+                        expected: "a square matrix for '**'".to_string(),
+                        got: base.shape.to_string(),
+                    }),
+                    (Shape::Matrix { rows: n, .. }, 0) => {
+                        let n = *n;
+                        let identity = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+                        Ok(TypedExpr { expr: TypedExprKind::Matrix(identity), shape: base.shape })
+                    }
+                    (Shape::Matrix { .. }, exponent) => Ok(matrix_power(base, exponent)),
+                    (shape, _) => Err(CompileError::type_error(format!(
+                        "'**' needs a square matrix of known shape, got {}",
+                        shape
+                    ))),
+                }
+            }
+            
             Expr::Transpose(inner) => {
                 let inner_typed = self.analyze_expr(inner)?;
                 
-                let shape = match &inner_typed.shape {
-                    Shape::Matrix { rows, cols } => Shape::matrix(*cols, *rows),
-                    Shape::Scalar => Shape::Scalar,
-                    Shape::Unknown => Shape::Unknown,
-                };
+                let shape = inner_typed.shape.transposed();
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::Transpose(Box::new(inner_typed)),
@@ -190,8 +320,75 @@ impl Analyzer {
                 })
             }
             
-            Expr::FunctionCall { name, args } => {
-                self.analyze_function_call(name, args)
+            Expr::FunctionCall { name, args, kwargs } => {
+                for (key, _) in kwargs {
+                    if !Self::keyword_params(name).contains(&key.as_str()) {
+                        return Err(CompileError::type_error(format!(
+                            "{} got an unexpected keyword argument '{}'",
+                            name, key
+                        )));
+                    }
+                }
+                
+                if self.functions.contains_key(name) {
+                    return self.expand_call(name, args);
+                }
+                if name == "np.append" {
+                    return self.analyze_append(args, kwargs);
+                }
+                if matches!(name.as_str(), "np.concatenate" | "np.hstack" | "np.vstack") {
+                    return self.analyze_concatenate(name, args, kwargs);
+                }
+                if name == "np.roll" {
+                    return self.analyze_roll(args, kwargs);
+                }
+                if name == "np.linalg.norm" {
+                    return self.analyze_norm(args, kwargs);
+                }
+                if matches!(name.as_str(), "np.expand_dims" | "np.squeeze") {
+                    return self.analyze_unit_axes(name, args, kwargs);
+                }
+                let typed = self.analyze_function_call(name, args)?;
+                
+                match kwargs.iter().find(|(key, _)| key == "out") {
+                    Some((_, out)) => self.bind_out(out, typed),
+                    None => Ok(typed),
+                }
+            }
+            
+            Expr::Index(base, subscripts) => {
+                // np.split yields several arrays, so it is only usable with one picked out
+                if let Expr::FunctionCall { name, args, kwargs } = base.as_ref() {
+                    if matches!(name.as_str(), "np.split" | "np.hsplit" | "np.vsplit") {
+                        return self.analyze_split(name, args, kwargs, subscripts);
+                    }
+                }
+                
+                let base_typed = self.analyze_expr(base)?;
+                let (subscripts, shape) = self.resolve_subscripts(&base_typed.shape, subscripts)?;
+                
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Index(Box::new(base_typed), subscripts),
+                    shape,
+                })
+            }
+            
+            Expr::Let { name, value, body } => {
+                let value = self.analyze_expr(value)?;
+                
+                // The binding is visible only in the body; restore whatever it shadowed
+                let shadowed = self.shapes.insert(name.clone(), value.shape.clone());
+                let body = self.analyze_expr(body);
+                match shadowed {
+                    Some(shape) => self.shapes.insert(name.clone(), shape),
+                    None => self.shapes.remove(name),
+                };
+                let body = body?;
+                
+                Ok(TypedExpr {
+                    shape: body.shape.clone(),
+                    expr: TypedExprKind::Let { name: name.clone(), value: Box::new(value), body: Box::new(body) },
+                })
             }
             
             Expr::Tuple(elements) => {
@@ -212,8 +409,101 @@ impl Analyzer {
         }
     }
     
+    /// Inline a call to a user function
+    ///
+    /// Arguments are analyzed in the caller's scope, then bound like `let` temporaries
+    /// under names no identifier can spell (`ffn.x`), so each is computed once and
+    /// cannot be captured by the body.
+    fn expand_call(&mut self, name: &str, args: &[Expr]) -> CompileResult<TypedExpr> {
+        let (params, body) = self.functions[name].clone();
+        if args.len() != params.len() {
+            return Err(CompileError::type_error(format!(
+                "{}() expects {} argument(s), got {}",
+                name,
+                params.len(),
+                args.len()
+            )));
+        }
+        if self.expanding.iter().any(|f| f == name) {
+            return Err(CompileError::invalid_op(format!(
+                "'{}' is recursive ({} -> {}); functions are inlined and cannot call themselves",
+                name,
+                self.expanding.join(" -> "),
+                name
+            )));
+        }
+        
+        let values = args.iter().map(|arg| self.analyze_expr(arg)).collect::<CompileResult<Vec<_>>>()?;
+        let scoped: Vec<String> = params.iter().map(|param| format!("{}.{}", name, param)).collect();
+        let bindings: Vec<(String, Expr)> = params.into_iter()
+            .zip(scoped.iter().map(|s| Expr::Variable(s.clone())))
+            .collect();
+        for (param, value) in scoped.iter().zip(&values) {
+            self.shapes.insert(param.clone(), value.shape.clone());
+        }
+        
+        self.expanding.push(name.to_string());
+        let body = self.analyze_expr(&body.substitute(&bindings));
+        self.expanding.pop();
+        for param in &scoped {
+            self.shapes.remove(param);
+        }
+        
+        Ok(values.into_iter().zip(scoped).rev().fold(body?, |body, (value, param)| TypedExpr {
+            shape: body.shape.clone(),
+            expr: TypedExprKind::Let { name: param, value: Box::new(value), body: Box::new(body) },
+        }))
+    }
+    
+    /// Keyword arguments accepted by a function
+    fn keyword_params(name: &str) -> &'static [&'static str] {
+        match name {
+            "np.matmul" | "np.dot" => &["out"],
+            "np.split" | "np.append" | "np.concatenate" | "np.roll" | "np.expand_dims" | "np.squeeze" | "np.linalg.norm" => &["axis"],
+            _ => &[],
+        }
+    }
+    
+    /// Direct a result into the pre-defined buffer named by `out=`
+    fn bind_out(&mut self, out: &Expr, value: TypedExpr) -> CompileResult<TypedExpr> {
+        let Expr::Variable(buffer) = out else {
+            return Err(CompileError::type_error("out= must name an existing matrix"));
+        };
+        let buffer_shape = self.shapes.get(buffer)
+            .ok_or_else(|| CompileError::undefined(buffer.clone()))?;
+        
+        if value.shape != Shape::Unknown && *buffer_shape != value.shape {
+            return Err(CompileError::ShapeMismatch {
+                expected: format!("out buffer {} to have shape {}", buffer, value.shape),
+                got: buffer_shape.to_string(),
+            });
+        }
+        
+        let shape = buffer_shape.clone();
+        Ok(TypedExpr {
+            expr: TypedExprKind::Out(buffer.clone(), Box::new(value)),
+            shape,
+        })
+    }
+    
     /// Analyze a numpy function call
     fn analyze_function_call(&mut self, name: &str, args: &[Expr]) -> CompileResult<TypedExpr> {
+        if let Some(kind) = Activation::from_name(name) {
+            if args.len() != 1 {
+                return Err(CompileError::type_error(format!(
+                    "{} expects 1 argument",
+                    name
+                )));
+            }
+            
+            let inner = self.analyze_expr(&args[0])?;
+            let shape = inner.shape.clone();
+            return Ok(TypedExpr {
+                expr: TypedExprKind::Activation(kind, Box::new(inner)),
+                shape,
+            });
+        }
+        
         match name {
             "np.zeros" | "np.ones" | "np.empty" => {
                 if args.len() != 1 {
@@ -224,12 +514,48 @@ impl Analyzer {
                 }
                 
                 let shape = self.extract_shape(&args[0])?;
+                self.check_size(name, shape.0, shape.1)?;
+                let fill = if name == "np.ones" { 1.0 } else { 0.0 };
                 Ok(TypedExpr {
-                    expr: TypedExprKind::Matrix(vec![vec![0.0; shape.1]; shape.0]),
+                    expr: TypedExprKind::Matrix(vec![vec![fill; shape.1]; shape.0]),
                     shape: Shape::matrix(shape.0, shape.1),
                 })
             }
             
+            "np.zeros_like" | "np.ones_like" => {
+                if args.len() != 1 {
+                    return Err(CompileError::type_error(format!(
+                        "{} expects 1 argument",
+                        name
+                    )));
+                }
+                
+                let like = self.analyze_expr(&args[0])?;
+                let fill = if name == "np.ones_like" { 1.0 } else { 0.0 };
+                let expr = match &like.shape {
+                    Shape::Scalar => TypedExprKind::Scalar(fill),
+                    Shape::Vector(len) => {
+                        self.check_size(name, 1, *len)?;
+                        TypedExprKind::Matrix(vec![vec![fill; *len]])
+                    }
+                    Shape::Matrix { rows, cols } => {
+                        self.check_size(name, *rows, *cols)?;
+                        TypedExprKind::Matrix(vec![vec![fill; *cols]; *rows])
+                    }
+                    Shape::Unknown => {
+                        return Err(CompileError::type_error(format!(
+                            "{} needs an argument of known shape",
+                            name
+                        )));
+                    }
+                };
+                
+                Ok(TypedExpr {
+                    expr,
+                    shape: like.shape,
+                })
+            }
+            
             "np.eye" | "np.identity" => {
                 if args.is_empty() {
                     return Err(CompileError::type_error(format!(
@@ -239,9 +565,10 @@ impl Analyzer {
                 }
                 
                 let n = self.extract_number(&args[0])? as usize;
+                self.check_size(name, n, n)?;
                 let mut matrix = vec![vec![0.0; n]; n];
-                for i in 0..n {
-                    matrix[i][i] = 1.0;
+                for (i, row) in matrix.iter_mut().enumerate() {
+                    row[i] = 1.0;
                 }
                 
                 Ok(TypedExpr {
@@ -251,18 +578,168 @@ impl Analyzer {
             }
             
             "np.transpose" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(CompileError::type_error(
+                        "np.transpose expects 1 or 2 arguments (array, optional axes)"
+                    ));
+                }
+                
+                let inner = self.analyze_expr(&args[0])?;
+                
+                if let Some(axes) = args.get(1) {
+                    let axes = self.extract_axes(axes)?;
+                    let ndim = if inner.shape.is_vector() { 1 } else { 2 };
+                    
+                    let mut sorted = axes.clone();
+                    sorted.sort_unstable();
+                    if sorted != (0..ndim).collect::<Vec<_>>() {
+                        return Err(CompileError::type_error(format!(
+                            "np.transpose axes {:?} are not a permutation of {} axes",
+                            axes, ndim
+                        )));
+                    }
+                    
+                    // Identity permutation leaves the array unchanged
+                    if axes.iter().enumerate().all(|(i, &axis)| i == axis) {
+                        return Ok(inner);
+                    }
+                }
+                
+                let shape = inner.shape.transposed();
+                
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Transpose(Box::new(inner)),
+                    shape,
+                })
+            }
+            
+            "np.clip" | "clip" => {
+                if args.len() != 3 {
+                    return Err(CompileError::type_error(format!(
+                        "{} expects (array, min, max)",
+                        name
+                    )));
+                }
+                
+                let (min, max) = (self.extract_number(&args[1])?, self.extract_number(&args[2])?);
+                if min > max {
+                    return Err(CompileError::type_error(format!(
+                        "{} min {} is greater than max {}",
+                        name, min, max
+                    )));
+                }
+                let inner = self.analyze_expr(&args[0])?;
+                let shape = inner.shape.clone();
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Clip { source: Box::new(inner), min, max },
+                    shape,
+                })
+            }
+            
+            "np.diag" => {
                 if args.len() != 1 {
-                    return Err(CompileError::type_error("np.transpose expects 1 argument"));
+                    return Err(CompileError::type_error("np.diag expects 1 argument"));
                 }
                 
+                // A vector becomes the diagonal of a square matrix; a matrix gives back its diagonal
                 let inner = self.analyze_expr(&args[0])?;
                 let shape = match &inner.shape {
-                    Shape::Matrix { rows, cols } => Shape::matrix(*cols, *rows),
-                    _ => Shape::Unknown,
+                    Shape::Vector(len) => {
+                        self.check_size(name, *len, *len)?;
+                        Shape::matrix(*len, *len)
+                    }
+                    Shape::Matrix { rows, cols } => Shape::Vector(*rows.min(cols)),
+                    Shape::Scalar => {
+                        return Err(CompileError::type_error("np.diag needs a 1D or 2D array, got a scalar"));
+                    }
+                    Shape::Unknown => {
+                        return Err(CompileError::type_error("np.diag needs an argument of known shape"));
+                    }
                 };
                 
                 Ok(TypedExpr {
-                    expr: TypedExprKind::Transpose(Box::new(inner)),
+                    expr: TypedExprKind::Diag(Box::new(inner)),
+                    shape,
+                })
+            }
+            
+            "np.split" | "np.hsplit" | "np.vsplit" => Err(CompileError::type_error(format!(
+                "{} returns several arrays; select one with {}(...)[i]",
+                name, name
+            ))),
+            
+            "np.ravel" | ".flatten" | ".ravel" => {
+                if args.len() != 1 {
+                    return Err(CompileError::type_error(format!(
+                        "{} expects 1 argument",
+                        name
+                    )));
+                }
+                
+                let inner = self.analyze_expr(&args[0])?;
+                let shape = match &inner.shape {
+                    Shape::Matrix { rows, cols } => Shape::Vector(rows * cols),
+                    Shape::Vector(len) => Shape::Vector(*len),
+                    Shape::Scalar => Shape::Vector(1),
+                    Shape::Unknown => Shape::Unknown,
+                };
+                
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Reshape(Box::new(inner)),
+                    shape,
+                })
+            }
+            
+            "np.reshape" | ".reshape" => {
+                // `A.reshape(3, 4)` passes the dimensions unpacked
+                let shape = match args {
+                    [_, shape] => self.extract_shape(shape)?,
+                    [_, rows, cols] if name == ".reshape" => {
+                        (self.extract_number(rows)? as usize, self.extract_number(cols)? as usize)
+                    }
+                    _ => {
+                        return Err(CompileError::type_error(format!(
+                            "{} expects (array, (rows, cols))",
+                            name
+                        )));
+                    }
+                };
+                self.check_size(name, shape.0, shape.1)?;
+                
+                let inner = self.analyze_expr(&args[0])?;
+                if let Some((rows, cols)) = inner.shape.dimensions() {
+                    if rows * cols != shape.0 * shape.1 {
+                        return Err(CompileError::ShapeMismatch {
+                            expected: format!("{} elements for shape ({}, {})", shape.0 * shape.1, shape.0, shape.1),
+                            got: format!("{} elements in {}", rows * cols, inner.shape),
+                        });
+                    }
+                }
+                
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Reshape(Box::new(inner)),
+                    shape: Shape::matrix(shape.0, shape.1),
+                })
+            }
+            
+            "np.outer" => {
+                if args.len() != 2 {
+                    return Err(CompileError::type_error("np.outer expects 2 arguments"));
+                }
+                
+                let left = self.analyze_expr(&args[0])?;
+                let right = self.analyze_expr(&args[1])?;
+                let (Shape::Vector(m), Shape::Vector(n)) = (&left.shape, &right.shape) else {
+                    return Err(CompileError::type_error(format!(
+                        "np.outer needs two vectors, got {} and {}",
+                        left.shape, right.shape
+                    )));
+                };
+                self.check_size(name, *m, *n)?;
+                let shape = Shape::matrix(*m, *n);
+                
+                Ok(TypedExpr {
+                    expr: TypedExprKind::Outer(Box::new(left), Box::new(right)),
                     shape,
                 })
             }
@@ -278,18 +755,7 @@ impl Analyzer {
                 let left = self.analyze_expr(&args[0])?;
                 let right = self.analyze_expr(&args[1])?;
                 
-                let shape = match (&left.shape, &right.shape) {
-                    (Shape::Matrix { rows: m, cols: k1 }, Shape::Matrix { rows: k2, cols: n }) => {
-                        if k1 != k2 {
-                            return Err(CompileError::ShapeMismatch {
-                                expected: format!("inner dimensions to match"),
-                                got: format!("{} != {}", k1, k2),
-                            });
-                        }
-                        Shape::matrix(*m, *n)
-                    }
-                    _ => Shape::Unknown,
-                };
+                let shape = left.shape.matmul_with(&right.shape)?;
                 
                 Ok(TypedExpr {
                     expr: TypedExprKind::MatMul(Box::new(left), Box::new(right)),
@@ -301,6 +767,30 @@ impl Analyzer {
         }
     }
     
+    /// Reject matrices larger than `max_elements` before anything is allocated
+    fn check_size(&self, what: &str, rows: usize, cols: usize) -> CompileResult<()> {
+        match rows.checked_mul(cols) {
+            Some(elements) if elements <= self.max_elements => Ok(()),
+            elements => Err(CompileError::invalid_op(format!(
+                "Matrix '{}' ({}x{}) has {} elements, exceeding the limit of {}",
+                what,
+                rows,
+                cols,
+                elements.map_or_else(|| "too many".to_string(), |e| e.to_string()),
+                self.max_elements
+            ))),
+        }
+    }
+    
+    /// Fail with `error` in strict mode, otherwise record `warning` and carry on
+    fn strict_check(&mut self, error: CompileError, warning: String) -> CompileResult<()> {
+        if self.strict {
+            return Err(error);
+        }
+        self.warnings.push(warning);
+        Ok(())
+    }
+    
     /// Extract a shape tuple from an expression
     fn extract_shape(&self, expr: &Expr) -> CompileResult<(usize, usize)> {
         match expr {
@@ -313,72 +803,541 @@ impl Analyzer {
         }
     }
     
-    /// Extract a numeric value from an expression
-    fn extract_number(&self, expr: &Expr) -> CompileResult<f64> {
+    /// Extract an axes tuple such as `(1, 0)` from an expression
+    fn extract_axes(&self, expr: &Expr) -> CompileResult<Vec<usize>> {
         match expr {
-            Expr::Scalar(n) => Ok(*n),
-            _ => Err(CompileError::type_error("Expected numeric value")),
+            Expr::Tuple(elements) => elements.iter()
+                .map(|e| {
+                    let n = self.extract_number(e)?;
+                    if n < 0.0 || n.fract() != 0.0 {
+                        return Err(CompileError::type_error(format!("Invalid axis: {}", n)));
+                    }
+                    Ok(n as usize)
+                })
+                .collect(),
+            _ => Err(CompileError::type_error("Expected axes tuple, e.g. (1, 0)")),
         }
     }
     
-    /// Check if two shapes are broadcast compatible
-    fn check_broadcast_compatible(&self, left: &Shape, right: &Shape) -> CompileResult<Shape> {
-        match (left, right) {
-            (Shape::Scalar, other) | (other, Shape::Scalar) => Ok(other.clone()),
-            (Shape::Matrix { rows: r1, cols: c1 }, Shape::Matrix { rows: r2, cols: c2 }) => {
-                if r1 == r2 && c1 == c2 {
-                    Ok(Shape::matrix(*r1, *c1))
-                } else {
-                    Err(CompileError::ShapeMismatch {
-                        expected: format!("matching shapes for broadcast"),
-                        got: format!("({}, {}) and ({}, {})", r1, c1, r2, c2),
-                    })
-                }
+    /// Analyze `np.split(A, sections[, axis])[part]`
+    fn analyze_split(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        kwargs: &KeywordArgs,
+        subscripts: &[Subscript],
+    ) -> CompileResult<TypedExpr> {
+        if name != "np.split" {
+            // Axis-fixed wrappers take no axis argument
+            if !kwargs.is_empty() || args.len() != 2 {
+                return Err(CompileError::type_error(format!("{} expects (array, sections)", name)));
             }
-            (Shape::Unknown, other) | (other, Shape::Unknown) => Ok(other.clone()),
+        } else if kwargs.iter().any(|(key, _)| key != "axis") || !(2..=3).contains(&args.len()) {
+            return Err(CompileError::type_error("np.split expects (array, sections[, axis])"));
         }
-    }
-}
-
-impl Default for Analyzer {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parser::Parser;
-    
-    #[test]
-    fn test_analyze_matmul() {
-        let mut parser = Parser::new("C = A @ B");
-        let program = parser.parse_program().unwrap();
+        let axis_arg = args.get(2).or_else(|| kwargs.first().map(|(_, value)| value));
+        let axis = match axis_arg {
+            Some(expr) => self.extract_number(expr)? as i64,
+            None if name == "np.hsplit" => 1,
+            None => 0,
+        };
+        let sections = self.extract_number(&args[1])?;
+        if sections < 1.0 || sections.fract() != 0.0 {
+            return Err(CompileError::type_error(format!("Invalid number of sections: {}", sections)));
+        }
+        let sections = sections as usize;
         
-        let mut analyzer = Analyzer::new();
-        analyzer.define_matrix("A", (2, 3));
-        analyzer.define_matrix("B", (3, 4));
+        let source = self.analyze_expr(&args[0])?;
+        if name == "np.vsplit" && source.shape.is_vector() {
+            return Err(CompileError::type_error("np.vsplit needs a 2-D array"));
+        }
+        // As in NumPy, hsplit of a 1-D array splits its only axis
+        let axis = if name == "np.hsplit" && source.shape.is_vector() { 0 } else { axis };
+        let (len, axis) = match (&source.shape, axis) {
+            (Shape::Matrix { rows, .. }, 0) => (*rows, 0),
+            (Shape::Matrix { cols, .. }, 1) => (*cols, 1),
+            (Shape::Vector(len), 0) => (*len, 0),
+            (Shape::Unknown, _) => {
+                return Err(CompileError::type_error("Cannot split an operand of unknown shape"));
+            }
+            (shape, a) => {
+                return Err(CompileError::type_error(format!("Invalid axis {} for shape {}", a, shape)));
+            }
+        };
+        if len % sections != 0 {
+            return Err(CompileError::invalid_op(format!(
+                "Axis {} of length {} does not split into {} equal parts",
+                axis, len, sections
+            )));
+        }
         
-        let typed = analyzer.analyze(program).unwrap();
+        let [Subscript::Index(i)] = subscripts else {
+            return Err(CompileError::type_error(format!("Select one {} result with a single index", name)));
+        };
+        let part = if *i < 0 { i + sections as i64 } else { *i };
+        if part < 0 || part >= sections as i64 {
+            return Err(CompileError::invalid_op(format!(
+                "Index {} is out of bounds for {} split sections",
+                i, sections
+            )));
+        }
         
-        assert_eq!(typed.statements.len(), 1);
-        let stmt = &typed.statements[0];
-        assert_eq!(stmt.target, "C");
-        assert_eq!(stmt.value.shape, Shape::matrix(2, 4));
+        let shape = match source.shape {
+            Shape::Matrix { rows, cols } if axis == 0 => Shape::matrix(rows / sections, cols),
+            Shape::Matrix { rows, cols } => Shape::matrix(rows, cols / sections),
+            _ => Shape::Vector(len / sections),
+        };
+        
+        Ok(TypedExpr {
+            expr: TypedExprKind::Split { source: Box::new(source), axis, sections, part: part as usize },
+            shape,
+        })
     }
     
-    #[test]
-    fn test_analyze_matmul_error() {
-        let mut parser = Parser::new("C = A @ B");
-        let program = parser.parse_program().unwrap();
+    /// `np.append(A, B[, axis])`: concatenation along `axis`, or of both flattened when omitted
+    fn analyze_append(&mut self, args: &[Expr], kwargs: &KeywordArgs) -> CompileResult<TypedExpr> {
+        if !(2..=3).contains(&args.len()) || (args.len() == 3 && !kwargs.is_empty()) {
+            return Err(CompileError::type_error("np.append expects (array, values[, axis])"));
+        }
+        let axis_arg = args.get(2).or_else(|| kwargs.first().map(|(_, value)| value));
+        let left = self.analyze_expr(&args[0])?;
+        let right = self.analyze_expr(&args[1])?;
         
-        let mut analyzer = Analyzer::new();
-        analyzer.define_matrix("A", (2, 3));
-        analyzer.define_matrix("B", (4, 5)); // Incompatible dimensions
+        let Some(axis_arg) = axis_arg else {
+            // Without an axis NumPy flattens both operands first
+            let flatten = |expr: TypedExpr| -> CompileResult<(TypedExpr, usize)> {
+                let len = match expr.shape {
+                    Shape::Matrix { rows, cols } => rows * cols,
+                    Shape::Vector(len) => len,
+                    Shape::Scalar => 1,
+                    Shape::Unknown => {
+                        return Err(CompileError::type_error("Cannot append an operand of unknown shape"));
+                    }
+                };
+                Ok((TypedExpr { expr: TypedExprKind::Reshape(Box::new(expr)), shape: Shape::Vector(len) }, len))
+            };
+            let (left, left_len) = flatten(left)?;
+            let (right, right_len) = flatten(right)?;
+            return Ok(TypedExpr {
+                expr: TypedExprKind::Concat { left: Box::new(left), right: Box::new(right), axis: 0 },
+                shape: Shape::Vector(left_len + right_len),
+            });
+        };
         
-        let result = analyzer.analyze(program);
-        assert!(result.is_err());
+        let axis = self.extract_number(axis_arg)? as i64;
+        self.concat("append", left, right, axis)
+    }
+    
+    /// `np.concatenate((A, B, ...)[, axis])`, `np.hstack((A, B, ...))` and `np.vstack((A, B, ...))`
+    ///
+    /// Parts are joined pairwise from the left. `np.vstack` treats vectors as rows;
+    /// `np.hstack` joins vectors end to end and matrices along axis 1.
+    fn analyze_concatenate(&mut self, name: &str, args: &[Expr], kwargs: &KeywordArgs) -> CompileResult<TypedExpr> {
+        let max_args = if name == "np.concatenate" { 2 } else { 1 };
+        if args.is_empty() || args.len() > max_args || (args.len() == 2 && !kwargs.is_empty()) {
+            let axis = if max_args == 2 { "[, axis]" } else { "" };
+            return Err(CompileError::type_error(format!("{} expects (arrays{})", name, axis)));
+        }
+        let Expr::Tuple(elements) = &args[0] else {
+            return Err(CompileError::type_error(format!("{} expects a tuple of arrays, e.g. {}((A, B))", name, name)));
+        };
+        let mut parts = elements.iter()
+            .map(|element| self.analyze_expr(element))
+            .collect::<CompileResult<Vec<_>>>()?;
+        
+        let axis = match name {
+            "np.vstack" => {
+                parts = parts.into_iter()
+                    .map(|part| match part.shape {
+                        Shape::Vector(len) => TypedExpr {
+                            expr: TypedExprKind::Reshape(Box::new(part)),
+                            shape: Shape::matrix(1, len),
+                        },
+                        _ => part,
+                    })
+                    .collect();
+                0
+            }
+            "np.hstack" if parts.first().is_some_and(|part| part.shape.is_vector()) => 0,
+            "np.hstack" => 1,
+            _ => match args.get(1).or_else(|| kwargs.first().map(|(_, value)| value)) {
+                Some(axis) => self.extract_number(axis)? as i64,
+                None => 0,
+            },
+        };
+        
+        let mut parts = parts.into_iter();
+        let first = parts.next()
+            .ok_or_else(|| CompileError::type_error(format!("{} needs at least one array", name)))?;
+        parts.try_fold(first, |left, right| self.concat("concatenate", left, right, axis))
+    }
+    
+    /// Join two operands along `axis`, checking that every other dimension matches
+    fn concat(&self, verb: &str, left: TypedExpr, right: TypedExpr, axis: i64) -> CompileResult<TypedExpr> {
+        let shape = match (&left.shape, &right.shape, axis) {
+            (Shape::Matrix { rows: r1, cols: c1 }, Shape::Matrix { rows: r2, cols: c2 }, 0) if c1 == c2 => {
+                Shape::matrix(r1 + r2, *c1)
+            }
+            (Shape::Matrix { rows: r1, cols: c1 }, Shape::Matrix { rows: r2, cols: c2 }, 1) if r1 == r2 => {
+                Shape::matrix(*r1, c1 + c2)
+            }
+            (Shape::Vector(l1), Shape::Vector(l2), 0) => Shape::Vector(l1 + l2),
+            (Shape::Unknown, _, _) | (_, Shape::Unknown, _) => {
+                return Err(CompileError::type_error(format!("Cannot {} an operand of unknown shape", verb)));
+            }
+            (l, r, 0 | 1) if l.is_matrix() && r.is_matrix() => {
+                return Err(CompileError::ShapeMismatch {
+                    expected: format!("all dimensions except axis {} to match", axis),
+                    got: format!("{} and {}", l, r),
+                });
+            }
+            (l, r, _) => {
+                return Err(CompileError::type_error(format!(
+                    "Cannot {} {} and {} along axis {}",
+                    verb, l, r, axis
+                )));
+            }
+        };
+        if let Some((rows, cols)) = shape.dimensions() {
+            self.check_size(&format!("np.{}", verb), rows, cols)?;
+        }
+        
+        Ok(TypedExpr {
+            expr: TypedExprKind::Concat { left: Box::new(left), right: Box::new(right), axis: axis as usize },
+            shape,
+        })
+    }
+    
+    /// `np.roll(A, shift[, axis])`: cyclic shift along `axis`, or over the flattened array
+    fn analyze_roll(&mut self, args: &[Expr], kwargs: &KeywordArgs) -> CompileResult<TypedExpr> {
+        if !(2..=3).contains(&args.len()) || (args.len() == 3 && !kwargs.is_empty()) {
+            return Err(CompileError::type_error("np.roll expects (array, shift[, axis])"));
+        }
+        let shift = self.extract_number(&args[1])?;
+        if shift.fract() != 0.0 {
+            return Err(CompileError::type_error(format!("np.roll shift must be an integer, got {}", shift)));
+        }
+        let source = self.analyze_expr(&args[0])?;
+        
+        let axis = match args.get(2).or_else(|| kwargs.first().map(|(_, value)| value)) {
+            Some(expr) => {
+                let axis = self.extract_number(expr)? as i64;
+                let ndim = match source.shape {
+                    Shape::Matrix { .. } => 2,
+                    Shape::Vector(_) => 1,
+                    Shape::Scalar => 0,
+                    Shape::Unknown => {
+                        return Err(CompileError::type_error("Cannot roll an operand of unknown shape along an axis"));
+                    }
+                };
+                let normalized = if axis < 0 { axis + ndim } else { axis };
+                if normalized < 0 || normalized >= ndim {
+                    return Err(CompileError::type_error(format!(
+                        "Invalid axis {} for shape {}",
+                        axis, source.shape
+                    )));
+                }
+                Some(normalized as usize)
+            }
+            None => None,
+        };
+        
+        let shape = source.shape.clone();
+        Ok(TypedExpr {
+            expr: TypedExprKind::Roll { source: Box::new(source), shift: shift as i64, axis },
+            shape,
+        })
+    }
+    
+    /// `np.linalg.norm(A[, axis=...])`: L2 norm of a vector, Frobenius norm of a matrix,
+    /// or the norms along one axis (`axis=1` gives one per row)
+    fn analyze_norm(&mut self, args: &[Expr], kwargs: &KeywordArgs) -> CompileResult<TypedExpr> {
+        if args.len() != 1 {
+            return Err(CompileError::type_error("np.linalg.norm expects (array[, axis=...])"));
+        }
+        let source = self.analyze_expr(&args[0])?;
+        
+        let Some((_, axis_arg)) = kwargs.first() else {
+            return Ok(TypedExpr {
+                expr: TypedExprKind::Norm { source: Box::new(source), axis: None },
+                shape: Shape::Scalar,
+            });
+        };
+        let axis = self.extract_number(axis_arg)? as i64;
+        let (axis, shape) = match (&source.shape, axis) {
+            (Shape::Matrix { cols, .. }, 0 | -2) => (Some(0), Shape::Vector(*cols)),
+            (Shape::Matrix { rows, .. }, 1 | -1) => (Some(1), Shape::Vector(*rows)),
+            // A vector has one axis, so its norm along it is the whole norm
+            (Shape::Vector(_), 0 | -1) => (None, Shape::Scalar),
+            (Shape::Unknown, _) => {
+                return Err(CompileError::type_error("Cannot take the norm of an operand of unknown shape along an axis"));
+            }
+            (shape, axis) => {
+                return Err(CompileError::type_error(format!("Invalid axis {} for shape {}", axis, shape)));
+            }
+        };
+        
+        Ok(TypedExpr {
+            expr: TypedExprKind::Norm { source: Box::new(source), axis },
+            shape,
+        })
+    }
+    
+    /// `np.expand_dims(A, axis)` and `np.squeeze(A[, axis])`: add or drop length-1 axes
+    ///
+    /// Only up to two dimensions exist, so expanding works on vectors and scalars, and
+    /// squeezing leaves a vector or scalar. Both are row-major views of the same data.
+    fn analyze_unit_axes(&mut self, name: &str, args: &[Expr], kwargs: &KeywordArgs) -> CompileResult<TypedExpr> {
+        let expand = name == "np.expand_dims";
+        let arity = if expand { 2..=2 } else { 1..=2 };
+        if !arity.contains(&(args.len() + kwargs.len())) {
+            return Err(CompileError::type_error(if expand {
+                "np.expand_dims expects (array, axis)"
+            } else {
+                "np.squeeze expects (array[, axis])"
+            }));
+        }
+        let axis = match args.get(1).or_else(|| kwargs.first().map(|(_, value)| value)) {
+            Some(expr) => Some(self.extract_number(expr)? as i64),
+            None => None,
+        };
+        let source = self.analyze_expr(&args[0])?;
+        
+        let invalid_axis = |axis: i64| CompileError::type_error(format!(
+            "Invalid axis {} for {} of shape {}",
+            axis, name, source.shape
+        ));
+        let shape = match (expand, &source.shape, axis) {
+            (_, Shape::Unknown, _) => {
+                return Err(CompileError::type_error(format!("Cannot apply {} to an operand of unknown shape", name)));
+            }
+            (true, Shape::Vector(len), Some(0 | -2)) => Shape::matrix(1, *len),
+            (true, Shape::Vector(len), Some(1 | -1)) => Shape::matrix(*len, 1),
+            (true, Shape::Scalar, Some(0 | -1)) => Shape::Vector(1),
+            (true, Shape::Matrix { .. }, _) => {
+                return Err(CompileError::type_error("np.expand_dims of a 2-D array would need 3 dimensions"));
+            }
+            (true, _, axis) => return Err(invalid_axis(axis.unwrap_or(0))),
+            (false, Shape::Matrix { rows, cols }, None) => match (rows, cols) {
+                (1, 1) => Shape::Scalar,
+                (1, n) | (n, 1) => Shape::Vector(*n),
+                _ => source.shape.clone(),
+            },
+            (false, Shape::Matrix { rows: 1, cols }, Some(0 | -2)) => Shape::Vector(*cols),
+            (false, Shape::Matrix { rows, cols: 1 }, Some(1 | -1)) => Shape::Vector(*rows),
+            (false, Shape::Vector(1), None | Some(0 | -1)) => Shape::Scalar,
+            (false, Shape::Vector(_) | Shape::Scalar, None) => source.shape.clone(),
+            (false, Shape::Matrix { .. }, Some(axis @ -2..=1)) | (false, Shape::Vector(_), Some(axis @ -1..=0)) => {
+                return Err(CompileError::invalid_op(format!(
+                    "Cannot squeeze axis {} of shape {}: its length is not 1",
+                    axis, source.shape
+                )));
+            }
+            (false, _, axis) => return Err(invalid_axis(axis.unwrap_or(0))),
+        };
+        
+        Ok(TypedExpr {
+            expr: TypedExprKind::Reshape(Box::new(source)),
+            shape,
+        })
+    }
+    
+    /// Extract a numeric value from an expression
+    fn extract_number(&self, expr: &Expr) -> CompileResult<f64> {
+        match expr {
+            Expr::Scalar(n) => Ok(*n),
+            _ => Err(CompileError::type_error("Expected numeric value")),
+        }
+    }
+    
+    /// Normalize subscripts against the indexed shape and infer the result shape
+    fn resolve_subscripts(
+        &self,
+        shape: &Shape,
+        subscripts: &[Subscript],
+    ) -> CompileResult<(Vec<Subscript>, Shape)> {
+        match shape {
+            Shape::Unknown => {
+                if subscripts.len() > 2 {
+                    return Err(CompileError::type_error(format!(
+                        "Too many indices: expected at most 2, got {}",
+                        subscripts.len()
+                    )));
+                }
+                Ok((subscripts.to_vec(), Shape::Unknown))
+            }
+            Shape::Scalar => Err(CompileError::type_error("Cannot index a scalar")),
+            Shape::Vector(len) => {
+                if subscripts.len() != 1 {
+                    return Err(CompileError::type_error(format!(
+                        "Too many indices for vector: expected 1, got {}",
+                        subscripts.len()
+                    )));
+                }
+                let sub = Self::normalize_subscript(&subscripts[0], *len, 0)?;
+                let shape = match &sub {
+                    Subscript::Index(_) => Shape::Scalar,
+                    Subscript::Slice { .. } => Shape::Vector(sub.indices(*len).len()),
+                };
+                Ok((vec![sub], shape))
+            }
+            Shape::Matrix { rows, cols } => {
+                if subscripts.len() > 2 {
+                    return Err(CompileError::type_error(format!(
+                        "Too many indices for matrix: expected at most 2, got {}",
+                        subscripts.len()
+                    )));
+                }
+                let row = Self::normalize_subscript(&subscripts[0], *rows, 0)?;
+                let col = match subscripts.get(1) {
+                    Some(sub) => Self::normalize_subscript(sub, *cols, 1)?,
+                    None => Self::normalize_subscript(&Subscript::full(), *cols, 1)?,
+                };
+                
+                let n_rows = row.indices(*rows).len();
+                let n_cols = col.indices(*cols).len();
+                let shape = match (&row, &col) {
+                    (Subscript::Index(_), Subscript::Index(_)) => Shape::Scalar,
+                    (Subscript::Index(_), Subscript::Slice { .. }) => Shape::Vector(n_cols),
+                    (Subscript::Slice { .. }, Subscript::Index(_)) => Shape::Vector(n_rows),
+                    (Subscript::Slice { .. }, Subscript::Slice { .. }) => Shape::matrix(n_rows, n_cols),
+                };
+                Ok((vec![row, col], shape))
+            }
+        }
+    }
+    
+    /// Resolve negative indices and default/clamped slice bounds, bounds-checking indices
+    fn normalize_subscript(sub: &Subscript, len: usize, axis: usize) -> CompileResult<Subscript> {
+        let len = len as i64;
+        match sub {
+            Subscript::Index(i) => {
+                let idx = if *i < 0 { i + len } else { *i };
+                if idx < 0 || idx >= len {
+                    return Err(CompileError::invalid_op(format!(
+                        "Index {} is out of bounds for axis {} with size {}",
+                        i, axis, len
+                    )));
+                }
+                Ok(Subscript::Index(idx))
+            }
+            Subscript::Slice { start, stop, step } => {
+                let step = step.unwrap_or(1);
+                if step == 0 {
+                    return Err(CompileError::invalid_op("Slice step cannot be zero"));
+                }
+                
+                // Same bounds rules as Python: a negative step walks down from the end
+                let (lower, upper) = if step > 0 { (0, len) } else { (-1, len - 1) };
+                let clamp = |b: i64| (if b < 0 { b + len } else { b }).clamp(lower, upper);
+                let start = start.map_or(if step > 0 { lower } else { upper }, clamp);
+                let stop = stop.map_or(if step > 0 { upper } else { lower }, clamp);
+                Ok(Subscript::Slice { start: Some(start), stop: Some(stop), step: Some(step) })
+            }
+        }
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `base ** exponent` (at least 1) by repeated squaring, binding each square once
+///
+/// `A ** 6` becomes `let pow1 = A in let pow2 = pow1 @ pow1 in let pow4 = pow2 @ pow2 in pow2 @ pow4`.
+fn matrix_power(base: TypedExpr, exponent: u32) -> TypedExpr {
+    let shape = base.shape.clone();
+    let power = |k: u32| TypedExpr { expr: TypedExprKind::Variable(format!("pow{}", 1u64 << k)), shape: shape.clone() };
+    let product = |l: TypedExpr, r: TypedExpr| TypedExpr {
+        expr: TypedExprKind::MatMul(Box::new(l), Box::new(r)),
+        shape: shape.clone(),
+    };
+    
+    let highest = u32::BITS - 1 - exponent.leading_zeros();
+    let mut body = (0..=highest)
+        .filter(|k| exponent & (1 << k) != 0)
+        .map(power)
+        .reduce(product)
+        .expect("exponent is at least 1");
+    for k in (1..=highest).rev() {
+        body = TypedExpr {
+            expr: TypedExprKind::Let {
+                name: format!("pow{}", 1u64 << k),
+                value: Box::new(product(power(k - 1), power(k - 1))),
+                body: Box::new(body),
+            },
+            shape: shape.clone(),
+        };
+    }
+    TypedExpr {
+        expr: TypedExprKind::Let { name: "pow1".to_string(), value: Box::new(base), body: Box::new(body) },
+        shape,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    
+    /// Typed value of the first statement of `source`
+    fn first_value(analyzer: &mut Analyzer, source: &str) -> CompileResult<TypedExpr> {
+        let program = Parser::new(source).parse_program().unwrap();
+        analyzer.analyze(program).map(|typed| typed.statements[0].value.clone())
+    }
+    
+    /// Shape of the first statement of `source`
+    fn shape_of(analyzer: &mut Analyzer, source: &str) -> CompileResult<Shape> {
+        first_value(analyzer, source).map(|value| value.shape)
+    }
+    
+    #[test]
+    fn test_analyze_matmul() {
+        let mut parser = Parser::new("C = A @ B");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 4));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        
+        assert_eq!(typed.statements.len(), 1);
+        let stmt = &typed.statements[0];
+        assert_eq!(stmt.target, "C");
+        assert_eq!(stmt.value.shape, Shape::matrix(2, 4));
+    }
+    
+    #[test]
+    fn test_analyze_matmul_error() {
+        let mut parser = Parser::new("C = A @ B");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (4, 5)); // Incompatible dimensions
+        
+        let result = analyzer.analyze(program);
+        assert!(result.is_err());
+    }
+    
+    #[test]
+    fn test_analyze_division() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (2, 3));
+        analyzer.define_matrix("C", (3, 2));
+        
+        let quotient = first_value(&mut analyzer, "D = A / B").unwrap();
+        assert_eq!(quotient.shape, Shape::matrix(2, 3));
+        assert!(matches!(quotient.expr, TypedExprKind::Div(..)));
+        assert_eq!(shape_of(&mut analyzer, "D = A / 2").unwrap(), Shape::matrix(2, 3));
+        assert_eq!(shape_of(&mut analyzer, "D = 1 / A").unwrap(), Shape::matrix(2, 3));
+        assert_eq!(shape_of(&mut analyzer, "D = A / A[0]").unwrap(), Shape::matrix(2, 3));
+        
+        assert!(shape_of(&mut analyzer, "D = A / C").is_err());
     }
     
     #[test]
@@ -393,4 +1352,530 @@ mod tests {
         
         assert_eq!(typed.statements[0].value.shape, Shape::matrix(3, 2));
     }
+    
+    #[test]
+    fn test_analyze_transpose_axes() {
+        let mut parser = Parser::new("B = np.transpose(A, (1, 0)); C = np.transpose(A, (0, 1))");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(3, 2));
+        assert!(matches!(typed.statements[0].value.expr, TypedExprKind::Transpose(_)));
+        assert_eq!(typed.statements[1].value.shape, Shape::matrix(2, 3));
+        assert!(matches!(typed.statements[1].value.expr, TypedExprKind::Variable(_)));
+    }
+    
+    #[test]
+    fn test_analyze_transpose_invalid_axes() {
+        let mut parser = Parser::new("B = np.transpose(A, (1, 1))");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        assert!(analyzer.analyze(program).is_err());
+    }
+    
+    #[test]
+    fn test_analyze_out_parameter() {
+        let mut parser = Parser::new("np.matmul(A, B, out=C)");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 4));
+        analyzer.define_matrix("C", (2, 4));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        assert!(matches!(&typed.statements[0].value.expr, TypedExprKind::Out(buffer, _) if buffer == "C"));
+        
+        let mut parser = Parser::new("np.matmul(A, B, out=C)");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 4));
+        analyzer.define_matrix("C", (4, 2)); // Wrong shape
+        
+        let result = analyzer.analyze(program);
+        assert!(matches!(result, Err(CompileError::ShapeMismatch { .. })));
+    }
+    
+    #[test]
+    fn test_analyze_flatten() {
+        let mut parser = Parser::new("v = A.flatten(); w = np.ravel(A.T)");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        
+        assert_eq!(typed.statements[0].value.shape, Shape::Vector(6));
+        assert!(matches!(typed.statements[0].value.expr, TypedExprKind::Reshape(_)));
+        assert_eq!(typed.statements[1].value.shape, Shape::Vector(6));
+    }
+    
+    #[test]
+    fn test_analyze_self_reference_without_prior_value() {
+        let mut parser = Parser::new("A = A @ B");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("B", (3, 3));
+        
+        let result = analyzer.analyze(program);
+        assert!(matches!(result, Err(CompileError::InvalidOperation { .. })));
+    }
+    
+    #[test]
+    fn test_analyze_update_of_defined_matrix() {
+        let mut parser = Parser::new("A = A @ B");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 4));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(2, 4));
+        assert_eq!(analyzer.shapes()["A"], Shape::matrix(2, 4));
+    }
+    
+    #[test]
+    fn test_analyze_rejects_oversized_matrix() {
+        let mut parser = Parser::new("C = A @ B");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (100000, 100000));
+        analyzer.define_matrix("B", (100000, 3));
+        
+        let err = analyzer.analyze(program).unwrap_err();
+        assert!(err.to_string().contains("'A'"));
+        assert!(err.to_string().contains("10000000000 elements"));
+        
+        // np.zeros is rejected before its data is built
+        let mut parser = Parser::new("Z = np.zeros((1000, 1000))");
+        let program = parser.parse_program().unwrap();
+        let mut analyzer = Analyzer::new().with_max_elements(1000);
+        assert!(analyzer.analyze(program).is_err());
+    }
+    
+    #[test]
+    fn test_analyze_zeros_like() {
+        let mut parser = Parser::new("Z = np.zeros_like(A); O = np.ones_like(A[0])");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(2, 3));
+        assert_eq!(typed.statements[0].value.expr, TypedExprKind::Matrix(vec![vec![0.0; 3]; 2]));
+        assert_eq!(typed.statements[1].value.shape, Shape::Vector(3));
+        assert_eq!(typed.statements[1].value.expr, TypedExprKind::Matrix(vec![vec![1.0; 3]]));
+        
+        let mut parser = Parser::new("Z = np.zeros_like(X)");
+        let program = parser.parse_program().unwrap();
+        assert!(Analyzer::new().analyze(program).is_err());
+    }
+    
+    #[test]
+    fn test_analyze_row_index() {
+        let mut parser = Parser::new("r = A[0]; s = A[-1, :]");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        
+        assert_eq!(typed.statements[0].value.shape, Shape::Vector(3));
+        assert_eq!(typed.statements[1].value.shape, Shape::Vector(3));
+        if let TypedExprKind::Index(_, subscripts) = &typed.statements[1].value.expr {
+            assert_eq!(subscripts[0], Subscript::Index(1));
+        } else {
+            panic!("Expected Index expression");
+        }
+    }
+    
+    #[test]
+    fn test_analyze_column_index() {
+        let mut parser = Parser::new("c = A[:, 0]");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        let typed = analyzer.analyze(program).unwrap();
+        
+        assert_eq!(typed.statements[0].value.shape, Shape::Vector(2));
+    }
+    
+    #[test]
+    fn test_analyze_index_out_of_bounds() {
+        let mut parser = Parser::new("c = A[:, 3]");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        assert!(analyzer.analyze(program).is_err());
+    }
+    
+    #[test]
+    fn test_analyze_strided_slice() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (5, 4));
+        
+        let program = Parser::new("B = A[::-1, ::2]; C = A[1::-1]").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(5, 2));
+        assert_eq!(typed.statements[1].value.shape, Shape::matrix(2, 4));
+        
+        let program = Parser::new("D = A[::0]").parse_program().unwrap();
+        let message = analyzer.analyze(program).unwrap_err().to_string();
+        assert!(message.contains("step cannot be zero"), "{}", message);
+    }
+    
+    #[test]
+    fn test_analyze_split() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (4, 2));
+        
+        let program = Parser::new("P = np.split(A, 2)[1]").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(2, 2));
+        
+        for bad in ["P = np.split(A, 3)[0]", "P = np.split(A, 2)", "P = np.split(A, 2)[2]"] {
+            let program = Parser::new(bad).parse_program().unwrap();
+            assert!(analyzer.analyze(program).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_append() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (1, 3));
+        
+        assert_eq!(shape_of(&mut analyzer, "C = np.append(A, B, axis=0)").unwrap(), Shape::matrix(3, 3));
+        assert_eq!(shape_of(&mut analyzer, "C = np.append(A, A, 1)").unwrap(), Shape::matrix(2, 6));
+        assert_eq!(shape_of(&mut analyzer, "C = np.append(A, B)").unwrap(), Shape::Vector(9));
+        
+        assert!(matches!(
+            shape_of(&mut analyzer, "C = np.append(A, B, axis=1)"),
+            Err(CompileError::ShapeMismatch { .. })
+        ));
+        assert!(shape_of(&mut analyzer, "C = np.append(A, B, axis=2)").is_err());
+    }
+    
+    #[test]
+    fn test_analyze_concatenate() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (1, 3));
+        
+        assert_eq!(shape_of(&mut analyzer, "C = np.vstack((A, B))").unwrap(), Shape::matrix(3, 3));
+        assert_eq!(shape_of(&mut analyzer, "C = np.concatenate((A, B, A))").unwrap(), Shape::matrix(5, 3));
+        assert_eq!(shape_of(&mut analyzer, "C = np.concatenate((A, A), axis=1)").unwrap(), Shape::matrix(2, 6));
+        assert_eq!(shape_of(&mut analyzer, "C = np.hstack((A, A, A))").unwrap(), Shape::matrix(2, 9));
+        assert_eq!(shape_of(&mut analyzer, "C = np.vstack((A[0], A[1]))").unwrap(), Shape::matrix(2, 3));
+        
+        assert!(matches!(
+            shape_of(&mut analyzer, "C = np.hstack((A, B))"),
+            Err(CompileError::ShapeMismatch { .. })
+        ));
+        assert!(matches!(
+            shape_of(&mut analyzer, "C = np.vstack((A, A.T))"),
+            Err(CompileError::ShapeMismatch { .. })
+        ));
+        assert!(shape_of(&mut analyzer, "C = np.vstack(A)").is_err());
+    }
+    
+    #[test]
+    fn test_analyze_roll() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        assert_eq!(shape_of(&mut analyzer, "B = np.roll(A, 1, axis=1)").unwrap(), Shape::matrix(2, 3));
+        assert_eq!(shape_of(&mut analyzer, "B = np.roll(A, -5)").unwrap(), Shape::matrix(2, 3));
+        assert_eq!(shape_of(&mut analyzer, "B = np.roll(A[0], 1, -1)").unwrap(), Shape::Vector(3));
+        
+        for bad in ["B = np.roll(A, 1, axis=2)", "B = np.roll(A[0], 1, 1)", "B = np.roll(A, 0.5)", "B = np.roll(A)"] {
+            assert!(shape_of(&mut analyzer, bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_norm() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        assert_eq!(shape_of(&mut analyzer, "n = np.linalg.norm(A)").unwrap(), Shape::Scalar);
+        assert_eq!(shape_of(&mut analyzer, "n = np.linalg.norm(A[0])").unwrap(), Shape::Scalar);
+        assert_eq!(shape_of(&mut analyzer, "n = np.linalg.norm(A, axis=0)").unwrap(), Shape::Vector(3));
+        assert_eq!(shape_of(&mut analyzer, "n = np.linalg.norm(A, axis=-1)").unwrap(), Shape::Vector(2));
+        assert_eq!(shape_of(&mut analyzer, "n = np.linalg.norm(A[0], axis=0)").unwrap(), Shape::Scalar);
+        
+        for bad in ["n = np.linalg.norm(A, axis=2)", "n = np.linalg.norm(A[0], axis=1)", "n = np.linalg.norm(A, 2)", "n = np.linalg.norm(A, ord=2)"] {
+            assert!(shape_of(&mut analyzer, bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_reshape() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 6));
+        
+        for source in ["B = np.reshape(A, (3, 4))", "B = A.reshape((3, 4))", "B = A.reshape(3, 4)"] {
+            let program = Parser::new(source).parse_program().unwrap();
+            let typed = analyzer.analyze(program).unwrap();
+            assert_eq!(typed.statements[0].value.shape, Shape::matrix(3, 4), "{}", source);
+            assert!(matches!(typed.statements[0].value.expr, TypedExprKind::Reshape(_)));
+        }
+        
+        let program = Parser::new("B = np.reshape(A, (3, 3))").parse_program().unwrap();
+        let err = analyzer.analyze(program).unwrap_err();
+        assert!(matches!(err, CompileError::ShapeMismatch { .. }));
+        assert_eq!(err.to_string(), "Shape mismatch: expected 9 elements for shape (3, 3), got 12 elements in (2, 6)");
+    }
+    
+    #[test]
+    fn test_define_matrix_with_data() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix_with_data("A", (2, 3), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        analyzer.define_matrix("B", (3, 2));
+        let typed = analyzer.analyze(Parser::new("C = A @ B").parse_program().unwrap()).unwrap();
+        assert_eq!(typed.input_data["A"], vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        
+        // A's values reach the passes; B, given only a shape, is still zeros
+        let config = crate::SystolicConfig::new(3, 8, 32);
+        let tiled = crate::TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        let program = crate::CodeGenerator::new(config).generate(tiled).unwrap();
+        assert_eq!(program.passes[0].matrix_a, vec![1, 2, 3, 4, 5, 6, 0, 0, 0]);
+        assert!(program.passes[0].matrix_b.iter().all(|&v| v == 0));
+        
+        let err = analyzer.define_matrix_with_data("D", (2, 2), &[1.0, 2.0, 3.0]).unwrap_err();
+        assert_eq!(err.to_string(), "Shape mismatch: expected 4 values for 'D' (2x2), got 3 values");
+    }
+    
+    #[test]
+    fn test_analyze_matrix_power() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (3, 3));
+        analyzer.define_matrix("R", (2, 3));
+        
+        let analyze = |analyzer: &mut Analyzer, source: &str| {
+            let program = Parser::new(source).parse_program().unwrap();
+            analyzer.analyze(program).map(|typed| typed.statements[0].value.clone())
+        };
+        let identity = analyze(&mut analyzer, "B = A ** 0").unwrap();
+        assert_eq!(identity.shape, Shape::matrix(3, 3));
+        assert_eq!(identity.expr, TypedExprKind::Matrix(vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]]));
+        
+        let cube = analyze(&mut analyzer, "B = A ** 3").unwrap();
+        assert_eq!(cube.shape, Shape::matrix(3, 3));
+        let mut matmuls = 0;
+        cube.visit(&mut |expr| matmuls += matches!(expr.expr, TypedExprKind::MatMul(..)) as usize);
+        assert_eq!(matmuls, 2);
+        
+        assert!(matches!(analyze(&mut analyzer, "B = R ** 2"), Err(CompileError::ShapeMismatch { .. })));
+        assert!(matches!(analyze(&mut analyzer, "B = R ** 0"), Err(CompileError::ShapeMismatch { .. })));
+        assert!(analyze(&mut analyzer, "B = A[0] ** 2").is_err());
+    }
+    
+    #[test]
+    fn test_analyze_clip() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        let program = Parser::new("Y = np.clip(A, -1, 1.5)").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(2, 3));
+        assert!(matches!(typed.statements[0].value.expr, TypedExprKind::Clip { min, max, .. } if min == -1.0 && max == 1.5));
+        
+        for bad in ["Y = clip(A, 1, 0)", "Y = clip(A, 0)", "Y = clip(A, 0, A)"] {
+            let program = Parser::new(bad).parse_program().unwrap();
+            assert!(analyzer.analyze(program).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_diag() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        assert_eq!(shape_of(&mut analyzer, "D = np.diag(A[0])").unwrap(), Shape::matrix(3, 3));
+        // Rectangular matrices give the first min(rows, cols) diagonal entries, as in NumPy
+        assert_eq!(shape_of(&mut analyzer, "d = np.diag(A)").unwrap(), Shape::Vector(2));
+        assert_eq!(shape_of(&mut analyzer, "d = np.diag(A.T @ A)").unwrap(), Shape::Vector(3));
+        
+        for bad in ["d = np.diag(2)", "d = np.diag(A, A)"] {
+            assert!(shape_of(&mut analyzer, bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_outer() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        
+        assert_eq!(shape_of(&mut analyzer, "M = np.outer([1, 2, 3], [1, 2, 3, 4])").unwrap(), Shape::matrix(3, 4));
+        assert_eq!(shape_of(&mut analyzer, "M = np.outer(A[1], A[0])").unwrap(), Shape::matrix(3, 3));
+        
+        for bad in ["M = np.outer(A, A[0])", "M = np.outer(A[0])", "M = np.outer(A[0], 2)"] {
+            assert!(shape_of(&mut analyzer, bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_expand_dims_and_squeeze() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("R", (1, 4));
+        analyzer.define_matrix("C", (4, 1));
+        
+        // Expanding a vector gives a row or a column
+        assert_eq!(shape_of(&mut analyzer, "B = np.expand_dims(A[0], 0)").unwrap(), Shape::matrix(1, 3));
+        assert_eq!(shape_of(&mut analyzer, "B = np.expand_dims(A[0], axis=-1)").unwrap(), Shape::matrix(3, 1));
+        // Squeezing drops the length-1 axis
+        assert_eq!(shape_of(&mut analyzer, "v = np.squeeze(R)").unwrap(), Shape::Vector(4));
+        assert_eq!(shape_of(&mut analyzer, "v = np.squeeze(C, axis=1)").unwrap(), Shape::Vector(4));
+        assert_eq!(shape_of(&mut analyzer, "v = np.squeeze(A)").unwrap(), Shape::matrix(2, 3));
+        
+        for bad in ["B = np.expand_dims(A, 0)", "B = np.expand_dims(A[0], 2)", "v = np.squeeze(R, 1)", "v = np.squeeze(R, 3)"] {
+            assert!(shape_of(&mut analyzer, bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_hsplit_vsplit() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (4, 6));
+        
+        assert_eq!(shape_of(&mut analyzer, "P = np.hsplit(A, 3)[0]").unwrap(), Shape::matrix(4, 2));
+        assert_eq!(shape_of(&mut analyzer, "P = np.vsplit(A, 2)[1]").unwrap(), Shape::matrix(2, 6));
+        assert_eq!(shape_of(&mut analyzer, "P = np.hsplit(A[0], 2)[1]").unwrap(), Shape::Vector(3));
+        
+        for bad in ["P = np.hsplit(A, 4)[0]", "P = np.vsplit(A, 2, 1)[0]", "P = np.vsplit(A[0], 2)[0]"] {
+            assert!(shape_of(&mut analyzer, bad).is_err(), "{}", bad);
+        }
+    }
+    
+    #[test]
+    fn test_analyze_let_is_scoped() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 4));
+        analyzer.define_matrix("t", (5, 5));
+        
+        let program = Parser::new("C = let t = A @ B in t + t").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(2, 4));
+        
+        // The outer t is visible again after the body, and u is not visible at all
+        assert_eq!(analyzer.shapes()["t"], Shape::matrix(5, 5));
+        let program = Parser::new("D = let u = A in u; E = u").parse_program().unwrap();
+        assert!(matches!(analyzer.analyze(program), Err(CompileError::UndefinedVariable { name }) if name == "u"));
+    }
+    
+    #[test]
+    fn test_analyze_def_inlines_at_call_site() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("W1", (4, 8));
+        analyzer.define_matrix("W2", (8, 2));
+        analyzer.define_matrix("X", (3, 4));
+        
+        let source = "def ffn(x) = relu(x @ W1) @ W2; Y = ffn(X); Z = ffn(ffn(X) @ W2.T @ W1.T)";
+        let typed = analyzer.analyze(Parser::new(source).parse_program().unwrap()).unwrap();
+        assert_eq!(typed.statements.len(), 2);
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(3, 2));
+        assert_eq!(typed.matmul_count(), 2 + 6);
+        
+        let analyze = |source: &str| Analyzer::new().analyze(Parser::new(source).parse_program().unwrap());
+        let err = analyze("def f(x) = g(x); def g(x) = f(x) @ x; Y = f([[1]])").unwrap_err();
+        assert!(err.to_string().contains("'f' is recursive (f -> g -> f)"), "{}", err);
+        assert!(analyze("def f(x) = x; Y = f([[1]], [[2]])").is_err());
+        assert!(analyze("def f(x, x) = x").is_err());
+    }
+    
+    #[test]
+    fn test_undefined_variable() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        let result = analyzer.analyze(Parser::new("C = A @ B").parse_program().unwrap());
+        assert!(matches!(result, Err(CompileError::UndefinedVariable { name }) if name == "B"));
+        
+        // Earlier assignments define names for later statements, but not the reverse
+        analyzer.analyze(Parser::new("B = A.T; C = A @ B").parse_program().unwrap()).unwrap();
+        let result = analyzer.analyze(Parser::new("D = A @ E; E = A.T").parse_program().unwrap());
+        assert!(matches!(result, Err(CompileError::UndefinedVariable { name }) if name == "E"));
+    }
+    
+    #[test]
+    fn test_strict_mode() {
+        let source = "C = A @ B; A = C.T";
+        
+        let mut lenient = Analyzer::new();
+        lenient.define_matrix("A", (2, 3));
+        lenient.define_matrix("B", (3, 3));
+        lenient.analyze(Parser::new(source).parse_program().unwrap()).unwrap();
+        assert_eq!(lenient.warnings().len(), 1);
+        assert!(lenient.warnings()[0].contains("'A' redefined"));
+        
+        let mut strict = Analyzer::new().with_strict(true);
+        strict.define_matrix("A", (2, 3));
+        strict.define_matrix("B", (3, 3));
+        let result = strict.analyze(Parser::new(source).parse_program().unwrap());
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("'A' redefined with shape (3, 2) (was (2, 3))"), "{}", message);
+    }
+    
+    #[test]
+    fn test_dot_matrix_vector_shape() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 4));
+        analyzer.define_matrix("M", (1, 4));
+        
+        let program = Parser::new("v = M[0]; y = np.dot(A, v)").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        assert_eq!(typed.statements[1].value.shape, Shape::Vector(2));
+        
+        let program = Parser::new("z = np.dot(A, y)").parse_program().unwrap();
+        let result = analyzer.analyze(program);
+        assert!(matches!(result, Err(CompileError::ShapeMismatch { .. })));
+        
+        // 1D literals are vectors; a row matrix transposes to a real column
+        analyzer.define_matrix("S", (3, 3));
+        let program = Parser::new("v = [1, 2, 3]; y = S @ v; r = [[1, 2, 3]].T").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        let shapes: Vec<_> = typed.statements.iter().map(|stmt| stmt.value.shape.clone()).collect();
+        assert_eq!(shapes, [Shape::Vector(3), Shape::Vector(3), Shape::matrix(3, 1)]);
+        assert!(analyzer.analyze(Parser::new("y = S @ [1, 2]").parse_program().unwrap()).is_err());
+    }
+    
+    #[test]
+    fn test_analyze_bias_broadcast() {
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("X", (3, 4));
+        analyzer.define_matrix("row", (1, 4));
+        analyzer.define_matrix("col", (3, 1));
+        analyzer.define_matrix("bad", (2, 4));
+        
+        let program = Parser::new("a = X + row; b = col - X; c = X + 2").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        for stmt in &typed.statements {
+            assert_eq!(stmt.value.shape, Shape::matrix(3, 4));
+        }
+        
+        let result = analyzer.analyze(Parser::new("d = X + bad").parse_program().unwrap());
+        assert!(matches!(result, Err(CompileError::ShapeMismatch { .. })));
+    }
 }
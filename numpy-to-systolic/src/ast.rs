@@ -1,7 +1,11 @@
 //! Abstract Syntax Tree definitions for NumPy expressions
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::error::{CompileError, CompileResult};
+
 /// A complete program consisting of statements
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
@@ -15,8 +19,13 @@ pub enum Statement {
     Assignment { target: String, value: Expr },
     /// Expression statement (for evaluation)
     Expression(Expr),
+    /// Expression template, inlined at each call: `def ffn(x) = relu(x @ W1) @ W2`
+    Def { name: String, params: Vec<String>, body: Expr },
 }
 
+/// Keyword arguments of a call, in source order: `out=C`
+pub type KeywordArgs = Vec<(String, Expr)>;
+
 /// Expression types
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
@@ -29,9 +38,15 @@ pub enum Expr {
     /// Matrix literal: `[[1, 2], [3, 4]]`
     Matrix(MatrixLiteral),
     
+    /// 1D array literal: `[1, 2, 3]`
+    Vector(Vec<f64>),
+    
     /// Matrix multiplication: `A @ B`
     MatMul(Box<Expr>, Box<Expr>),
     
+    /// Parenthesized matmul operand of `@`: the `(B @ C)` in `A @ (B @ C)`
+    Paren(Box<Expr>),
+    
     /// Element-wise addition: `A + B`
     Add(Box<Expr>, Box<Expr>),
     
@@ -41,17 +56,127 @@ pub enum Expr {
     /// Element-wise multiplication: `A * B`
     Mul(Box<Expr>, Box<Expr>),
     
+    /// Element-wise division: `A / B`
+    Div(Box<Expr>, Box<Expr>),
+    
+    /// Matrix power: `A ** 3`
+    MatPow(Box<Expr>, u32),
+    
     /// Scalar multiplication: `scalar * A`
     ScalarMul(Box<Expr>, Box<Expr>),
     
     /// Matrix transpose: `A.T` or `np.transpose(A)`
     Transpose(Box<Expr>),
     
-    /// Function call: `np.zeros((m, n))`, `np.eye(n)`, etc.
-    FunctionCall { name: String, args: Vec<Expr> },
+    /// Function call: `np.zeros((m, n))`, `np.eye(n)`, `np.matmul(A, B, out=C)`, etc.
+    FunctionCall { name: String, args: Vec<Expr>, kwargs: KeywordArgs },
     
     /// Tuple literal for shapes: `(3, 4)`
     Tuple(Vec<Expr>),
+    
+    /// Subscript: `A[0]`, `A[:, 1]`, `A[1:3, :]`, `A[::-1]`
+    Index(Box<Expr>, Vec<Subscript>),
+    
+    /// Temporary scoped to `body`: `let t = A @ B in t + t`
+    Let { name: String, value: Box<Expr>, body: Box<Expr> },
+}
+
+impl Expr {
+    /// Copy of the expression with free reads of each bound variable replaced by its value
+    pub fn substitute(&self, bindings: &[(String, Expr)]) -> Expr {
+        let sub = |e: &Expr| Box::new(e.substitute(bindings));
+        match self {
+            Expr::Variable(v) => bindings.iter()
+                .find(|(name, _)| name == v)
+                .map_or_else(|| self.clone(), |(_, value)| value.clone()),
+            Expr::Scalar(_) | Expr::Matrix(_) | Expr::Vector(_) => self.clone(),
+            Expr::MatMul(l, r) => Expr::MatMul(sub(l), sub(r)),
+            Expr::Paren(inner) => Expr::Paren(sub(inner)),
+            Expr::Add(l, r) => Expr::Add(sub(l), sub(r)),
+            Expr::Sub(l, r) => Expr::Sub(sub(l), sub(r)),
+            Expr::Mul(l, r) => Expr::Mul(sub(l), sub(r)),
+            Expr::Div(l, r) => Expr::Div(sub(l), sub(r)),
+            Expr::ScalarMul(l, r) => Expr::ScalarMul(sub(l), sub(r)),
+            Expr::Transpose(inner) => Expr::Transpose(sub(inner)),
+            Expr::MatPow(base, exponent) => Expr::MatPow(sub(base), *exponent),
+            Expr::Index(inner, subscripts) => Expr::Index(sub(inner), subscripts.clone()),
+            Expr::FunctionCall { name, args, kwargs } => Expr::FunctionCall {
+                name: name.clone(),
+                args: args.iter().map(|a| a.substitute(bindings)).collect(),
+                kwargs: kwargs.iter().map(|(k, v)| (k.clone(), v.substitute(bindings))).collect(),
+            },
+            Expr::Tuple(elements) => Expr::Tuple(elements.iter().map(|e| e.substitute(bindings)).collect()),
+            Expr::Let { name, value, body } => {
+                // The binding shadows any substitution of the same name in its body
+                let inner: Vec<(String, Expr)> = bindings.iter().filter(|(n, _)| n != name).cloned().collect();
+                Expr::Let { name: name.clone(), value: sub(value), body: Box::new(body.substitute(&inner)) }
+            }
+        }
+    }
+    
+    /// Whether the expression reads the variable `name` anywhere
+    pub fn references(&self, name: &str) -> bool {
+        match self {
+            Expr::Variable(v) => v == name,
+            Expr::Scalar(_) | Expr::Matrix(_) | Expr::Vector(_) => false,
+            Expr::MatMul(l, r)
+            | Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::Mul(l, r)
+            | Expr::Div(l, r)
+            | Expr::ScalarMul(l, r) => l.references(name) || r.references(name),
+            Expr::Transpose(inner) | Expr::Index(inner, _) | Expr::MatPow(inner, _) | Expr::Paren(inner) => {
+                inner.references(name)
+            }
+            Expr::FunctionCall { args, kwargs, .. } => {
+                args.iter().any(|a| a.references(name))
+                    || kwargs.iter().any(|(_, v)| v.references(name))
+            }
+            Expr::Tuple(elements) => elements.iter().any(|e| e.references(name)),
+            Expr::Let { name: bound, value, body } => {
+                value.references(name) || (bound != name && body.references(name))
+            }
+        }
+    }
+}
+
+/// A single-axis subscript inside `[...]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Subscript {
+    /// Integer index selecting one row/column: `A[0]`, `A[-1]`
+    Index(i64),
+    /// Strided range over an axis: `:`, `1:`, `:3`, `1:3`, `::2`, `::-1`
+    Slice { start: Option<i64>, stop: Option<i64>, step: Option<i64> },
+}
+
+impl Subscript {
+    /// The full-axis slice `:`
+    pub fn full() -> Self {
+        Subscript::Slice { start: None, stop: None, step: None }
+    }
+    
+    /// Positions selected on an axis of length `len`, in order
+    ///
+    /// Expects a subscript already normalized by the analyzer: explicit bounds and step,
+    /// with `start` in bounds and `stop` exclusive. For a negative step `stop` may be -1,
+    /// meaning the slice runs through position 0.
+    pub fn indices(&self, len: usize) -> Vec<usize> {
+        match self {
+            Subscript::Index(i) => vec![*i as usize],
+            Subscript::Slice { start, stop, step } => {
+                let step = step.unwrap_or(1);
+                let mut i = start.unwrap_or(if step > 0 { 0 } else { len as i64 - 1 });
+                let stop = stop.unwrap_or(if step > 0 { len as i64 } else { -1 });
+                
+                let mut indices = Vec::new();
+                while (step > 0 && i < stop) || (step < 0 && i > stop) {
+                    indices.push(i as usize);
+                    i += step;
+                }
+                indices
+            }
+        }
+    }
 }
 
 /// A matrix literal value
@@ -102,6 +227,8 @@ pub struct TypedExpr {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Shape {
     Scalar,
+    /// 1D array of the given length, laid out as a row in memory
+    Vector(usize),
     Matrix { rows: usize, cols: usize },
     Unknown,
 }
@@ -115,19 +242,93 @@ impl Shape {
         matches!(self, Shape::Matrix { .. })
     }
     
+    pub fn is_vector(&self) -> bool {
+        matches!(self, Shape::Vector(_))
+    }
+    
     pub fn dimensions(&self) -> Option<(usize, usize)> {
         match self {
             Shape::Matrix { rows, cols } => Some((*rows, *cols)),
+            Shape::Vector(len) => Some((1, *len)),
             Shape::Scalar => Some((1, 1)),
             Shape::Unknown => None,
         }
     }
+    
+    /// Shape after `.T`; transposing a 1D array or scalar is a no-op, as in NumPy
+    pub fn transposed(&self) -> Shape {
+        match self {
+            Shape::Matrix { rows, cols } => Shape::matrix(*cols, *rows),
+            other => other.clone(),
+        }
+    }
+    
+    /// Shape of `self @ other`
+    ///
+    /// A vector right operand acts as a column and yields a vector.
+    pub fn matmul_with(&self, other: &Shape) -> CompileResult<Shape> {
+        match (self, other) {
+            (Shape::Matrix { rows: m, cols: k1 }, Shape::Matrix { rows: k2, cols: n }) => {
+                if k1 != k2 {
+                    return Err(CompileError::ShapeMismatch {
+                        expected: format!("inner dimensions to match, got {} and {}", k1, k2),
+                        got: format!("left: ({}, {}), right: ({}, {})", m, k1, k2, n),
+                    });
+                }
+                Ok(Shape::matrix(*m, *n))
+            }
+            (Shape::Matrix { rows: m, cols: k }, Shape::Vector(len)) => {
+                if k != len {
+                    return Err(CompileError::ShapeMismatch {
+                        expected: format!("vector of length {}", k),
+                        got: format!("length {}", len),
+                    });
+                }
+                Ok(Shape::Vector(*m))
+            }
+            (Shape::Unknown, _) | (_, Shape::Unknown) => Ok(Shape::Unknown),
+            (l, r) => Err(CompileError::type_error(format!(
+                "Matrix multiplication is not defined for {} and {}",
+                l, r
+            ))),
+        }
+    }
+    
+    /// Shape of an element-wise combination of `self` and `other`
+    ///
+    /// As in NumPy, a size-1 axis stretches to match the other operand, so scalars, rows like
+    /// `(1, n)` and columns like `(m, 1)` broadcast against an `(m, n)` matrix; a vector acts as a
+    /// single row. Only one operand may stretch. An unknown operand takes the other's shape.
+    pub fn broadcast_with(&self, other: &Shape) -> CompileResult<Shape> {
+        match (self, other) {
+            (Shape::Scalar, other) | (other, Shape::Scalar) => Ok(other.clone()),
+            (Shape::Unknown, other) | (other, Shape::Unknown) => Ok(other.clone()),
+            (l, r) if l == r => Ok(l.clone()),
+            (l, r) if r.stretches_to(l) => Ok(l.clone()),
+            (l, r) if l.stretches_to(r) => Ok(r.clone()),
+            (l, r) => Err(CompileError::ShapeMismatch {
+                expected: "matching shapes for broadcast".to_string(),
+                got: format!("{} and {}", l, r),
+            }),
+        }
+    }
+    
+    /// Whether every axis of `self` equals the one in `target` or has size 1
+    pub fn stretches_to(&self, target: &Shape) -> bool {
+        match (self.dimensions(), target.dimensions()) {
+            (Some((rows, cols)), Some((to_rows, to_cols))) => {
+                (rows == to_rows || rows == 1) && (cols == to_cols || cols == 1)
+            }
+            _ => false,
+        }
+    }
 }
 
 impl std::fmt::Display for Shape {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Shape::Scalar => write!(f, "scalar"),
+            Shape::Vector(len) => write!(f, "({},)", len),
             Shape::Matrix { rows, cols } => write!(f, "({}, {})", rows, cols),
             Shape::Unknown => write!(f, "unknown"),
         }
@@ -141,11 +342,85 @@ pub enum TypedExprKind {
     Scalar(f64),
     Matrix(Vec<Vec<f64>>),
     MatMul(Box<TypedExpr>, Box<TypedExpr>),
+    /// A matmul operand the source parenthesized, kept whole when chains are reordered
+    Grouped(Box<TypedExpr>),
     Add(Box<TypedExpr>, Box<TypedExpr>),
     Sub(Box<TypedExpr>, Box<TypedExpr>),
     Mul(Box<TypedExpr>, Box<TypedExpr>),
+    Div(Box<TypedExpr>, Box<TypedExpr>),
+    /// Outer product of two vectors: `np.outer(u, v)`
+    Outer(Box<TypedExpr>, Box<TypedExpr>),
     ScalarMul(Box<TypedExpr>, Box<TypedExpr>),
     Transpose(Box<TypedExpr>),
+    /// Subscript with indices normalized against the source shape
+    Index(Box<TypedExpr>, Vec<Subscript>),
+    /// Element-wise activation function
+    Activation(Activation, Box<TypedExpr>),
+    /// Same row-major data viewed with the expression's shape
+    Reshape(Box<TypedExpr>),
+    /// Value written into an existing buffer via `out=`
+    Out(String, Box<TypedExpr>),
+    /// One of `sections` equal parts of `source` along `axis`: `np.split(A, 2)[1]`
+    Split { source: Box<TypedExpr>, axis: usize, sections: usize, part: usize },
+    /// `left` followed by `right` along `axis`: `np.append(A, B, axis=0)`
+    Concat { left: Box<TypedExpr>, right: Box<TypedExpr>, axis: usize },
+    /// Cyclic shift by `shift` along `axis`, or over the flattened data: `np.roll(A, 1, axis=1)`
+    Roll { source: Box<TypedExpr>, shift: i64, axis: Option<usize> },
+    /// L2 norm along `axis`, or over every element (Frobenius for matrices): `np.linalg.norm(A, axis=1)`
+    Norm { source: Box<TypedExpr>, axis: Option<usize> },
+    /// Diagonal matrix of a vector, or diagonal of a matrix: `np.diag(v)`, `np.diag(A)`
+    Diag(Box<TypedExpr>),
+    /// Element-wise clamp into `[min, max]`: `np.clip(A, 0, 127)`
+    Clip { source: Box<TypedExpr>, min: f64, max: f64 },
+    /// `value` computed once and read as `name` inside `body`
+    Let { name: String, value: Box<TypedExpr>, body: Box<TypedExpr> },
+}
+
+/// Element-wise activation functions, applied on the host after the array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Activation {
+    Relu,
+    Tanh,
+    Sigmoid,
+    /// GELU using the tanh approximation
+    Gelu,
+}
+
+impl Activation {
+    /// Look up an activation by its builtin name (`relu`, `np.tanh`, ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.strip_prefix("np.").unwrap_or(name) {
+            "relu" => Some(Activation::Relu),
+            "tanh" => Some(Activation::Tanh),
+            "sigmoid" => Some(Activation::Sigmoid),
+            "gelu" => Some(Activation::Gelu),
+            _ => None,
+        }
+    }
+    
+    /// Apply the activation to a single value
+    pub fn apply(&self, x: f64) -> f64 {
+        match self {
+            Activation::Relu => x.max(0.0),
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Gelu => {
+                let c = (2.0 / std::f64::consts::PI).sqrt();
+                0.5 * x * (1.0 + (c * (x + 0.044715 * x.powi(3))).tanh())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Activation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Activation::Relu => write!(f, "relu"),
+            Activation::Tanh => write!(f, "tanh"),
+            Activation::Sigmoid => write!(f, "sigmoid"),
+            Activation::Gelu => write!(f, "gelu"),
+        }
+    }
 }
 
 /// A typed statement
@@ -159,4 +434,340 @@ pub struct TypedStatement {
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypedProgram {
     pub statements: Vec<TypedStatement>,
+    /// Quantization scales of inputs defined with one, by name
+    pub input_scales: HashMap<String, f64>,
+    /// Rows of inputs defined with data, by name; loaded before the first statement
+    pub input_data: HashMap<String, Vec<Vec<f64>>>,
+}
+
+/// Coarse kind of work a program does, for routing to a specialized scheduler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkloadKind {
+    /// No matrix multiplication; everything runs on the host
+    HostOnly,
+    /// Exactly one matmul and nothing else to compute
+    SingleMatMul,
+    /// Several matmuls and nothing else to compute
+    ChainedMatMul,
+    /// Matmuls plus element-wise host work such as `+`, `-`, `*`
+    ElementwiseEpilogue,
+    /// Contains an activation function
+    Activation,
+}
+
+impl TypedExpr {
+    /// Direct subexpressions, left to right
+    pub fn children(&self) -> Vec<&TypedExpr> {
+        match &self.expr {
+            TypedExprKind::Variable(_) | TypedExprKind::Scalar(_) | TypedExprKind::Matrix(_) => vec![],
+            TypedExprKind::MatMul(l, r)
+            | TypedExprKind::Add(l, r)
+            | TypedExprKind::Sub(l, r)
+            | TypedExprKind::Mul(l, r)
+            | TypedExprKind::Div(l, r)
+            | TypedExprKind::Outer(l, r)
+            | TypedExprKind::ScalarMul(l, r)
+            | TypedExprKind::Concat { left: l, right: r, .. }
+            | TypedExprKind::Let { value: l, body: r, .. } => vec![l, r],
+            TypedExprKind::Transpose(inner)
+            | TypedExprKind::Grouped(inner)
+            | TypedExprKind::Index(inner, _)
+            | TypedExprKind::Activation(_, inner)
+            | TypedExprKind::Reshape(inner)
+            | TypedExprKind::Diag(inner)
+            | TypedExprKind::Out(_, inner)
+            | TypedExprKind::Split { source: inner, .. }
+            | TypedExprKind::Roll { source: inner, .. }
+            | TypedExprKind::Norm { source: inner, .. }
+            | TypedExprKind::Clip { source: inner, .. } => vec![inner],
+        }
+    }
+    
+    /// Call `f` on this expression and every subexpression
+    pub fn visit(&self, f: &mut impl FnMut(&TypedExpr)) {
+        f(self);
+        for child in self.children() {
+            child.visit(f);
+        }
+    }
+    
+    /// Short name of this node's operation, for diagrams
+    fn node_name(&self) -> String {
+        match &self.expr {
+            TypedExprKind::Variable(name) => name.clone(),
+            TypedExprKind::Scalar(value) => value.to_string(),
+            TypedExprKind::Matrix(_) => "literal".to_string(),
+            TypedExprKind::MatMul(..) => "@".to_string(),
+            TypedExprKind::Grouped(_) => "( )".to_string(),
+            TypedExprKind::Add(..) => "+".to_string(),
+            TypedExprKind::Sub(..) => "-".to_string(),
+            TypedExprKind::Mul(..) => "*".to_string(),
+            TypedExprKind::Div(..) => "/".to_string(),
+            TypedExprKind::Outer(..) => "outer".to_string(),
+            TypedExprKind::ScalarMul(..) => "scale".to_string(),
+            TypedExprKind::Transpose(_) => ".T".to_string(),
+            TypedExprKind::Index(..) => "index".to_string(),
+            TypedExprKind::Activation(kind, _) => kind.to_string(),
+            TypedExprKind::Reshape(_) => "reshape".to_string(),
+            TypedExprKind::Out(name, _) => format!("out={}", name),
+            TypedExprKind::Split { axis, sections, part, .. } => format!("split {}/{} axis {}", part, sections, axis),
+            TypedExprKind::Concat { axis, .. } => format!("concat axis {}", axis),
+            TypedExprKind::Roll { shift, .. } => format!("roll {}", shift),
+            TypedExprKind::Norm { .. } => "norm".to_string(),
+            TypedExprKind::Diag(_) => "diag".to_string(),
+            TypedExprKind::Clip { min, max, .. } => format!("clip [{}, {}]", min, max),
+            TypedExprKind::Let { name, .. } => format!("let {}", name),
+        }
+    }
+    
+    /// Append this subtree to `out` as DOT nodes numbered from `*next`, returning its node id
+    fn write_dot(&self, out: &mut String, next: &mut usize) -> String {
+        let id = format!("n{}", *next);
+        *next += 1;
+        let children = self.children();
+        // Operator nodes list their operand shapes so a bad inference is easy to trace
+        let shapes = if children.is_empty() {
+            self.shape.to_string()
+        } else {
+            let operands: Vec<String> = children.iter().map(|child| child.shape.to_string()).collect();
+            format!("{} -> {}", operands.join(", "), self.shape)
+        };
+        let color = if self.shape == Shape::Unknown { ", color=red" } else { "" };
+        out.push_str(&format!("  {} [label=\"{}\\n{}\"{}];\n", id, self.node_name(), shapes, color));
+        for child in children {
+            let child_id = child.write_dot(out, next);
+            out.push_str(&format!("  {} -> {};\n", id, child_id));
+        }
+        id
+    }
+    
+    /// Whether any subexpression reads variable `name`
+    pub fn references(&self, name: &str) -> bool {
+        let mut found = false;
+        self.visit(&mut |expr| found |= matches!(&expr.expr, TypedExprKind::Variable(v) if v == name));
+        found
+    }
+    
+    /// Replace subexpressions top-down; where `f` returns a replacement, its subtree is not visited
+    ///
+    /// `let` bodies are left alone, since their binding may shadow a name a replacement reads.
+    pub fn replace_with(&mut self, f: &mut impl FnMut(&TypedExpr) -> Option<TypedExpr>) {
+        if let Some(replacement) = f(self) {
+            *self = replacement;
+            return;
+        }
+        match &mut self.expr {
+            TypedExprKind::Variable(_) | TypedExprKind::Scalar(_) | TypedExprKind::Matrix(_) => {}
+            TypedExprKind::Let { value, .. } => value.replace_with(f),
+            TypedExprKind::MatMul(l, r)
+            | TypedExprKind::Add(l, r)
+            | TypedExprKind::Sub(l, r)
+            | TypedExprKind::Mul(l, r)
+            | TypedExprKind::Div(l, r)
+            | TypedExprKind::Outer(l, r)
+            | TypedExprKind::ScalarMul(l, r)
+            | TypedExprKind::Concat { left: l, right: r, .. } => {
+                l.replace_with(f);
+                r.replace_with(f);
+            }
+            TypedExprKind::Transpose(inner)
+            | TypedExprKind::Grouped(inner)
+            | TypedExprKind::Index(inner, _)
+            | TypedExprKind::Activation(_, inner)
+            | TypedExprKind::Reshape(inner)
+            | TypedExprKind::Diag(inner)
+            | TypedExprKind::Out(_, inner)
+            | TypedExprKind::Split { source: inner, .. }
+            | TypedExprKind::Roll { source: inner, .. }
+            | TypedExprKind::Norm { source: inner, .. }
+            | TypedExprKind::Clip { source: inner, .. } => inner.replace_with(f),
+        }
+    }
+    
+    /// Rewrite free reads of variable `from` to read `to` instead
+    pub fn rename(&mut self, from: &str, to: &str) {
+        match &mut self.expr {
+            TypedExprKind::Variable(name) if name == from => *name = to.to_string(),
+            TypedExprKind::Variable(_) | TypedExprKind::Scalar(_) | TypedExprKind::Matrix(_) => {}
+            TypedExprKind::Let { name, value, body } => {
+                value.rename(from, to);
+                // An inner binding of the same name shadows `from` in its body
+                if name != from {
+                    body.rename(from, to);
+                }
+            }
+            TypedExprKind::MatMul(l, r)
+            | TypedExprKind::Add(l, r)
+            | TypedExprKind::Sub(l, r)
+            | TypedExprKind::Mul(l, r)
+            | TypedExprKind::Div(l, r)
+            | TypedExprKind::Outer(l, r)
+            | TypedExprKind::ScalarMul(l, r)
+            | TypedExprKind::Concat { left: l, right: r, .. } => {
+                l.rename(from, to);
+                r.rename(from, to);
+            }
+            TypedExprKind::Transpose(inner)
+            | TypedExprKind::Grouped(inner)
+            | TypedExprKind::Index(inner, _)
+            | TypedExprKind::Activation(_, inner)
+            | TypedExprKind::Reshape(inner)
+            | TypedExprKind::Diag(inner)
+            | TypedExprKind::Out(_, inner)
+            | TypedExprKind::Split { source: inner, .. }
+            | TypedExprKind::Roll { source: inner, .. }
+            | TypedExprKind::Norm { source: inner, .. }
+            | TypedExprKind::Clip { source: inner, .. } => inner.rename(from, to),
+        }
+    }
+}
+
+impl TypedProgram {
+    /// GraphViz DOT graph of every statement's expression tree, each node labelled with
+    /// its inferred shape (for `dot -Tpng`)
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph typed_program {\n  node [shape=box, fontname=monospace];\n");
+        let mut next = 0;
+        for (i, statement) in self.statements.iter().enumerate() {
+            out.push_str(&format!("  s{} [label=\"{} =\", shape=plaintext];\n", i, statement.target));
+            let root = statement.value.write_dot(&mut out, &mut next);
+            out.push_str(&format!("  s{} -> {};\n", i, root));
+        }
+        out.push_str("}\n");
+        out
+    }
+    
+    /// Number of matrix multiplications across all statements
+    pub fn matmul_count(&self) -> usize {
+        self.count(|kind| matches!(kind, TypedExprKind::MatMul(..) | TypedExprKind::Outer(..)))
+    }
+    
+    /// Whether any statement applies an activation function
+    pub fn has_activation(&self) -> bool {
+        self.count(|kind| matches!(kind, TypedExprKind::Activation(..))) > 0
+    }
+    
+    /// Whether any statement does element-wise arithmetic on the host
+    pub fn has_elementwise(&self) -> bool {
+        self.count(|kind| {
+            matches!(
+                kind,
+                TypedExprKind::Add(..)
+                    | TypedExprKind::Sub(..)
+                    | TypedExprKind::Mul(..)
+                    | TypedExprKind::Div(..)
+                    | TypedExprKind::ScalarMul(..)
+            )
+        }) > 0
+    }
+    
+    /// Whether the program is a single matmul with no other arithmetic
+    pub fn is_pure_matmul(&self) -> bool {
+        self.classify() == WorkloadKind::SingleMatMul
+    }
+    
+    /// Summarize the program's workload; activations outrank element-wise work,
+    /// which outranks plain matmuls
+    pub fn classify(&self) -> WorkloadKind {
+        if self.has_activation() {
+            WorkloadKind::Activation
+        } else if self.has_elementwise() {
+            WorkloadKind::ElementwiseEpilogue
+        } else {
+            match self.matmul_count() {
+                0 => WorkloadKind::HostOnly,
+                1 => WorkloadKind::SingleMatMul,
+                _ => WorkloadKind::ChainedMatMul,
+            }
+        }
+    }
+    
+    fn count(&self, pred: impl Fn(&TypedExprKind) -> bool) -> usize {
+        let mut count = 0;
+        for statement in &self.statements {
+            statement.value.visit(&mut |expr| {
+                if pred(&expr.expr) {
+                    count += 1;
+                }
+            });
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_to_dot() {
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 4));
+        let program = crate::parser::Parser::new("C = A @ B").parse_program().unwrap();
+        let dot = analyzer.analyze(program).unwrap().to_dot();
+        
+        assert!(dot.starts_with("digraph typed_program {"), "{}", dot);
+        assert!(dot.contains("s0 [label=\"C =\", shape=plaintext];"), "{}", dot);
+        assert!(dot.contains("n0 [label=\"@\\n(2, 3), (3, 4) -> (2, 4)\"];"), "{}", dot);
+        assert!(dot.contains("n1 [label=\"A\\n(2, 3)\"];"), "{}", dot);
+        assert!(dot.contains("n2 [label=\"B\\n(3, 4)\"];"), "{}", dot);
+        assert!(dot.contains("n0 -> n1;") && dot.contains("n0 -> n2;"), "{}", dot);
+        assert!(dot.trim_end().ends_with('}'));
+    }
+    
+    #[test]
+    fn test_transposed() {
+        assert_eq!(Shape::matrix(2, 3).transposed(), Shape::matrix(3, 2));
+        assert_eq!(Shape::Vector(4).transposed(), Shape::Vector(4));
+        assert_eq!(Shape::Unknown.transposed(), Shape::Unknown);
+    }
+    
+    #[test]
+    fn test_matmul_with() {
+        let a = Shape::matrix(2, 3);
+        assert_eq!(a.matmul_with(&Shape::matrix(3, 4)).unwrap(), Shape::matrix(2, 4));
+        assert_eq!(a.matmul_with(&Shape::Vector(3)).unwrap(), Shape::Vector(2));
+        assert_eq!(a.matmul_with(&Shape::Unknown).unwrap(), Shape::Unknown);
+        
+        assert!(matches!(a.matmul_with(&Shape::matrix(4, 4)), Err(CompileError::ShapeMismatch { .. })));
+        assert!(matches!(a.matmul_with(&Shape::Vector(2)), Err(CompileError::ShapeMismatch { .. })));
+        assert!(matches!(a.matmul_with(&Shape::Scalar), Err(CompileError::TypeError { .. })));
+    }
+    
+    #[test]
+    fn test_broadcast_with() {
+        let a = Shape::matrix(2, 3);
+        assert_eq!(a.broadcast_with(&a).unwrap(), a);
+        assert_eq!(Shape::Scalar.broadcast_with(&a).unwrap(), a);
+        assert_eq!(Shape::Unknown.broadcast_with(&Shape::Vector(3)).unwrap(), Shape::Vector(3));
+        assert_eq!(a.broadcast_with(&Shape::matrix(1, 1)).unwrap(), a);
+        
+        assert_eq!(a.broadcast_with(&Shape::Vector(3)).unwrap(), a);
+        
+        assert!(a.broadcast_with(&Shape::matrix(3, 2)).is_err());
+        assert!(a.broadcast_with(&Shape::Vector(2)).is_err());
+        assert!(Shape::Vector(3).broadcast_with(&Shape::Vector(4)).is_err());
+        // Both operands would have to stretch
+        assert!(Shape::matrix(1, 3).broadcast_with(&Shape::matrix(2, 1)).is_err());
+    }
+    
+    #[test]
+    fn test_classify_workload() {
+        let classify = |source: &str| {
+            let program = crate::parser::Parser::new(source).parse_program().unwrap();
+            let mut analyzer = crate::analyzer::Analyzer::new();
+            for name in ["A", "B", "C", "D"] {
+                analyzer.define_matrix(name, (3, 3));
+            }
+            analyzer.analyze(program).unwrap().classify()
+        };
+        
+        assert_eq!(classify("E = A @ B"), WorkloadKind::SingleMatMul);
+        assert_eq!(classify("E = A @ B; F = E @ C.T"), WorkloadKind::ChainedMatMul);
+        assert_eq!(classify("E = A @ B + C"), WorkloadKind::ElementwiseEpilogue);
+        assert_eq!(classify("E = relu(A @ B)"), WorkloadKind::Activation);
+        assert_eq!(classify("E = relu(A @ B + C)"), WorkloadKind::Activation);
+        assert_eq!(classify("E = A.T"), WorkloadKind::HostOnly);
+    }
 }
@@ -2,78 +2,328 @@
 //!
 //! Converts tiled operations into sequences of systolic array passes.
 
-use std::collections::HashMap;
-use crate::error::CompileResult;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use crate::error::{CompileError, CompileResult};
 use crate::hardware::{
-    HardwareProgram, PassOperation, SystolicConfig, SystolicPass, TileCoord,
-    pad_matrix, quantize_matrix, row_to_column_major,
+    EpilogueOp, EpilogueStep, HardwareProgram, MatMulGroup, OperandBuffer, PassOperation, SystolicConfig, SystolicPass,
+    TileCoord,
+    check_integer_data, pad_matrix, pad_matrix_with_value, quantize_matrix, row_to_column_major, tile_product,
+};
+use crate::tiling::{
+    broadcast_zip, concat_data, diag_data, norm_data, roll_data, split_block, EpilogueStage, MatMulTile, TiledOperation, TiledProgram,
 };
-use crate::tiling::{MatMulTile, TiledOperation, TiledProgram};
 
 /// Code generator for systolic array hardware
 pub struct CodeGenerator {
     config: SystolicConfig,
     pass_counter: usize,
-    /// Storage for matrix data
-    matrix_data: HashMap<String, MatrixData>,
+    /// Storage for matrix data; aliases share the same buffer
+    matrix_data: HashMap<String, Arc<MatrixData>>,
+    /// Matmul groups each buffer of the current program depends on
+    producers: HashMap<String, Vec<usize>>,
+    /// Base address of each operand buffer in the current program's memory plan
+    addresses: HashMap<String, usize>,
+    /// First free address in the memory plan
+    next_addr: usize,
+    /// Variable each loaded buffer aliases, so shared operands get one address
+    aliases: HashMap<String, String>,
+    /// Quantization scale of each calibrated operand (1.0 when absent)
+    scales: HashMap<String, f64>,
+    /// Buffers holding zeros in place of data only known at run time
+    placeholders: HashSet<String>,
+}
+
+/// Percentile of |x| mapped to the largest representable value by `calibrate`
+pub const CALIBRATION_PERCENTILE: f64 = 99.0;
+
+/// Scale chosen for one operand by `CodeGenerator::calibrate`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperandCalibration {
+    pub name: String,
+    /// Percentile-based scale applied when quantizing the operand
+    pub scale: f64,
+    /// Scale a max-based calibration would have chosen, for comparison
+    pub max_scale: f64,
+    /// Fraction of sample values that saturate under `scale`
+    pub clip_rate: f64,
+}
+
+/// Nearest-rank percentile of `values`; 0.0 when empty
+fn percentile(values: &[f64], pct: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Transpose of `src` as a `shape` matrix; entries `src` lacks read as zero
+fn transpose_data(src: &MatrixData, shape: (usize, usize)) -> MatrixData {
+    let data = (0..shape.0)
+        .flat_map(|i| (0..shape.1).map(move |j| (i, j)))
+        .map(|(i, j)| src.data.get(j * src.shape.1 + i).copied().unwrap_or(0.0))
+        .collect();
+    MatrixData { data, shape }
 }
 
 /// Stored matrix data
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 struct MatrixData {
     data: Vec<f64>,
     shape: (usize, usize),
 }
 
+/// A tiled matmul part-way through emitting its passes
+struct MatMulStream {
+    target: String,
+    left: Arc<MatrixData>,
+    right: Arc<MatrixData>,
+    left_shape: (usize, usize),
+    right_shape: (usize, usize),
+    output_shape: (usize, usize),
+    tiles: Vec<MatMulTile>,
+    /// Index of the tile whose pass comes next
+    next_tile: usize,
+    tile_size: usize,
+    /// Buffer the accumulators start from (a fused residual add)
+    residual: Option<Arc<MatrixData>>,
+    epilogue: Vec<EpilogueStage>,
+    scales: (f64, f64),
+    /// Base addresses of the A and B operand buffers
+    bases: (usize, usize),
+    /// Both operand buffers are stored transposed (see `TiledOperation::TiledMatMul`)
+    transposed: bool,
+    /// Accumulator contents of the current output tile
+    acc: Vec<i64>,
+    /// The product in real units, filled in as output tiles finish
+    output: Vec<f64>,
+}
+
+/// Lazily generated passes of a tiled program (see `CodeGenerator::passes_iter`)
+///
+/// `program` collects everything but the passes: groups, operand buffers,
+/// host epilogues and the output shape.
+struct PassStream<'a> {
+    generator: &'a mut CodeGenerator,
+    program: HardwareProgram,
+    operations: std::vec::IntoIter<TiledOperation>,
+    matmul: Option<MatMulStream>,
+    started: bool,
+    done: bool,
+}
+
+impl PassStream<'_> {
+    /// Run operations until one produces a pass, or the program ends
+    fn advance(&mut self) -> CompileResult<Option<SystolicPass>> {
+        if !self.started {
+            self.started = true;
+            self.generator.config.validate()?;
+        }
+        loop {
+            if let Some(matmul) = &mut self.matmul {
+                match self.generator.next_matmul_pass(matmul)? {
+                    Some(pass) => return Ok(Some(pass)),
+                    None => self.matmul = None,
+                }
+            }
+            let Some(op) = self.operations.next() else {
+                return Ok(None);
+            };
+            self.generator.track_dependencies(&op, &mut self.program);
+            self.generator.track_placeholders(&op);
+            self.matmul = self.generator.process_operation(&op, &mut self.program)?;
+        }
+    }
+}
+
+impl Iterator for PassStream<'_> {
+    type Item = CompileResult<SystolicPass>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.advance().transpose();
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
 impl CodeGenerator {
     pub fn new(config: SystolicConfig) -> Self {
         Self {
             config,
             pass_counter: 0,
             matrix_data: HashMap::new(),
+            producers: HashMap::new(),
+            addresses: HashMap::new(),
+            next_addr: 0,
+            aliases: HashMap::new(),
+            scales: HashMap::new(),
+            placeholders: HashSet::new(),
         }
     }
     
+    /// Pick a quantization scale for each named operand from sample data
+    ///
+    /// The scale maps the `CALIBRATION_PERCENTILE`th percentile of |x| to the largest
+    /// representable value, so a few outliers clip instead of squeezing every other
+    /// value into a handful of levels as a max-based scale would. Later passes reading
+    /// these operands (or aliases of them) are quantized with the chosen scales.
+    pub fn calibrate(&mut self, samples: &[(&str, &[f64])]) -> Vec<OperandCalibration> {
+        let max_value = self.config.max_value() as f64;
+        
+        samples.iter()
+            .map(|&(name, data)| {
+                let magnitudes: Vec<f64> = data.iter().map(|v| v.abs()).collect();
+                let max_abs = magnitudes.iter().copied().fold(0.0, f64::max);
+                let clip_at = percentile(&magnitudes, CALIBRATION_PERCENTILE);
+                
+                let scale = if clip_at > 0.0 { max_value / clip_at } else { 1.0 };
+                let max_scale = if max_abs > 0.0 { max_value / max_abs } else { 1.0 };
+                let clipped = magnitudes.iter().filter(|&&m| m * scale > max_value).count();
+                let clip_rate = if data.is_empty() { 0.0 } else { clipped as f64 / data.len() as f64 };
+                
+                self.scales.insert(name.to_string(), scale);
+                OperandCalibration { name: name.to_string(), scale, max_scale, clip_rate }
+            })
+            .collect()
+    }
+    
+    /// Calibrated quantization scales by operand name
+    pub fn scales(&self) -> &HashMap<String, f64> {
+        &self.scales
+    }
+    
     /// Generate hardware program from tiled operations
     pub fn generate(&mut self, program: TiledProgram) -> CompileResult<HardwareProgram> {
-        let mut hw_program = HardwareProgram::new(self.config.clone());
-        
-        for op in &program.operations {
-            self.process_operation(op, &mut hw_program)?;
+        let mut stream = self.stream(program);
+        while let Some(pass) = stream.next() {
+            let pass = pass?;
+            stream.program.add_pass(pass);
         }
         
+        let mut hw_program = stream.program;
         hw_program.generate_summary();
         Ok(hw_program)
     }
     
+    /// Generate the passes of `program` one at a time
+    ///
+    /// Yields the same passes as `generate`, but only the current matmul's state
+    /// is held, so a consumer serializing passes as they arrive never stores the
+    /// whole stream. Iteration stops after the first error.
+    pub fn passes_iter(&mut self, program: TiledProgram) -> impl Iterator<Item = CompileResult<SystolicPass>> + '_ {
+        self.stream(program)
+    }
+    
+    /// Start a pass stream over `program`
+    fn stream(&mut self, program: TiledProgram) -> PassStream<'_> {
+        // Pass ids are per program; stored matrix data carries over
+        self.pass_counter = 0;
+        self.producers.clear();
+        self.addresses.clear();
+        self.next_addr = 0;
+        self.aliases.clear();
+        
+        PassStream {
+            program: HardwareProgram::new(self.config.clone()),
+            generator: self,
+            operations: program.operations.into_iter(),
+            matmul: None,
+            started: false,
+            done: false,
+        }
+    }
+    
+    /// Record which matmul groups `op`'s result depends on
+    ///
+    /// Host operations inherit their sources' dependencies; a matmul starts a new group.
+    fn track_dependencies(&mut self, op: &TiledOperation, program: &mut HardwareProgram) {
+        let mut deps: Vec<usize> = op.sources().iter()
+            .flat_map(|source| self.producers.get(*source).cloned().unwrap_or_default())
+            .collect();
+        deps.sort_unstable();
+        deps.dedup();
+        
+        if let TiledOperation::TiledMatMul { target, tiles, output_shape, .. } = op {
+            program.groups.push(MatMulGroup {
+                target: target.clone(),
+                first_pass: self.pass_counter,
+                num_passes: tiles.len(),
+                depends_on: deps,
+                shape: *output_shape,
+            });
+            deps = vec![program.groups.len() - 1];
+        }
+        
+        self.producers.insert(op.target().to_string(), deps);
+    }
+    
+    /// Record whether `op`'s result is real data or a zero placeholder
+    ///
+    /// Unknown inputs and host steps codegen does not compute are placeholders, and
+    /// anything computed from a placeholder is one too.
+    fn track_placeholders(&mut self, op: &TiledOperation) {
+        let placeholder = match op {
+            TiledOperation::Activation { .. } | TiledOperation::ScalarMul { .. } => true,
+            _ => op.sources().iter().any(|source| {
+                !self.matrix_data.contains_key(*source) || self.placeholders.contains(*source)
+            }),
+        };
+        if placeholder {
+            if let TiledOperation::LoadMatrix { source, .. } = op {
+                self.placeholders.insert(source.clone());
+            }
+            self.placeholders.insert(op.target().to_string());
+        } else {
+            self.placeholders.remove(op.target());
+        }
+    }
+    
     /// Process a single tiled operation
+    ///
+    /// A matmul is only set up here; its passes come from the returned stream.
     fn process_operation(
         &mut self,
         op: &TiledOperation,
         program: &mut HardwareProgram,
-    ) -> CompileResult<()> {
+    ) -> CompileResult<Option<MatMulStream>> {
         match op {
-            TiledOperation::LoadMatrix { target, source, shape } => {
-                // Reference to existing matrix - copy the reference
-                if let Some(data) = self.matrix_data.get(source) {
-                    self.matrix_data.insert(target.clone(), data.clone());
-                } else {
-                    // Placeholder - actual data will come from external source
-                    self.matrix_data.insert(target.clone(), MatrixData {
-                        data: vec![0.0; shape.0 * shape.1],
-                        shape: *shape,
-                    });
+            TiledOperation::LoadMatrix { target, source, shape, scale } => {
+                // Alias the source buffer rather than copying it
+                let buffer = match self.matrix_data.get(source) {
+                    Some(buffer) => Arc::clone(buffer),
+                    None => {
+                        // Placeholder - actual data will come from external source.
+                        // Register it under the source name so later reads share it.
+                        let buffer = Arc::new(MatrixData {
+                            data: vec![0.0; shape.0 * shape.1],
+                            shape: *shape,
+                        });
+                        self.matrix_data.insert(source.clone(), Arc::clone(&buffer));
+                        buffer
+                    }
+                };
+                self.matrix_data.insert(target.clone(), buffer);
+                let root = self.aliases.get(source).unwrap_or(source).clone();
+                self.aliases.insert(target.clone(), root);
+                // A calibrated scale takes precedence over the one the input was defined with
+                if let Some(scale) = scale {
+                    self.scales.entry(source.clone()).or_insert(*scale);
+                }
+                if let Some(&scale) = self.scales.get(source) {
+                    self.scales.insert(target.clone(), scale);
                 }
-                Ok(())
+                Ok(None)
             }
             
             TiledOperation::LoadLiteral { target, data, shape } => {
-                self.matrix_data.insert(target.clone(), MatrixData {
-                    data: data.clone(),
-                    shape: *shape,
-                });
-                Ok(())
+                self.store(target, data.clone(), *shape);
+                Ok(None)
             }
             
             TiledOperation::TiledMatMul {
@@ -85,8 +335,11 @@ impl CodeGenerator {
                 output_shape,
                 tiles,
                 tile_size,
+                accumulate,
+                epilogue,
+                transposed,
             } => {
-                self.generate_tiled_matmul(
+                self.start_matmul(
                     program,
                     target,
                     left_source,
@@ -96,28 +349,184 @@ impl CodeGenerator {
                     *output_shape,
                     tiles,
                     *tile_size,
+                    accumulate.as_deref(),
+                    epilogue,
+                    *transposed,
                 )
+                .map(Some)
             }
             
-            TiledOperation::Add { target, shape, .. } |
-            TiledOperation::Sub { target, shape, .. } |
-            TiledOperation::ElementMul { target, shape, .. } |
-            TiledOperation::ScalarMul { target, shape, .. } |
-            TiledOperation::Transpose { target, shape, .. } => {
-                // These operations are handled outside the systolic array
-                // Just track the output shape
-                self.matrix_data.insert(target.clone(), MatrixData {
-                    data: vec![0.0; shape.0 * shape.1],
+            TiledOperation::Index { target, source, rows, cols, shape } => {
+                self.gather(target, source, rows, cols, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Split { target, source, axis, part, shape, .. } => {
+                let (rows, cols) = split_block(*axis, *part, *shape);
+                self.gather(target, source, &rows, &cols, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Concat { target, left, right, axis, shape } => {
+                let (l, r) = (self.matrix_data.get(left), self.matrix_data.get(right));
+                let data = match (l, r) {
+                    (Some(l), Some(r)) => concat_data(&l.data, &r.data, *axis, *shape),
+                    _ => vec![0.0; shape.0 * shape.1],
+                };
+                self.store(target, data, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Roll { target, source, shift, axis, shape } => {
+                let data = roll_data(&self.buffer_or_zeros(source, *shape).data, *shift, *axis, *shape);
+                self.store(target, data, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Norm { target, source, axis, source_shape, shape } => {
+                let data = norm_data(&self.buffer_or_zeros(source, *source_shape).data, *axis, *source_shape);
+                self.store(target, data, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Diag { target, source, source_shape, shape } => {
+                let data = diag_data(&self.buffer_or_zeros(source, *source_shape).data, *source_shape, *shape);
+                self.store(target, data, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Clip { target, source, min, max, shape } => {
+                // Clamped on the host; later passes read the clamped data
+                let data = self.buffer_or_zeros(source, *shape).data.iter().map(|v| v.clamp(*min, *max)).collect();
+                self.store(target, data, *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Reshape { target, source, source_shape, shape } => {
+                // Same row-major data under a new shape
+                let data = self.buffer_or_zeros(source, *source_shape).data.clone();
+                self.store(target, data, *shape);
+                if let Some(&scale) = self.scales.get(source) {
+                    self.scales.insert(target.clone(), scale);
+                }
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Activation { target, source, kind, shape } => {
+                // Applied by the host after the array produces `source`
+                program.epilogues.push(EpilogueStep {
+                    target: target.clone(),
+                    source: source.clone(),
+                    activation: *kind,
                     shape: *shape,
                 });
+                self.store(target, vec![0.0; shape.0 * shape.1], *shape);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Add { target, left, right, shape, .. } => {
+                self.elementwise(target, left, right, *shape, |a, b| a + b);
                 program.output_shape = *shape;
-                Ok(())
+                Ok(None)
+            }
+            TiledOperation::Sub { target, left, right, shape, .. } => {
+                self.elementwise(target, left, right, *shape, |a, b| a - b);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            TiledOperation::ElementMul { target, left, right, shape } => {
+                self.elementwise(target, left, right, *shape, |a, b| a * b);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            TiledOperation::ElementDiv { target, left, right, shape } => {
+                // Placeholder data would divide by zero; leave the result a placeholder too
+                if self.placeholders.contains(target) {
+                    self.store(target, vec![0.0; shape.0 * shape.1], *shape);
+                    program.output_shape = *shape;
+                    return Ok(None);
+                }
+                if self.buffer_or_zeros(right, *shape).data.contains(&0.0) {
+                    return Err(CompileError::invalid_op(format!("Division by zero in '{}'", target)));
+                }
+                self.elementwise(target, left, right, *shape, |a, b| a / b);
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::Transpose { target, source, shape } => {
+                // Later passes read the transposed layout, so materialize it on the host
+                let src = self.buffer_or_zeros(source, (shape.1, shape.0));
+                self.store(target, transpose_data(&src, *shape).data, *shape);
+                if let Some(&scale) = self.scales.get(source) {
+                    self.scales.insert(target.clone(), scale);
+                }
+                program.output_shape = *shape;
+                Ok(None)
+            }
+            
+            TiledOperation::ScalarMul { target, shape, .. } => {
+                // These operations are handled outside the systolic array
+                // Just track the output shape
+                self.store(target, vec![0.0; shape.0 * shape.1], *shape);
+                program.output_shape = *shape;
+                Ok(None)
             }
         }
     }
     
-    /// Generate passes for a tiled matrix multiplication
-    fn generate_tiled_matmul(
+    /// Store a freshly computed buffer under `name`
+    fn store(&mut self, name: &str, data: Vec<f64>, shape: (usize, usize)) {
+        self.matrix_data.insert(name.to_string(), Arc::new(MatrixData { data, shape }));
+    }
+    
+    /// Copy the selected elements of `source` when its data is known
+    fn gather(&mut self, target: &str, source: &str, rows: &[usize], cols: &[usize], shape: (usize, usize)) {
+        let data = match self.matrix_data.get(source) {
+            Some(src) => {
+                let src_cols = src.shape.1;
+                rows.iter()
+                    .flat_map(|&i| cols.iter().map(move |&j| i * src_cols + j))
+                    .map(|idx| src.data.get(idx).copied().unwrap_or(0.0))
+                    .collect()
+            }
+            None => vec![0.0; shape.0 * shape.1],
+        };
+        self.store(target, data, shape);
+    }
+    
+    /// Compute an element-wise result on the host so later passes see its data
+    fn elementwise(&mut self, target: &str, left: &str, right: &str, shape: (usize, usize), f: impl Fn(f64, f64) -> f64) {
+        let (l, r) = (self.buffer_or_zeros(left, shape), self.buffer_or_zeros(right, shape));
+        let data = match broadcast_zip((&l.data, l.shape), (&r.data, r.shape), f) {
+            Some((result_shape, data)) if result_shape == shape => data,
+            _ => vec![0.0; shape.0 * shape.1],
+        };
+        self.store(target, data, shape);
+    }
+    
+    /// Shared handle to a stored buffer, or zeros if it was never defined
+    fn buffer_or_zeros(&self, name: &str, shape: (usize, usize)) -> Arc<MatrixData> {
+        self.matrix_data.get(name).cloned().unwrap_or_else(|| {
+            Arc::new(MatrixData {
+                data: vec![0.0; shape.0 * shape.1],
+                shape,
+            })
+        })
+    }
+    
+    /// Check and place a tiled matrix multiplication, ready to emit its passes
+    #[allow(clippy::too_many_arguments)]
+    fn start_matmul(
         &mut self,
         program: &mut HardwareProgram,
         target: &str,
@@ -128,39 +537,185 @@ impl CodeGenerator {
         output_shape: (usize, usize),
         tiles: &[MatMulTile],
         tile_size: usize,
-    ) -> CompileResult<()> {
-        let left_data = self.matrix_data.get(left_source)
-            .map(|d| d.data.clone())
-            .unwrap_or_else(|| vec![0.0; left_shape.0 * left_shape.1]);
-        
-        let right_data = self.matrix_data.get(right_source)
-            .map(|d| d.data.clone())
-            .unwrap_or_else(|| vec![0.0; right_shape.0 * right_shape.1]);
+        accumulate: Option<&str>,
+        epilogue: &[EpilogueStage],
+        transposed: bool,
+    ) -> CompileResult<MatMulStream> {
+        // A transposed operand's buffer is stored with its rows and columns swapped
+        let stored = |(rows, cols): (usize, usize)| if transposed { (cols, rows) } else { (rows, cols) };
+        let mut left = self.buffer_or_zeros(left_source, stored(left_shape));
+        let mut right = self.buffer_or_zeros(right_source, stored(right_shape));
+        if transposed {
+            left = Arc::new(transpose_data(&left, left_shape));
+            right = Arc::new(transpose_data(&right, right_shape));
+        }
         
         program.output_shape = output_shape;
         
-        for tile in tiles {
-            let pass = self.generate_matmul_pass(
-                tile,
-                &left_data,
-                left_shape,
-                &right_data,
-                right_shape,
-                tile_size,
-            )?;
-            program.add_pass(pass);
+        let scales = (self.scale_of(left_source), self.scale_of(right_source));
+        if self.config.integer_only {
+            if scales != (1.0, 1.0) {
+                return Err(CompileError::invalid_op(
+                    "Integer-only mode does not allow calibrated operand scales",
+                ));
+            }
+            let name = |source: &str| self.aliases.get(source).map_or(source, String::as_str).to_string();
+            check_integer_data(&name(left_source), &left.data, &self.config)?;
+            check_integer_data(&name(right_source), &right.data, &self.config)?;
         }
+        let k = left_shape.1;
+        // Known data is bounded by its largest quantized value; run-time data by
+        // `max_magnitude`, since `min_value` outweighs `max_value`
+        let largest = |source: &str, data: &[f64], scale: f64| {
+            if self.placeholders.contains(source) || !self.matrix_data.contains_key(source) {
+                return self.config.max_magnitude();
+            }
+            quantize_matrix(data, scale, &self.config).iter().map(|v| (*v as i128).abs()).max().unwrap_or(0)
+        };
+        let bounds = (largest(left_source, &left.data, scales.0), largest(right_source, &right.data, scales.1));
+        let worst_case = (k as i128).saturating_mul(bounds.0.saturating_mul(bounds.1));
+        if !self.config.saturate_accumulators && worst_case > self.config.acc_max() as i128 {
+            let products = if bounds.0 == bounds.1 {
+                format!("{}^2", bounds.0)
+            } else {
+                format!("{} * {}", bounds.0, bounds.1)
+            };
+            return Err(CompileError::codegen(format!(
+                "Accumulator overflow in '{}': K = {} products of up to {} can sum to {}, beyond the {}-bit accumulator limit of {}",
+                target,
+                k,
+                products,
+                worst_case,
+                self.config.acc_width,
+                self.config.acc_max()
+            )));
+        }
+        let a_base = self.allocate(program, left_source, stored(left_shape), tile_size);
+        let b_base = self.allocate(program, right_source, stored(right_shape), tile_size);
         
-        // Store placeholder for output
-        self.matrix_data.insert(target.to_string(), MatrixData {
-            data: vec![0.0; output_shape.0 * output_shape.1],
-            shape: output_shape,
-        });
+        Ok(MatMulStream {
+            target: target.to_string(),
+            left,
+            right,
+            left_shape,
+            right_shape,
+            output_shape,
+            tiles: tiles.to_vec(),
+            next_tile: 0,
+            tile_size,
+            // Residual values enter the accumulators at the product's scale
+            residual: accumulate.map(|name| self.buffer_or_zeros(name, output_shape)),
+            epilogue: epilogue.to_vec(),
+            scales,
+            bases: (a_base, b_base),
+            transposed,
+            acc: vec![0i64; tile_size * tile_size],
+            output: vec![0.0; output_shape.0 * output_shape.1],
+        })
+    }
+    
+    /// Emit the pass for the next tile of `matmul`, or store its product once every tile is done
+    fn next_matmul_pass(&mut self, matmul: &mut MatMulStream) -> CompileResult<Option<SystolicPass>> {
+        let Some(tile) = matmul.tiles.get(matmul.next_tile) else {
+            self.store(&matmul.target, std::mem::take(&mut matmul.output), matmul.output_shape);
+            return Ok(None);
+        };
+        matmul.next_tile += 1;
+        let (tile_size, output_shape) = (matmul.tile_size, matmul.output_shape);
+        let acc_scale = matmul.scales.0 * matmul.scales.1;
+        let (acc_min, acc_max) = (self.config.acc_min(), self.config.acc_max());
+        let layout = self.config.memory_layout;
+        
+        let mut pass = self.generate_matmul_pass(
+            tile,
+            &matmul.left.data,
+            matmul.left_shape,
+            &matmul.right.data,
+            matmul.right_shape,
+            tile_size,
+            matmul.scales,
+        )?;
+        let offset = |(rows, cols): (usize, usize), row: usize, col: usize| match matmul.transposed {
+            false => layout.tile_offset((rows, cols), tile_size, row, col),
+            true => layout.tile_offset((cols, rows), tile_size, col, row),
+        };
+        pass.a_addr = matmul.bases.0 + offset(matmul.left_shape, tile.a_row_range.0, tile.a_col_range.0);
+        pass.b_addr = matmul.bases.1 + offset(matmul.right_shape, tile.b_row_range.0, tile.b_col_range.0);
         
-        Ok(())
+        // Accumulator contents of the current output tile; K tiles are consecutive
+        let acc = &mut matmul.acc;
+        if tile.is_first_k {
+            acc.fill(0);
+            if let Some(residual) = &matmul.residual {
+                for i in tile.a_row_range.0..tile.a_row_range.1 {
+                    for j in tile.b_col_range.0..tile.b_col_range.1 {
+                        let value = self.config.rounding.round(residual.data[i * output_shape.1 + j] * acc_scale) as i64;
+                        acc[(i - tile.a_row_range.0) * tile_size + (j - tile.b_col_range.0)] = value.clamp(acc_min, acc_max);
+                    }
+                }
+                pass.acc_init = acc.clone();
+            }
+        }
+        if tile.reload {
+            pass.acc_init = acc.clone();
+        }
+        let product = tile_product(&pass.matrix_a, &pass.matrix_b, tile_size, self.config.dataflow);
+        acc.iter_mut().zip(product).for_each(|(sum, p)| *sum = sum.wrapping_add(p));
+        if tile.is_last_k {
+            // Finished sums saturate to the accumulator range as they drain, as in `Simulator`
+            acc.iter_mut().for_each(|value| *value = (*value).clamp(acc_min, acc_max));
+            pass.expected_output = acc.clone();
+            let drained = if matmul.epilogue.is_empty() {
+                acc.iter().map(|&value| value as f64 / acc_scale).collect()
+            } else {
+                pass.epilogue = self.epilogue_ops(&matmul.epilogue, tile, tile_size, output_shape, acc_scale);
+                pass.apply_epilogue(acc)
+            };
+            // The product in real units, so a chained matmul reading it sees its data
+            for i in tile.a_row_range.0..tile.a_row_range.1 {
+                for j in tile.b_col_range.0..tile.b_col_range.1 {
+                    matmul.output[i * output_shape.1 + j] = drained[(i - tile.a_row_range.0) * tile_size + (j - tile.b_col_range.0)];
+                }
+            }
+        }
+        
+        Ok(Some(pass))
+    }
+    
+    /// Fused epilogue of one final pass: dequantize, then the stages with bias tiles sliced out
+    fn epilogue_ops(
+        &self,
+        epilogue: &[EpilogueStage],
+        tile: &MatMulTile,
+        tile_size: usize,
+        output_shape: (usize, usize),
+        acc_scale: f64,
+    ) -> Vec<EpilogueOp> {
+        let mut ops = vec![EpilogueOp::Dequantize { scale: acc_scale }];
+        ops.extend(epilogue.iter().map(|stage| match stage {
+            EpilogueStage::Bias(name) => {
+                let bias = self.buffer_or_zeros(name, output_shape);
+                let mut values = vec![0.0; tile_size * tile_size];
+                for i in tile.a_row_range.0..tile.a_row_range.1 {
+                    for j in tile.b_col_range.0..tile.b_col_range.1 {
+                        // A bias row, column or single element broadcasts over the product
+                        let (row, col) = (if bias.shape.0 == 1 { 0 } else { i }, if bias.shape.1 == 1 { 0 } else { j });
+                        let idx = row * bias.shape.1 + col;
+                        values[(i - tile.a_row_range.0) * tile_size + (j - tile.b_col_range.0)] =
+                            bias.data.get(idx).copied().unwrap_or(0.0);
+                    }
+                }
+                EpilogueOp::Bias(values)
+            }
+            EpilogueStage::Scale(factor) => EpilogueOp::Scale(*factor),
+            EpilogueStage::Activation(kind) => EpilogueOp::Activation(*kind),
+            EpilogueStage::Clip { min, max } => EpilogueOp::Clip { min: *min, max: *max },
+        }));
+        ops
     }
     
     /// Generate a single systolic array pass
+    #[allow(clippy::too_many_arguments)]
     fn generate_matmul_pass(
         &mut self,
         tile: &MatMulTile,
@@ -169,6 +724,7 @@ impl CodeGenerator {
         right_data: &[f64],
         right_shape: (usize, usize),
         tile_size: usize,
+        (a_scale, b_scale): (f64, f64),
     ) -> CompileResult<SystolicPass> {
         let pass_id = self.pass_counter;
         self.pass_counter += 1;
@@ -197,21 +753,21 @@ impl CodeGenerator {
             }
         }
         
-        // Pad tiles to array size
-        let padded_a = pad_matrix(
-            &quantize_matrix(&a_tile, 1.0, &self.config),
-            a_rows, a_cols,
-            tile_size, tile_size,
-        );
+        // Pad tiles to array size. Padded K entries meet in every real output, so they are
+        // always 0; only the rows of A and columns of B past the edge take the zero-point.
+        let zero_point = self.config.zero_point;
+        let a_k_padded = pad_matrix(&quantize_matrix(&a_tile, a_scale, &self.config), a_rows, a_cols, a_rows, tile_size);
+        let padded_a = pad_matrix_with_value(&a_k_padded, a_rows, tile_size, tile_size, tile_size, zero_point);
         
-        let padded_b_row_major = pad_matrix(
-            &quantize_matrix(&b_tile, 1.0, &self.config),
-            b_rows, b_cols,
-            tile_size, tile_size,
-        );
+        let b_k_padded = pad_matrix(&quantize_matrix(&b_tile, b_scale, &self.config), b_rows, b_cols, tile_size, b_cols);
+        let padded_b_row_major = pad_matrix_with_value(&b_k_padded, tile_size, b_cols, tile_size, tile_size, zero_point);
         
-        // Convert B to column-major for hardware
-        let padded_b = row_to_column_major(&padded_b_row_major, tile_size, tile_size);
+        // Output-stationary arrays stream B column-major; weight-stationary preload it row-major
+        let padded_b = if self.config.dataflow.b_column_major() {
+            row_to_column_major(&padded_b_row_major, tile_size, tile_size)
+        } else {
+            padded_b_row_major
+        };
         
         let operation = if tile.is_first_k && tile.is_last_k {
             PassOperation::Final
@@ -227,10 +783,8 @@ impl CodeGenerator {
             id: pass_id,
             description: format!(
                 "C[{}:{}, {}:{}] += A[{}:{}, {}:{}] @ B[{}:{}, {}:{}]",
-                tile.output_row * tile_size,
-                (tile.output_row + 1) * tile_size,
-                tile.output_col * tile_size,
-                (tile.output_col + 1) * tile_size,
+                tile.a_row_range.0, tile.a_row_range.1,
+                tile.b_col_range.0, tile.b_col_range.1,
                 tile.a_row_range.0, tile.a_row_range.1,
                 tile.a_col_range.0, tile.a_col_range.1,
                 tile.b_row_range.0, tile.b_row_range.1,
@@ -248,13 +802,42 @@ impl CodeGenerator {
                 tile.output_col * tile_size,
             ),
             operation,
+            a_addr: 0,
+            b_addr: 0,
+            a_scale,
+            b_scale,
+            expected_output: Vec::new(),
+            acc_init: Vec::new(),
+            spill: tile.spill,
+            reload: tile.reload,
+            epilogue: Vec::new(),
         })
     }
+    
+    fn scale_of(&self, name: &str) -> f64 {
+        self.scales.get(name).copied().unwrap_or(1.0)
+    }
+    
+    /// Base address of an operand buffer, placing it after the last one on first use
+    ///
+    /// Loads of the same variable resolve to that variable's buffer.
+    fn allocate(&mut self, program: &mut HardwareProgram, name: &str, shape: (usize, usize), tile_size: usize) -> usize {
+        let name = self.aliases.get(name).map_or(name, String::as_str);
+        if let Some(&addr) = self.addresses.get(name) {
+            return addr;
+        }
+        let addr = self.next_addr;
+        self.next_addr += self.config.memory_layout.buffer_size(shape, tile_size);
+        self.addresses.insert(name.to_string(), addr);
+        program.inputs.push(OperandBuffer { name: name.to_string(), addr, shape });
+        addr
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hardware::MemoryLayout;
     use crate::tiling::TilingStrategy;
     use crate::analyzer::Analyzer;
     use crate::parser::Parser;
@@ -284,6 +867,34 @@ mod tests {
         assert_eq!(hw_program.output_shape, (2, 2));
     }
     
+    #[test]
+    fn test_passes_iter_matches_generate() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let source = "A = [[1, 2, 3], [4, 5, 6], [7, 8, 9]]; C = relu(A @ A) @ A.T";
+        let tiled = || {
+            let typed = Analyzer::new().analyze(Parser::new(source).parse_program().unwrap()).unwrap();
+            TilingStrategy::new(config.clone()).tile_program(&typed).unwrap()
+        };
+        
+        let program = CodeGenerator::new(config.clone()).generate(tiled()).unwrap();
+        let mut codegen = CodeGenerator::new(config.clone());
+        let streamed: Vec<_> = codegen.passes_iter(tiled()).collect::<CompileResult<_>>().unwrap();
+        
+        assert_eq!(streamed.len(), program.passes.len());
+        assert_eq!(streamed[0].content_hash(), program.passes[0].content_hash());
+        // The second matmul reads the first one's product, computed as its passes streamed by
+        let last = program.passes.len() - 1;
+        assert_eq!(streamed[last].expected_output, program.passes[last].expected_output);
+        
+        // An error ends the stream
+        let typed = Analyzer::new().analyze(Parser::new("C = [[127]] @ [[127]]").parse_program().unwrap()).unwrap();
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        let mut codegen = CodeGenerator::new(SystolicConfig::new(2, 8, 8));
+        let mut passes = codegen.passes_iter(tiled);
+        assert!(matches!(passes.next(), Some(Err(_))));
+        assert!(passes.next().is_none());
+    }
+    
     #[test]
     fn test_literal_matrix_codegen() {
         let config = SystolicConfig::new(3, 8, 32);
@@ -311,4 +922,287 @@ mod tests {
         assert_eq!(pass.matrix_a[3], 3);
         assert_eq!(pass.matrix_a[4], 4);
     }
+    
+    #[test]
+    fn test_broadcast_operand_data() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile("C = ([[1, 2], [3, 4]] * [[2]] + 1) @ [[1, 1], [0, 1]]", &config).unwrap();
+        
+        assert_eq!(program.passes[0].matrix_a, vec![3, 5, 0, 7, 9, 0, 0, 0, 0]);
+    }
+    
+    #[test]
+    fn test_chained_matmul_reads_intermediate() {
+        let config = SystolicConfig::new(4, 8, 32);
+        let program = crate::compile(
+            "G = [[1, 0, 2], [0, 1, 1]] @ [[1, 2, 0, 1], [0, 1, 1, 0], [1, 0, 1, 2]] @ [[1, 0], [0, 1], [1, 1], [2, 0]]",
+            &config,
+        )
+        .unwrap();
+        
+        // (A @ B) lands in a named (2, 4) buffer that the second matmul depends on
+        assert_eq!(program.groups.len(), 2);
+        assert_eq!(program.groups[0].target, "G_left");
+        assert_eq!(program.groups[0].shape, (2, 4));
+        assert_eq!(program.groups[1].depends_on, vec![0]);
+        
+        // The second pass streams the computed product, not a zeroed placeholder
+        let second = &program.passes[1];
+        assert_eq!(second.a_shape, (2, 4));
+        assert_eq!(second.matrix_a[..8], [3, 2, 2, 5, 1, 1, 2, 2]);
+        assert_eq!(program.evaluate().unwrap(), vec![vec![15.0, 4.0], vec![7.0, 3.0]]);
+    }
+    
+    #[test]
+    fn test_statement_reads_earlier_result() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let program = crate::compile("C = [[1, 2], [3, 4]] @ [[1, 1], [0, 1]]; D = C @ [[2, 0], [0, 1]]", &config).unwrap();
+        
+        // D's pass streams C's computed values and waits on C's group
+        assert_eq!(program.groups[1].depends_on, vec![0]);
+        assert_eq!(program.passes[1].matrix_a, vec![1, 3, 3, 7]);
+        assert_eq!(program.evaluate().unwrap(), vec![vec![2.0, 3.0], vec![6.0, 7.0]]);
+    }
+    
+    #[test]
+    fn test_indexed_operand_codegen() {
+        let config = SystolicConfig::new(3, 8, 32);
+        
+        // Row 1 of one literal and column 0 of another
+        let mut parser = Parser::new("r = [[1, 2], [3, 4]][1]; C = [[5, 6], [7, 8]][:, 0]");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiler = TilingStrategy::new(config.clone());
+        let tiled = tiler.tile_program(&typed).unwrap();
+        
+        let mut codegen = CodeGenerator::new(config);
+        codegen.generate(tiled).unwrap();
+        
+        assert_eq!(codegen.matrix_data["r"].data, vec![3.0, 4.0]);
+        assert_eq!(codegen.matrix_data["C"].data, vec![5.0, 7.0]);
+    }
+    
+    #[test]
+    fn test_activation_recorded_as_epilogue() {
+        let config = SystolicConfig::new(3, 8, 32);
+        
+        // A relu straight off a matmul would be fused, so it runs on the host after the add
+        let mut parser = Parser::new("Y = relu(A @ B + B)");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 2));
+        analyzer.define_matrix("B", (2, 2));
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiler = TilingStrategy::new(config.clone());
+        let tiled = tiler.tile_program(&typed).unwrap();
+        
+        let mut codegen = CodeGenerator::new(config);
+        let hw_program = codegen.generate(tiled).unwrap();
+        
+        assert_eq!(hw_program.passes.len(), 1);
+        assert_eq!(hw_program.epilogues.len(), 1);
+        assert_eq!(hw_program.epilogues[0].activation, crate::ast::Activation::Relu);
+        assert_eq!(hw_program.epilogues[0].source, "Y_act_source");
+    }
+    
+    #[test]
+    fn test_fused_activation_marks_final_passes() {
+        // A lone activation is fused without `fuse_epilogue`
+        let config = SystolicConfig::new(2, 8, 32);
+        let program = crate::compile_with_shapes("Y = relu(A @ B)", &[("A", (2, 4)), ("B", (4, 2))], &config).unwrap();
+        
+        // Two K passes; only the one that drains the accumulators applies the activation
+        assert_eq!(program.passes.len(), 2);
+        assert!(program.passes[0].epilogue.is_empty());
+        assert_eq!(
+            program.passes[1].epilogue,
+            vec![EpilogueOp::Dequantize { scale: 1.0 }, EpilogueOp::Activation(crate::ast::Activation::Relu)]
+        );
+        assert!(program.epilogues.is_empty());
+        
+        let json: serde_json::Value = serde_json::from_str(&program.to_json().unwrap()).unwrap();
+        assert_eq!(json["passes"][1]["epilogue"][1], serde_json::json!({ "Activation": "Relu" }));
+    }
+    
+    #[test]
+    fn test_folded_transpose_matches_explicit() {
+        let config = SystolicConfig::new(2, 8, 32).with_memory_layout(MemoryLayout::Tiled);
+        let (a, b) = ("[[1, 2, 3, 4], [5, 6, 7, 8], [-1, 0, 1, 0]]", "[[1, 0], [2, -1], [0, 3], [1, 1]]");
+        let folded = crate::compile(&format!("D = ({} @ {}).T", a, b), &config).unwrap();
+        let explicit = crate::compile(&format!("D = {}.T @ {}.T", b, a), &config).unwrap();
+        
+        assert_eq!(folded.output_shape, (2, 3));
+        assert_eq!(folded.passes.len(), explicit.passes.len());
+        for (folded, explicit) in folded.passes.iter().zip(&explicit.passes) {
+            assert_eq!(folded.matrix_a, explicit.matrix_a);
+            assert_eq!(folded.matrix_b, explicit.matrix_b);
+            assert_eq!(folded.expected_output, explicit.expected_output);
+        }
+        
+        // Operands stay in their original layout; A passes read B's tiles column-wise
+        let program = crate::compile_with_shapes("D = (A @ B).T", &[("A", (3, 4)), ("B", (4, 2))], &config).unwrap();
+        let inputs: Vec<_> = program.inputs.iter().map(|input| (input.name.as_str(), input.addr, input.shape)).collect();
+        assert_eq!(inputs, vec![("B", 0, (4, 2)), ("A", 8, (3, 4))]);
+        let addrs: Vec<_> = program.passes.iter().map(|p| (p.a_addr, p.b_addr)).collect();
+        assert_eq!(addrs[..4], [(0, 8), (4, 8 + 4), (0, 8 + 8), (4, 8 + 12)]);
+    }
+    
+    #[test]
+    fn test_expected_output_saturates() {
+        let config = SystolicConfig::new(2, 8, 12).with_saturate_accumulators(true);
+        let source = "C = [[127, 127], [1, 1]] @ [[127, 1], [127, 1]]";
+        let program = crate::compile(source, &config).unwrap();
+        
+        let tiled = TilingStrategy::new(config.clone())
+            .tile_program(&Analyzer::new().analyze(Parser::new(source).parse_program().unwrap()).unwrap())
+            .unwrap();
+        let simulated = crate::simulator::Simulator::new(config).run(&tiled).unwrap();
+        let expected: Vec<f64> = program.passes[0].expected_output.iter().map(|&v| v as f64).collect();
+        assert_eq!(expected, simulated.data);
+        assert_eq!(expected[0], 2047.0);
+        assert_eq!(program.evaluate().unwrap(), simulated.to_rows());
+    }
+    
+    #[test]
+    fn test_division_by_zero() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let err = crate::compile("C = ([[1, 2]] / [[1, 0]]) @ [[1], [1]]", &config).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid operation: Division by zero in 'C_left'");
+        
+        // A run-time divisor is only a zero placeholder, so nothing is divided on the host
+        let shapes = [("A", (2, 2)), ("B", (2, 2)), ("D", (2, 2))];
+        let program = crate::compile_with_shapes("C = (A / B) @ D", &shapes, &config).unwrap();
+        assert_eq!(program.passes[0].matrix_a, vec![0; 4]);
+    }
+    
+    #[test]
+    fn test_alias_shares_buffer() {
+        let config = SystolicConfig::new(3, 8, 32);
+        
+        let mut parser = Parser::new("A = [[1, 2], [3, 4]]; B = A; C = B @ D");
+        let program = parser.parse_program().unwrap();
+        
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("D", (2, 2));
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiler = TilingStrategy::new(config.clone());
+        let tiled = tiler.tile_program(&typed).unwrap();
+        
+        let mut codegen = CodeGenerator::new(config);
+        let hw_program = codegen.generate(tiled).unwrap();
+        
+        // B and the matmul's left operand both resolve to A's buffer
+        let a = &codegen.matrix_data["A"];
+        assert!(Arc::ptr_eq(a, &codegen.matrix_data["B"]));
+        assert!(Arc::ptr_eq(a, &codegen.matrix_data["C_left"]));
+        assert_eq!(hw_program.passes[0].matrix_a[..2], [1, 2]);
+    }
+    
+    #[test]
+    fn test_operand_addresses() {
+        let generate = |config: SystolicConfig, shapes: [(&str, (usize, usize)); 2]| {
+            let program = Parser::new("C = A @ B").parse_program().unwrap();
+            let mut analyzer = Analyzer::new();
+            for (name, shape) in shapes {
+                analyzer.define_matrix(name, shape);
+            }
+            let typed = analyzer.analyze(program).unwrap();
+            let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+            CodeGenerator::new(config).generate(tiled).unwrap()
+        };
+        
+        // Two output row tiles reuse the single B tile
+        let hw_program = generate(SystolicConfig::new(3, 8, 32), [("A", (6, 3)), ("B", (3, 3))]);
+        let addrs: Vec<_> = hw_program.passes.iter().map(|p| (p.a_addr, p.b_addr)).collect();
+        assert_eq!(addrs, vec![(0, 18), (9, 18)]);
+        
+        // Along K, a contiguous A tile starts 3 columns in; a tiled one a whole tile in
+        let shapes = [("A", (3, 6)), ("B", (6, 3))];
+        let contiguous = generate(SystolicConfig::new(3, 8, 32), shapes);
+        let tiled = generate(SystolicConfig::new(3, 8, 32).with_memory_layout(MemoryLayout::Tiled), shapes);
+        assert_eq!(contiguous.passes[1].a_addr, 3);
+        assert_eq!(tiled.passes[1].a_addr, 9);
+        assert_eq!(tiled.passes[1].b_addr, 18 + 9);
+    }
+    
+    #[test]
+    fn test_calibration_ignores_outliers() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let mut codegen = CodeGenerator::new(config.clone());
+        
+        let mut sample = vec![0.5; 99];
+        sample.push(100.0);
+        let report = codegen.calibrate(&[("A", &sample)]);
+        
+        assert_eq!(report[0].scale, 254.0);
+        assert_eq!(report[0].max_scale, 1.27);
+        assert_eq!(report[0].clip_rate, 0.01);
+        
+        // The scale follows A through its alias into the matmul operand
+        let program = Parser::new("C = A @ B").parse_program().unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (2, 2));
+        analyzer.define_matrix("B", (2, 2));
+        let typed = analyzer.analyze(program).unwrap();
+        let tiled = TilingStrategy::new(config).tile_program(&typed).unwrap();
+        let hw_program = codegen.generate(tiled).unwrap();
+        
+        assert_eq!(hw_program.passes[0].a_scale, 254.0);
+        assert_eq!(hw_program.passes[0].b_scale, 1.0);
+    }
+    
+    #[test]
+    fn test_defined_scale_uses_full_range() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = Parser::new("W = [[0.5, 1], [0.25, 0]]; C = W @ [[1, 1], [0, 1]]").parse_program().unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.define_scaled_matrix("W", (2, 2), 127.0);
+        let typed = analyzer.analyze(program).unwrap();
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        let hw_program = CodeGenerator::new(config).generate(tiled).unwrap();
+        
+        // Values in [0, 1] reach the int8 maximum instead of rounding to 0 or 1
+        let pass = &hw_program.passes[0];
+        assert_eq!(pass.a_scale, 127.0);
+        assert_eq!(pass.b_scale, 1.0);
+        assert_eq!(pass.matrix_a[..5], [64, 127, 0, 32, 0]);
+        assert_eq!(hw_program.evaluate().unwrap(), vec![vec![64.0 / 127.0, 191.0 / 127.0], vec![32.0 / 127.0, 32.0 / 127.0]]);
+    }
+    
+    #[test]
+    fn test_accumulator_overflow_rejected() {
+        let config = SystolicConfig::new(4, 8, 16);
+        let err = crate::compile_with_shapes("C = A @ B", &[("A", (2, 1000)), ("B", (1000, 2))], &config).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Code generation error: Accumulator overflow in 'C': K = 1000 products of up to 128^2 can sum to 16384000, \
+             beyond the 16-bit accumulator limit of 32767"
+        );
+        
+        // (-128)^2 = 16384 still fits, but 2 * 16384 = 32768 does not
+        assert!(crate::compile_with_shapes("C = A @ B", &[("A", (2, 1)), ("B", (1, 2))], &config).is_ok());
+        assert!(crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).is_err());
+        
+        // Known data is bounded by its own largest values, not the data width's
+        let int16 = SystolicConfig::new(4, 16, 32);
+        assert!(crate::compile("C = [[1, 1, 1]] @ [[1], [1], [1]]", &int16).is_ok());
+        let err = crate::compile_with_shapes("C = A @ B", &[("A", (1, 3)), ("B", (3, 1))], &int16).unwrap_err();
+        assert!(err.to_string().contains("K = 3 products of up to 32768^2"), "{}", err);
+        let err = crate::compile("C = [[30000, 1, 1]] @ [[30000], [1], [1]]", &int16).unwrap_err();
+        assert!(err.to_string().contains("K = 3 products of up to 30000^2 can sum to 2700000000"), "{}", err);
+        // Data derived from a run-time input is still unknown
+        let deep = "C = (A + 1) @ [[30000], [30000], [30000]]";
+        let err = crate::compile_with_shapes(deep, &[("A", (1, 3))], &int16).unwrap_err();
+        assert!(err.to_string().contains("products of up to 32768 * 30000"), "{}", err);
+        
+        // Opting into saturation compiles the deep K anyway
+        let saturating = config.with_saturate_accumulators(true);
+        assert!(crate::compile_with_shapes("C = A @ B", &[("A", (2, 1000)), ("B", (1000, 2))], &saturating).is_ok());
+    }
 }
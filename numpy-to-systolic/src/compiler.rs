@@ -0,0 +1,181 @@
+//! Incremental compiler that keeps state across inputs
+//!
+//! Unlike the one-shot `compile` functions, a `Compiler` remembers the shapes
+//! and data of every variable it has seen, so later inputs can refer to
+//! results defined by earlier ones:
+//!
+//! ```rust
+//! use numpy_to_systolic::{Compiler, SystolicConfig};
+//!
+//! let mut compiler = Compiler::new(SystolicConfig::default_3x3());
+//! compiler.compile("A = [[1, 2], [3, 4]]").unwrap();
+//! let program = compiler.compile("C = A @ A").unwrap();
+//! assert_eq!(program.output_shape, (2, 2));
+//! ```
+
+use std::collections::HashMap;
+
+use crate::analyzer::Analyzer;
+use crate::ast::{Program, Shape, Statement};
+use crate::codegen::CodeGenerator;
+use crate::error::{CompileError, CompileResult};
+use crate::hardware::{HardwareProgram, SystolicConfig};
+use crate::optimizer;
+use crate::parser::Parser;
+use crate::tiling::TilingStrategy;
+
+/// Stateful compiler for line-by-line use
+pub struct Compiler {
+    config: SystolicConfig,
+    analyzer: Analyzer,
+    codegen: CodeGenerator,
+    /// Limit on the passes of a whole input, checked before code generation
+    max_passes: Option<usize>,
+}
+
+impl Compiler {
+    pub fn new(config: SystolicConfig) -> Self {
+        Self {
+            analyzer: Analyzer::new(),
+            codegen: CodeGenerator::new(config.clone()),
+            config,
+            max_passes: None,
+        }
+    }
+
+    /// Override the maximum number of elements allowed in any matrix
+    pub fn with_max_elements(mut self, max_elements: usize) -> Self {
+        self.analyzer = self.analyzer.with_max_elements(max_elements);
+        self
+    }
+
+    /// Reject shape redefinitions instead of warning
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.analyzer = self.analyzer.with_strict(strict);
+        self
+    }
+
+    /// Fail an input whose program would need more than `max_passes` passes in all
+    ///
+    /// The config's `max_passes` only holds each matmul to its limit.
+    pub fn with_max_passes(mut self, max_passes: usize) -> Self {
+        self.max_passes = Some(max_passes);
+        self
+    }
+
+    /// Warnings tolerated while compiling the last input
+    pub fn warnings(&self) -> &[String] {
+        self.analyzer.warnings()
+    }
+
+    /// Target configuration
+    pub fn config(&self) -> &SystolicConfig {
+        &self.config
+    }
+
+    /// Define a matrix with known shape
+    pub fn define_matrix(&mut self, name: &str, shape: (usize, usize)) {
+        self.analyzer.define_matrix(name, shape);
+    }
+
+    /// Define a matrix with known row-major contents, compiled in as if it were a literal
+    pub fn define_matrix_with_data(&mut self, name: &str, shape: (usize, usize), data: &[f64]) -> CompileResult<()> {
+        self.analyzer.define_matrix_with_data(name, shape, data)
+    }
+
+    /// Shapes of every variable defined so far
+    pub fn shapes(&self) -> &HashMap<String, Shape> {
+        self.analyzer.shapes()
+    }
+
+    /// Compile one input, keeping its definitions for later inputs
+    pub fn compile(&mut self, source: &str) -> CompileResult<HardwareProgram> {
+        let mut parser = Parser::new(source);
+        let program = parser.parse_program()?;
+        self.compile_program(program)
+    }
+
+    /// Compile each top-level statement of `source` as its own program
+    ///
+    /// Every statement starts from the definitions made so far, and none of them
+    /// are kept, so an assignment in one does not define a variable for the next.
+    pub fn compile_batch(&self, source: &str) -> CompileResult<Vec<HardwareProgram>> {
+        let mut parser = Parser::new(source);
+        let program = parser.parse_program()?;
+
+        // Function definitions are not programs of their own; every statement after one can call it
+        let mut defs = Vec::new();
+        let mut programs = Vec::new();
+        for statement in program.statements {
+            if let Statement::Def { .. } = statement {
+                defs.push(statement);
+                continue;
+            }
+            let mut compiler = Compiler {
+                config: self.config.clone(),
+                analyzer: self.analyzer.clone(),
+                codegen: CodeGenerator::new(self.config.clone()),
+                max_passes: self.max_passes,
+            };
+            let statements = defs.iter().cloned().chain(std::iter::once(statement)).collect();
+            programs.push(compiler.compile_program(Program { statements })?);
+        }
+        Ok(programs)
+    }
+
+    fn compile_program(&mut self, program: Program) -> CompileResult<HardwareProgram> {
+        let typed_program = optimizer::optimize(self.analyzer.analyze(program)?);
+
+        let tiler = TilingStrategy::new(self.config.clone());
+        let tiled_ops = tiler.tile_program(&typed_program)?;
+        if let Some(max_passes) = self.max_passes {
+            let estimate = tiled_ops.estimated_passes();
+            if estimate > max_passes {
+                return Err(CompileError::tiling(format!(
+                    "program needs {} passes, more than the limit of {}",
+                    estimate, max_passes
+                )));
+            }
+        }
+
+        self.codegen.generate(tiled_ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definitions_persist_across_inputs() {
+        let mut compiler = Compiler::new(SystolicConfig::new(3, 8, 32));
+        compiler.define_matrix("A", (2, 3));
+
+        compiler.compile("B = A.T").unwrap();
+        let program = compiler.compile("C = A @ B").unwrap();
+
+        assert_eq!(program.output_shape, (2, 2));
+        assert_eq!(program.passes.len(), 1);
+        assert_eq!(program.passes[0].id, 0);
+        assert_eq!(compiler.shapes()["C"], Shape::matrix(2, 2));
+    }
+
+    #[test]
+    fn test_batch_keeps_compiler_settings() {
+        let mut compiler = Compiler::new(SystolicConfig::new(3, 8, 32)).with_max_passes(100);
+        compiler.define_matrix("A", (12, 12));
+
+        // 64 passes each, under the limit on their own
+        let programs = compiler.compile_batch("C = A @ A; D = A.T @ A").unwrap();
+        assert_eq!(programs.len(), 2);
+        assert!(!compiler.shapes().contains_key("C"));
+
+        let message = compiler.compile_batch("C = A @ A; D = A @ A @ A").unwrap_err().to_string();
+        assert_eq!(message, "Tiling error: program needs 128 passes, more than the limit of 100");
+
+        let mut compiler = Compiler::new(SystolicConfig::new(3, 8, 32)).with_strict(true).with_max_elements(100);
+        compiler.define_matrix("A", (2, 2));
+        assert!(compiler.compile_batch("C = np.zeros((20, 20))").unwrap_err().to_string().contains("100"));
+        assert!(compiler.compile_batch("C = A; A = np.eye(3)").unwrap_err().to_string().contains("'A' redefined"));
+    }
+}
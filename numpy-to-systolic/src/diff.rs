@@ -0,0 +1,127 @@
+//! Comparison of two compilations of the same program
+//!
+//! Useful for seeing what a config change or an optimization did to the
+//! pass stream: how many passes and cycles it saved, and which passes changed.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hardware::{HardwareProgram, PassOperation};
+
+/// Differences between a `before` and an `after` program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramDiff {
+    /// `after` pass count minus `before` pass count
+    pub pass_delta: isize,
+    /// `after` total cycles minus `before` total cycles
+    pub cycle_delta: isize,
+    /// Passes whose operation differs, or that exist in only one program
+    pub pass_changes: Vec<PassChange>,
+}
+
+/// Operation of one pass position in each program; `None` where the pass is absent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassChange {
+    pub pass_id: usize,
+    pub before: Option<PassOperation>,
+    pub after: Option<PassOperation>,
+}
+
+impl ProgramDiff {
+    /// Whether the two programs have the same shape of pass stream
+    pub fn is_empty(&self) -> bool {
+        self.pass_delta == 0 && self.cycle_delta == 0 && self.pass_changes.is_empty()
+    }
+}
+
+impl HardwareProgram {
+    /// Compare this program (before) against `other` (after)
+    pub fn diff(&self, other: &HardwareProgram) -> ProgramDiff {
+        let num_passes = self.passes.len().max(other.passes.len());
+        let pass_changes = (0..num_passes)
+            .filter_map(|i| {
+                let before = self.passes.get(i).map(|p| p.operation.clone());
+                let after = other.passes.get(i).map(|p| p.operation.clone());
+                (before != after).then_some(PassChange { pass_id: i, before, after })
+            })
+            .collect();
+
+        ProgramDiff {
+            pass_delta: other.passes.len() as isize - self.passes.len() as isize,
+            cycle_delta: other.total_cycles as isize - self.total_cycles as isize,
+            pass_changes,
+        }
+    }
+}
+
+impl fmt::Display for ProgramDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        writeln!(f, "Passes: {:+}", self.pass_delta)?;
+        writeln!(f, "Cycles: {:+}", self.cycle_delta)?;
+        for change in &self.pass_changes {
+            let describe = |op: &Option<PassOperation>| match op {
+                Some(op) => format!("{:?}", op),
+                None => "(none)".to_string(),
+            };
+            writeln!(
+                f,
+                "  Pass {}: {} -> {}",
+                change.pass_id,
+                describe(&change.before),
+                describe(&change.after)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+    use crate::codegen::CodeGenerator;
+    use crate::hardware::SystolicConfig;
+    use crate::parser::Parser;
+    use crate::tiling::TilingStrategy;
+
+    #[test]
+    fn test_diff_optimized_against_unoptimized() {
+        let source = "C = A @ (2 * np.eye(4))";
+        let shapes = [("A", (4, 4))];
+        let config = SystolicConfig::new(3, 8, 32);
+
+        // The same pipeline without the optimizer
+        let program = Parser::new(source).parse_program().unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (4, 4));
+        let typed = analyzer.analyze(program).unwrap();
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        let unoptimized = CodeGenerator::new(config.clone()).generate(tiled).unwrap();
+
+        let optimized = crate::compile_with_shapes(source, &shapes, &config).unwrap();
+        let diff = unoptimized.diff(&optimized);
+
+        assert_eq!(diff.pass_delta, -8);
+        assert_eq!(diff.cycle_delta, -40);
+        assert_eq!(diff.pass_changes.len(), 8);
+        assert_eq!(diff.pass_changes[0].before, Some(PassOperation::Initialize));
+        assert_eq!(diff.pass_changes[0].after, None);
+
+        let summary = diff.to_string();
+        assert!(summary.starts_with("Passes: -8\nCycles: -40\n"), "{}", summary);
+        assert!(summary.contains("Pass 1: Final -> (none)"), "{}", summary);
+    }
+
+    #[test]
+    fn test_identical_programs() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).unwrap();
+        assert!(program.diff(&program).is_empty());
+        assert_eq!(program.diff(&program).to_string(), "No differences\n");
+    }
+}
@@ -1,5 +1,7 @@
 //! Error types for the NumPy to Systolic compiler
 
+use std::ops::Range;
+
 use thiserror::Error;
 
 /// Result type for compilation operations
@@ -11,8 +13,9 @@ pub enum CompileError {
     #[error("Lexer error at position {position}: {message}")]
     LexerError { position: usize, message: String },
 
+    /// `span` is the byte range of the offending token, once the parser attaches it
     #[error("Parser error: {message}")]
-    ParseError { message: String },
+    ParseError { message: String, span: Option<Range<usize>> },
 
     #[error("Type error: {message}")]
     TypeError { message: String },
@@ -31,11 +34,54 @@ pub enum CompileError {
 
     #[error("Code generation error: {message}")]
     CodeGenError { message: String },
+
+    #[error("Simulation error: {message}")]
+    SimulationError { message: String },
 }
 
 impl CompileError {
     pub fn parse_error(msg: impl Into<String>) -> Self {
-        CompileError::ParseError { message: msg.into() }
+        CompileError::ParseError { message: msg.into(), span: None }
+    }
+    
+    /// Point a parse error at the source bytes in `range`; other errors are unchanged
+    pub fn with_span(mut self, range: Range<usize>) -> Self {
+        if let CompileError::ParseError { span, .. } = &mut self {
+            *span = Some(range);
+        }
+        self
+    }
+    
+    /// Byte range of the source text the error points at, if known
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            CompileError::ParseError { span, .. } => span.clone(),
+            _ => None,
+        }
+    }
+    
+    /// The message followed by the line of `source` the error points at, with a caret under the span
+    ///
+    /// Errors without a span render as their message alone.
+    pub fn render(&self, source: &str) -> String {
+        let Some(span) = self.span() else {
+            return self.to_string();
+        };
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let line = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let width = source[start..span.end.clamp(start, line_end)].chars().count().max(1);
+        let gutter = " ".repeat(line.to_string().len());
+        format!(
+            "{}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}{}",
+            self,
+            gutter, line, column,
+            gutter,
+            line, &source[line_start..line_end],
+            gutter, " ".repeat(column - 1), "^".repeat(width),
+        )
     }
 
     pub fn type_error(msg: impl Into<String>) -> Self {
@@ -57,4 +103,8 @@ impl CompileError {
     pub fn codegen(msg: impl Into<String>) -> Self {
         CompileError::CodeGenError { message: msg.into() }
     }
+
+    pub fn simulation(msg: impl Into<String>) -> Self {
+        CompileError::SimulationError { message: msg.into() }
+    }
 }
@@ -0,0 +1,286 @@
+//! Step-by-step narration of compilation decisions
+//!
+//! An `Explanation` is handed each phase's output in turn and records what the
+//! compiler decided there: the statements it parsed, the shapes it inferred,
+//! how each matmul was tiled and padded, and why every pass does what it does.
+//! Meant for teaching; `--verbose` only adds a header to the normal output.
+
+use std::fmt;
+
+use crate::analyzer::Analyzer;
+use crate::ast::{Expr, Program, Statement, TypedProgram};
+use crate::codegen::CodeGenerator;
+use crate::error::CompileResult;
+use crate::hardware::{HardwareProgram, PassOperation, SystolicConfig};
+use crate::optimizer;
+use crate::parser::Parser;
+use crate::tiling::{EpilogueStage, TiledOperation, TiledProgram, TilingStrategy};
+
+/// Decisions recorded while compiling, grouped by phase
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Explanation {
+    phases: Vec<(String, Vec<String>)>,
+}
+
+impl Explanation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recorded lines of the phase titled `title`, if it was recorded
+    pub fn phase(&self, title: &str) -> Option<&[String]> {
+        self.phases.iter().find(|(t, _)| t == title).map(|(_, lines)| lines.as_slice())
+    }
+
+    fn record(&mut self, title: &str, lines: Vec<String>) {
+        self.phases.push((title.to_string(), lines));
+    }
+
+    /// Summarize the parsed statements
+    pub fn record_parse(&mut self, program: &Program) {
+        let mut lines = vec![format!("{} statement(s)", program.statements.len())];
+        for (i, stmt) in program.statements.iter().enumerate() {
+            lines.push(match stmt {
+                Statement::Assignment { target, value } => format!("{}: {} = {}", i + 1, target, describe(value)),
+                Statement::Expression(expr) => format!("{}: {}", i + 1, describe(expr)),
+                Statement::Def { name, params, body } => {
+                    format!("{}: def {}({}) = {}", i + 1, name, params.join(", "), describe(body))
+                }
+            });
+        }
+        self.record("Parsing", lines);
+    }
+
+    /// Record the inferred shape of each statement and the overall workload
+    pub fn record_analysis(&mut self, program: &TypedProgram) {
+        let mut lines: Vec<String> = program.statements.iter()
+            .map(|stmt| format!("{} has shape {}", stmt.target, stmt.value.shape))
+            .collect();
+        lines.push(format!("Workload: {:?}", program.classify()));
+        self.record("Shape analysis", lines);
+    }
+
+    /// Record how every matmul was split into tiles, and what runs on the host
+    pub fn record_tiling(&mut self, program: &TiledProgram, config: &SystolicConfig) {
+        let mut lines = Vec::new();
+        for op in &program.operations {
+            match op {
+                TiledOperation::TiledMatMul { target, left_shape, output_shape, tile_size, accumulate, epilogue, .. } => {
+                    let (m, k) = *left_shape;
+                    let n = output_shape.1;
+                    let (m_tiles, n_tiles, k_tiles) = (m.div_ceil(*tile_size), n.div_ceil(*tile_size), k.div_ceil(*tile_size));
+                    lines.push(format!(
+                        "{}: ({}, {}) @ ({}, {}) on a {}x{} array is {}x{} output tiles with {} K step(s) each, {} pass(es)",
+                        target, m, k, k, n, tile_size, tile_size, m_tiles, n_tiles, k_tiles, m_tiles * n_tiles * k_tiles
+                    ));
+                    let ragged: Vec<String> = [("M", m), ("N", n), ("K", k)].iter()
+                        .filter(|(_, len)| len % tile_size != 0)
+                        .map(|(dim, len)| format!("{} = {} leaves {} of {}", dim, len, len % tile_size, tile_size))
+                        .collect();
+                    if !ragged.is_empty() && config.zero_point == 0 {
+                        lines.push(format!("  Edge tiles are zero-padded: {}", ragged.join(", ")));
+                    } else if !ragged.is_empty() {
+                        lines.push(format!(
+                            "  Edge tiles are padded (M and N with zero-point {}, K with 0): {}",
+                            config.zero_point,
+                            ragged.join(", ")
+                        ));
+                    }
+                    if let Some(residual) = accumulate {
+                        lines.push(format!("  Accumulators start from {} instead of zero", residual));
+                    }
+                    if !epilogue.is_empty() {
+                        let stages: Vec<String> = epilogue.iter().map(describe_stage).collect();
+                        lines.push(format!("  Final passes apply a fused epilogue: {}", stages.join(", ")));
+                    }
+                }
+                TiledOperation::LoadMatrix { .. } | TiledOperation::LoadLiteral { .. } => {}
+                other => lines.push(format!("{}: {} on the host", other.target(), host_op_name(other))),
+            }
+        }
+        lines.push(format!("Tiles are visited in {:?} order", config.tile_order));
+        self.record("Tiling", lines);
+    }
+
+    /// Record the rationale of every pass
+    pub fn record_codegen(&mut self, program: &HardwareProgram) {
+        let mut lines = Vec::new();
+        let mut accumulating = false;
+        for pass in &program.passes {
+            let why = match pass.operation {
+                PassOperation::Initialize => "first K step, accumulators start fresh",
+                PassOperation::Accumulate => "middle K step, adds to the partial sums",
+                PassOperation::Final if accumulating => "last K step, the output tile is complete",
+                PassOperation::Final => "the whole K range fits in one step, the output tile is complete",
+            };
+            accumulating = pass.operation != PassOperation::Final;
+            let dram = match (pass.reload, pass.spill) {
+                (true, true) => "; partial sums reloaded from and spilled to DRAM",
+                (true, false) => "; partial sums reloaded from DRAM",
+                (false, true) => "; partial sums spilled to DRAM",
+                (false, false) => "",
+            };
+            lines.push(format!("Pass {}: {} ({}{})", pass.id, pass.description, why, dram));
+        }
+        lines.push(format!(
+            "{} pass(es) at up to {} cycles each: {} cycles",
+            program.passes.len(),
+            program.config.cycles_for_matmul(),
+            program.total_cycles
+        ));
+        if program.spill_traffic_bytes() > 0 {
+            lines.push(format!(
+                "Accumulators hold {} K step(s), so partial sums cost {} bytes of DRAM traffic",
+                program.config.accumulator_k_tiles,
+                program.spill_traffic_bytes()
+            ));
+        }
+        self.record("Code generation", lines);
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (title, lines)) in self.phases.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, title)?;
+            for line in lines {
+                writeln!(f, "   {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compile `source` and explain every phase
+pub fn explain(
+    source: &str,
+    shapes: &[(&str, (usize, usize))],
+    config: &SystolicConfig,
+) -> CompileResult<Explanation> {
+    let mut explanation = Explanation::new();
+
+    let program = Parser::new(source).parse_program()?;
+    explanation.record_parse(&program);
+
+    let mut analyzer = Analyzer::new();
+    for (name, shape) in shapes {
+        analyzer.define_matrix(name, *shape);
+    }
+    let typed = optimizer::optimize(analyzer.analyze(program)?);
+    explanation.record_analysis(&typed);
+
+    let tiled = TilingStrategy::new(config.clone()).tile_program(&typed)?;
+    explanation.record_tiling(&tiled, config);
+
+    let hardware = CodeGenerator::new(config.clone()).generate(tiled)?;
+    explanation.record_codegen(&hardware);
+
+    Ok(explanation)
+}
+
+/// Compact NumPy-style rendering of an expression
+fn describe(expr: &Expr) -> String {
+    let operand = |e: &Expr| match e {
+        Expr::MatMul(..) | Expr::Add(..) | Expr::Sub(..) | Expr::Mul(..) | Expr::Div(..) | Expr::ScalarMul(..) | Expr::Let { .. } => {
+            format!("({})", describe(e))
+        }
+        _ => describe(e),
+    };
+    match expr {
+        Expr::Variable(name) => name.clone(),
+        Expr::Scalar(n) => n.to_string(),
+        Expr::Matrix(literal) => {
+            let (rows, cols) = literal.shape();
+            format!("<{}x{} literal>", rows, cols)
+        }
+        Expr::Vector(values) => format!("<length-{} literal>", values.len()),
+        Expr::MatMul(l, r) => format!("{} @ {}", operand(l), operand(r)),
+        Expr::Paren(inner) => format!("({})", describe(inner)),
+        Expr::Add(l, r) => format!("{} + {}", operand(l), operand(r)),
+        Expr::Sub(l, r) => format!("{} - {}", operand(l), operand(r)),
+        Expr::Mul(l, r) | Expr::ScalarMul(l, r) => format!("{} * {}", operand(l), operand(r)),
+        Expr::Div(l, r) => format!("{} / {}", operand(l), operand(r)),
+        Expr::Transpose(inner) => format!("{}.T", operand(inner)),
+        Expr::MatPow(base, exponent) => format!("{} ** {}", operand(base), exponent),
+        Expr::FunctionCall { name, args, kwargs } => {
+            let args: Vec<String> = args.iter()
+                .map(describe)
+                .chain(kwargs.iter().map(|(key, value)| format!("{}={}", key, describe(value))))
+                .collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        Expr::Tuple(elements) => format!("({})", elements.iter().map(describe).collect::<Vec<_>>().join(", ")),
+        Expr::Index(inner, subscripts) => format!("{}[<{} subscript(s)>]", operand(inner), subscripts.len()),
+        Expr::Let { name, value, body } => format!("let {} = {} in {}", name, describe(value), describe(body)),
+    }
+}
+
+/// One fused epilogue step, as written in the source
+fn describe_stage(stage: &EpilogueStage) -> String {
+    match stage {
+        EpilogueStage::Bias(name) => format!("+ {}", name),
+        EpilogueStage::Scale(factor) => format!("* {}", factor),
+        EpilogueStage::Activation(kind) => kind.to_string(),
+        EpilogueStage::Clip { min, max } => format!("clip({}, {})", min, max),
+    }
+}
+
+/// Short name of a host-side operation
+fn host_op_name(op: &TiledOperation) -> &'static str {
+    match op {
+        TiledOperation::Add { .. } => "element-wise add",
+        TiledOperation::Sub { .. } => "element-wise subtract",
+        TiledOperation::ElementMul { .. } => "element-wise multiply",
+        TiledOperation::ElementDiv { .. } => "element-wise divide",
+        TiledOperation::ScalarMul { .. } => "scalar multiply",
+        TiledOperation::Transpose { .. } => "transpose",
+        TiledOperation::Reshape { .. } => "reshape",
+        TiledOperation::Activation { .. } => "activation",
+        TiledOperation::Split { .. } => "split",
+        TiledOperation::Concat { .. } => "concatenate",
+        TiledOperation::Roll { .. } => "roll",
+        TiledOperation::Norm { .. } => "norm",
+        TiledOperation::Diag { .. } => "diagonal",
+        TiledOperation::Clip { .. } => "clip",
+        TiledOperation::Index { .. } => "index",
+        TiledOperation::LoadMatrix { .. } | TiledOperation::LoadLiteral { .. } | TiledOperation::TiledMatMul { .. } => "load",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_tiled_matmul() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let explanation = explain("C = A @ B + A @ B", &[("A", (7, 5)), ("B", (5, 4))], &config).unwrap();
+
+        assert_eq!(explanation.phase("Parsing").unwrap()[1], "1: C = (A @ B) + (A @ B)");
+        assert_eq!(explanation.phase("Shape analysis").unwrap()[0], "C has shape (7, 4)");
+        let tiling = explanation.phase("Tiling").unwrap();
+        assert_eq!(
+            tiling[0],
+            "C_add_left: (7, 5) @ (5, 4) on a 3x3 array is 3x2 output tiles with 2 K step(s) each, 12 pass(es)"
+        );
+        assert_eq!(tiling[1], "  Edge tiles are zero-padded: M = 7 leaves 1 of 3, N = 4 leaves 1 of 3, K = 5 leaves 2 of 3");
+        
+        let with_zero_point = explain("C = A @ B", &[("A", (7, 5)), ("B", (5, 4))], &config.clone().with_zero_point(-3)).unwrap();
+        assert!(with_zero_point.phase("Tiling").unwrap()[1].starts_with("  Edge tiles are padded (M and N with zero-point -3, K with 0): M = 7"));
+        assert!(tiling.contains(&"C: element-wise add on the host".to_string()), "{:?}", tiling);
+
+        let codegen = explanation.phase("Code generation").unwrap();
+        assert!(codegen[0].ends_with("(first K step, accumulators start fresh)"), "{}", codegen[0]);
+        assert!(codegen[1].ends_with("(last K step, the output tile is complete)"), "{}", codegen[1]);
+        assert_eq!(codegen.last().unwrap(), "24 pass(es) at up to 8 cycles each: 140 cycles");
+    }
+
+    #[test]
+    fn test_explanation_display_numbers_phases() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let text = explain("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).unwrap().to_string();
+
+        assert!(text.starts_with("1. Parsing\n   1 statement(s)\n   1: C = A @ B\n2. Shape analysis\n"), "{}", text);
+        assert!(text.contains("the whole K range fits in one step"), "{}", text);
+    }
+}
@@ -3,8 +3,20 @@
 //! These types correspond to the Chisel hardware implementation in
 //! hardware_examples/src/main/scala/systolic/
 
+use std::collections::HashMap;
+use std::ops::Range;
+
 use serde::{Deserialize, Serialize};
 
+use crate::ast::Activation;
+use crate::error::{CompileError, CompileResult};
+
+/// Widest data element `max_value`/`min_value` can represent
+pub const MAX_DATA_WIDTH: usize = 128;
+
+/// Passes a single matmul may tile into before compilation aborts
+pub const DEFAULT_MAX_PASSES: usize = 1_000_000;
+
 /// Configuration for the systolic array hardware
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystolicConfig {
@@ -14,6 +26,53 @@ pub struct SystolicConfig {
     pub data_width: usize,
     /// Bit width of accumulator/result
     pub acc_width: usize,
+    /// Order in which output tiles are visited
+    #[serde(default)]
+    pub tile_order: TileOrder,
+    /// How operands are laid out in the flat operand memory
+    #[serde(default)]
+    pub memory_layout: MemoryLayout,
+    /// Reject non-integer or out-of-range data instead of scaling and rounding it
+    #[serde(default)]
+    pub integer_only: bool,
+    /// Compute `A @ B + X` by starting the accumulators from `X`
+    #[serde(default)]
+    pub fuse_residual: bool,
+    /// Quantized value of real zero under affine quantization; the rows and columns
+    /// padding edge tiles out to the array take it (padded K entries are always 0)
+    #[serde(default)]
+    pub zero_point: i64,
+    /// How `.mem` and C-header exports group elements into memory words
+    #[serde(default)]
+    pub packing: WordPacking,
+    /// K tiles the accumulators can sum before partial sums spill to DRAM; 0 means no limit
+    #[serde(default)]
+    pub accumulator_k_tiles: usize,
+    /// Apply element-wise ops that follow a matmul (bias, scale, activation, clip) on its final passes;
+    /// an activation applied directly to a matmul is fused either way
+    #[serde(default)]
+    pub fuse_epilogue: bool,
+    /// Which operand stays in the PEs during a pass
+    #[serde(default)]
+    pub dataflow: Dataflow,
+    /// Allow a K deep enough to overflow the accumulators; sums saturate instead of failing to compile
+    #[serde(default)]
+    pub saturate_accumulators: bool,
+    /// Skip statements whose results are never read and that don't assign the final result
+    #[serde(default)]
+    pub eliminate_dead_statements: bool,
+    /// Count every pass as a full `array_size` tile, ignoring how much of it is loaded
+    #[serde(default)]
+    pub full_tile_cycles: bool,
+    /// Regroup `A @ B @ C` chains into the order needing the fewest tiles; parenthesized groups stay fixed
+    #[serde(default)]
+    pub reorder_matmul_chains: bool,
+    /// How quantization rounds values that fall between two integers
+    #[serde(default)]
+    pub rounding: RoundingMode,
+    /// Most passes one matmul may tile into before compilation fails; 0 means no limit
+    #[serde(default = "default_max_passes")]
+    pub max_passes: usize,
 }
 
 impl SystolicConfig {
@@ -22,27 +81,261 @@ impl SystolicConfig {
             array_size,
             data_width,
             acc_width,
+            tile_order: TileOrder::default(),
+            memory_layout: MemoryLayout::default(),
+            integer_only: false,
+            fuse_residual: false,
+            zero_point: 0,
+            packing: WordPacking::default(),
+            accumulator_k_tiles: 0,
+            fuse_epilogue: false,
+            dataflow: Dataflow::default(),
+            saturate_accumulators: false,
+            eliminate_dead_statements: false,
+            full_tile_cycles: false,
+            reorder_matmul_chains: false,
+            rounding: RoundingMode::default(),
+            max_passes: DEFAULT_MAX_PASSES,
         }
     }
     
+    /// Like `new`, but rejects sizes `validate` would (a zero-size array, a 1-bit element,
+    /// an accumulator narrower than the data)
+    pub fn try_new(array_size: usize, data_width: usize, acc_width: usize) -> CompileResult<Self> {
+        let config = Self::new(array_size, data_width, acc_width);
+        config.validate()?;
+        Ok(config)
+    }
+    
+    /// Configuration for an array running the given dataflow
+    pub fn new_with_dataflow(array_size: usize, data_width: usize, acc_width: usize, dataflow: Dataflow) -> Self {
+        Self::new(array_size, data_width, acc_width).with_dataflow(dataflow)
+    }
+    
+    /// Fail a matmul that would tile into more than `max_passes` passes (0 allows any number)
+    pub fn with_max_passes(mut self, max_passes: usize) -> Self {
+        self.max_passes = max_passes;
+        self
+    }
+    
+    /// Round quantized values with `rounding` (see `RoundingMode`)
+    pub fn with_rounding(mut self, rounding: RoundingMode) -> Self {
+        self.rounding = rounding;
+        self
+    }
+    
+    /// Use a different dataflow (see `Dataflow`)
+    pub fn with_dataflow(mut self, dataflow: Dataflow) -> Self {
+        self.dataflow = dataflow;
+        self
+    }
+    
+    /// Compile for a 1x1 array, so every output element gets its own passes
+    ///
+    /// Meant for debugging: each pass is a single multiply-accumulate, which makes
+    /// the dataflow easy to follow by hand.
+    pub fn with_scalar_mode(mut self, scalar_mode: bool) -> Self {
+        if scalar_mode {
+            self.array_size = 1;
+        }
+        self
+    }
+    
+    /// Spill partial sums to DRAM after every `k_tiles` K steps (0 keeps them on chip)
+    ///
+    /// Models targets whose accumulator banks cannot hold the sum over a very large
+    /// K: the partial sums are written out and reloaded before the next K group.
+    pub fn with_accumulator_k_tiles(mut self, k_tiles: usize) -> Self {
+        self.accumulator_k_tiles = k_tiles;
+        self
+    }
+    
+    /// Pack several elements into each exported memory word
+    pub fn with_packing(mut self, packing: WordPacking) -> Self {
+        self.packing = packing;
+        self
+    }
+    
+    /// Pad edge tiles' rows and columns with an affine zero-point instead of 0
+    pub fn with_zero_point(mut self, zero_point: i64) -> Self {
+        self.zero_point = zero_point;
+        self
+    }
+    
+    /// Fold a residual add into the matmul it follows (see `fuse_residual`)
+    pub fn with_fuse_residual(mut self, fuse_residual: bool) -> Self {
+        self.fuse_residual = fuse_residual;
+        self
+    }
+    
+    /// Fold element-wise ops after a matmul into its final passes (see `fuse_epilogue`)
+    pub fn with_fuse_epilogue(mut self, fuse_epilogue: bool) -> Self {
+        self.fuse_epilogue = fuse_epilogue;
+        self
+    }
+    
+    /// Let accumulators saturate rather than rejecting a K that could overflow them
+    pub fn with_saturate_accumulators(mut self, saturate_accumulators: bool) -> Self {
+        self.saturate_accumulators = saturate_accumulators;
+        self
+    }
+    
+    /// Drop unused assignments when tiling (see `eliminate_dead_statements`)
+    pub fn with_eliminate_dead_statements(mut self, eliminate_dead_statements: bool) -> Self {
+        self.eliminate_dead_statements = eliminate_dead_statements;
+        self
+    }
+    
+    /// Charge every pass the cycles of a full tile, as before edge tiles were counted by size
+    pub fn with_full_tile_cycles(mut self, full_tile_cycles: bool) -> Self {
+        self.full_tile_cycles = full_tile_cycles;
+        self
+    }
+    
+    /// Tile matmul chains in their cheapest association order (see `reorder_matmul_chains`)
+    pub fn with_reorder_matmul_chains(mut self, reorder_matmul_chains: bool) -> Self {
+        self.reorder_matmul_chains = reorder_matmul_chains;
+        self
+    }
+    
+    /// Require bit-exact integer data (see `check_integer_data`)
+    pub fn with_integer_only(mut self, integer_only: bool) -> Self {
+        self.integer_only = integer_only;
+        self
+    }
+    
+    /// Use a different operand memory layout
+    pub fn with_memory_layout(mut self, memory_layout: MemoryLayout) -> Self {
+        self.memory_layout = memory_layout;
+        self
+    }
+    
+    /// Use a different output tile traversal order
+    pub fn with_tile_order(mut self, tile_order: TileOrder) -> Self {
+        self.tile_order = tile_order;
+        self
+    }
+    
     /// Default configuration matching the Chisel toy example
     pub fn default_3x3() -> Self {
         Self::new(3, 8, 32)
     }
     
+    /// Check that the configuration describes a buildable array
+    pub fn validate(&self) -> CompileResult<()> {
+        if self.array_size == 0 {
+            return Err(CompileError::invalid_op("Array size must be at least 1"));
+        }
+        // One bit would leave a signed element nothing but its sign
+        if self.data_width < 2 || self.data_width > MAX_DATA_WIDTH {
+            return Err(CompileError::invalid_op(format!(
+                "Data width must be between 2 and {} bits, got {}",
+                MAX_DATA_WIDTH, self.data_width
+            )));
+        }
+        if self.acc_width < self.data_width {
+            return Err(CompileError::invalid_op(format!(
+                "Accumulator width ({} bits) must be at least the data width ({} bits)",
+                self.acc_width, self.data_width
+            )));
+        }
+        let zero_point = self.zero_point as i128;
+        if zero_point < self.min_value() || zero_point > self.max_value() {
+            return Err(CompileError::invalid_op(format!(
+                "Zero point {} does not fit in {}-bit data ({} to {})",
+                self.zero_point,
+                self.data_width,
+                self.min_value(),
+                self.max_value()
+            )));
+        }
+        let per_word = self.packing.elements_per_word;
+        if per_word > 1 && per_word * self.data_width > 64 {
+            return Err(CompileError::invalid_op(format!(
+                "Cannot pack {} {}-bit elements into a word of at most 64 bits",
+                per_word, self.data_width
+            )));
+        }
+        Ok(())
+    }
+    
     /// Get the maximum value that can be represented
-    pub fn max_value(&self) -> i64 {
-        (1i64 << (self.data_width - 1)) - 1
+    ///
+    /// Computed in `i128` by shifting down from `i128::MAX`, so widths up to
+    /// `MAX_DATA_WIDTH` never overflow.
+    pub fn max_value(&self) -> i128 {
+        i128::MAX >> (MAX_DATA_WIDTH - self.data_width.clamp(1, MAX_DATA_WIDTH))
     }
     
     /// Get the minimum value that can be represented
-    pub fn min_value(&self) -> i64 {
-        -(1i64 << (self.data_width - 1))
+    pub fn min_value(&self) -> i128 {
+        i128::MIN >> (MAX_DATA_WIDTH - self.data_width.clamp(1, MAX_DATA_WIDTH))
+    }
+    
+    /// Largest value the signed `acc_width`-bit accumulator holds (capped at `i64`)
+    pub fn acc_max(&self) -> i64 {
+        i64::MAX >> (64 - self.acc_width.clamp(1, 64))
+    }
+    
+    /// Smallest value the signed `acc_width`-bit accumulator holds (capped at `i64`)
+    pub fn acc_min(&self) -> i64 {
+        i64::MIN >> (64 - self.acc_width.clamp(1, 64))
     }
     
-    /// Number of cycles needed for one matrix multiplication
+    /// Largest magnitude a `data_width`-bit value can have: that of `min_value`
+    pub fn max_magnitude(&self) -> i128 {
+        self.min_value().saturating_abs().max(self.max_value())
+    }
+    
+    /// Number of cycles needed for one matrix multiplication on a full tile
     pub fn cycles_for_matmul(&self) -> usize {
-        3 * self.array_size - 1
+        self.phase_cycles().iter().map(|(_, cycles)| cycles).sum()
+    }
+    
+    /// Per-phase cycle counts of a full-tile pass, summing to `cycles_for_matmul`
+    ///
+    /// Output-stationary (3N - 1 in total): Load takes N cycles to feed the skewed
+    /// operands, Compute N - 1 more for the wavefront to reach the last PE, and
+    /// Drain N to shift results out.
+    ///
+    /// Weight-stationary (4N - 2): Load takes N cycles to shift the B tile into
+    /// the PEs, Compute 2N - 1 to stream the skewed rows of A through, and Drain
+    /// N - 1 for the last partial sums to leave the bottom edge.
+    pub fn phase_cycles(&self) -> [(TracePhase, usize); 3] {
+        let n = self.array_size;
+        self.phase_cycles_for(n, n, n)
+    }
+    
+    /// Per-phase cycle counts of a pass multiplying a `rows x depth` tile by a `depth x cols` one
+    ///
+    /// Each phase scales with the loaded dimension it walks instead of N, so an
+    /// output-stationary edge tile takes `depth + rows + cols - 1` cycles.
+    pub fn phase_cycles_for(&self, rows: usize, depth: usize, cols: usize) -> [(TracePhase, usize); 3] {
+        match self.dataflow {
+            Dataflow::OutputStationary => [
+                (TracePhase::Load, depth),
+                (TracePhase::Compute, cols.saturating_sub(1)),
+                (TracePhase::Drain, rows),
+            ],
+            Dataflow::WeightStationary => [
+                (TracePhase::Load, depth),
+                (TracePhase::Compute, (rows + depth).saturating_sub(1)),
+                (TracePhase::Drain, cols.saturating_sub(1)),
+            ],
+        }
+    }
+    
+    /// Per-phase cycle counts of `pass`, or of a full tile under `full_tile_cycles`
+    pub fn pass_phase_cycles(&self, pass: &SystolicPass) -> [(TracePhase, usize); 3] {
+        if self.full_tile_cycles {
+            return self.phase_cycles();
+        }
+        self.phase_cycles_for(pass.a_shape.0, pass.a_shape.1, pass.b_shape.1)
+    }
+    
+    /// Cycles `pass` takes, summing `pass_phase_cycles`
+    pub fn pass_cycles(&self, pass: &SystolicPass) -> usize {
+        self.pass_phase_cycles(pass).iter().map(|(_, cycles)| cycles).sum()
     }
 }
 
@@ -52,6 +345,181 @@ impl Default for SystolicConfig {
     }
 }
 
+/// How quantization maps a scaled value onto the integer grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RoundingMode {
+    /// Nearest integer, ties away from zero (`2.5 -> 3`, `-2.5 -> -3`), as `f64::round`
+    #[default]
+    HalfAwayFromZero,
+    /// Nearest integer, ties to the even neighbour (`2.5 -> 2`, `3.5 -> 4`); unbiased over many ties
+    HalfEven,
+    /// Drop the fraction, rounding toward zero (`2.5 -> 2`, `-2.5 -> -2`)
+    Truncate,
+}
+
+impl RoundingMode {
+    /// Round `value` to an integer-valued float
+    pub fn round(&self, value: f64) -> f64 {
+        match self {
+            RoundingMode::HalfAwayFromZero => value.round(),
+            RoundingMode::HalfEven => value.round_ties_even(),
+            RoundingMode::Truncate => value.trunc(),
+        }
+    }
+}
+
+/// Which operand stays resident in the PEs while the other streams through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Dataflow {
+    /// Each PE accumulates one output element; A enters from the left, B
+    /// (column-major) from the top
+    #[default]
+    OutputStationary,
+    /// The B tile is preloaded into the PEs row by row (row-major); A streams
+    /// through and partial sums flow down the columns
+    WeightStationary,
+}
+
+impl Dataflow {
+    /// Whether `SystolicPass::matrix_b` is stored column-major under this dataflow
+    pub fn b_column_major(&self) -> bool {
+        *self == Dataflow::OutputStationary
+    }
+    
+    /// Name of the `matrix_b` layout, for listings
+    pub fn b_layout(&self) -> &'static str {
+        if self.b_column_major() { "col-major" } else { "row-major" }
+    }
+}
+
+/// Traversal order of output tiles; K always stays innermost so partial sums accumulate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TileOrder {
+    /// Finish each row of output tiles before the next
+    #[default]
+    RowMajor,
+    /// Finish each column of output tiles before the next
+    ColMajor,
+    /// Morton (Z-curve) order, keeping nearby tiles close in time for operand reuse
+    ZOrder,
+}
+
+impl TileOrder {
+    /// Output tile coordinates `(row, col)` of an `m_tiles` x `n_tiles` grid, in visit order
+    pub fn output_tiles(&self, m_tiles: usize, n_tiles: usize) -> Vec<(usize, usize)> {
+        let mut tiles: Vec<(usize, usize)> = (0..m_tiles)
+            .flat_map(|i| (0..n_tiles).map(move |j| (i, j)))
+            .collect();
+        match self {
+            TileOrder::RowMajor => {}
+            TileOrder::ColMajor => tiles.sort_by_key(|&(i, j)| (j, i)),
+            TileOrder::ZOrder => tiles.sort_by_key(|&(i, j)| morton_key(i, j)),
+        }
+        tiles
+    }
+}
+
+/// Interleave the bits of `row` (odd positions) and `col` (even positions)
+fn morton_key(row: usize, col: usize) -> u128 {
+    (0..usize::BITS).fold(0u128, |key, bit| {
+        let r = ((row >> bit) & 1) as u128;
+        let c = ((col >> bit) & 1) as u128;
+        key | (c << (2 * bit)) | (r << (2 * bit + 1))
+    })
+}
+
+/// Order of packed elements within a word
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Endianness {
+    /// First element in the least significant bits
+    #[default]
+    Little,
+    /// First element in the most significant bits
+    Big,
+}
+
+/// Grouping of `data_width`-bit elements into memory words for exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordPacking {
+    /// Elements per word; 1 writes each element on its own
+    pub elements_per_word: usize,
+    pub endianness: Endianness,
+}
+
+impl Default for WordPacking {
+    fn default() -> Self {
+        Self { elements_per_word: 1, endianness: Endianness::Little }
+    }
+}
+
+impl WordPacking {
+    pub fn new(elements_per_word: usize, endianness: Endianness) -> Self {
+        Self { elements_per_word, endianness }
+    }
+    
+    /// Whether exports group more than one element per word
+    pub fn is_packed(&self) -> bool {
+        self.elements_per_word > 1
+    }
+    
+    /// Bits in one word of `data_width`-bit elements
+    pub fn word_bits(&self, data_width: usize) -> usize {
+        (self.elements_per_word.max(1) * data_width).min(64)
+    }
+    
+    /// Two's-complement elements packed into words; a short last word is zero-filled
+    pub fn pack(&self, values: &[i64], data_width: usize) -> Vec<u64> {
+        let per_word = self.elements_per_word.max(1);
+        let width = data_width.min(64);
+        let mask = if width == 64 { u64::MAX } else { (1u64 << width) - 1 };
+        values
+            .chunks(per_word)
+            .map(|chunk| {
+                chunk.iter().enumerate().fold(0u64, |word, (i, &v)| {
+                    let slot = match self.endianness {
+                        Endianness::Little => i,
+                        Endianness::Big => per_word - 1 - i,
+                    };
+                    word | (((v as u64) & mask) << (slot * width))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Layout of operand matrices in flat memory; addresses are element offsets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MemoryLayout {
+    /// Each matrix row-major; a tile starts at its first element, rows `cols` apart
+    #[default]
+    Contiguous,
+    /// Each matrix as consecutive zero-padded NxN tiles, so every tile is one burst
+    Tiled,
+}
+
+impl MemoryLayout {
+    /// Elements a `shape` matrix occupies
+    pub fn buffer_size(&self, shape: (usize, usize), tile_size: usize) -> usize {
+        match self {
+            MemoryLayout::Contiguous => shape.0 * shape.1,
+            MemoryLayout::Tiled => {
+                shape.0.div_ceil(tile_size) * shape.1.div_ceil(tile_size) * tile_size * tile_size
+            }
+        }
+    }
+    
+    /// Offset of the tile starting at `(row, col)` from the start of its matrix
+    pub fn tile_offset(&self, shape: (usize, usize), tile_size: usize, row: usize, col: usize) -> usize {
+        match self {
+            MemoryLayout::Contiguous => row * shape.1 + col,
+            MemoryLayout::Tiled => {
+                let tiles_per_row = shape.1.div_ceil(tile_size);
+                ((row / tile_size) * tiles_per_row + col / tile_size) * tile_size * tile_size
+            }
+        }
+    }
+}
+
 /// A single pass through the systolic array
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystolicPass {
@@ -63,7 +531,7 @@ pub struct SystolicPass {
     pub matrix_a: Vec<i64>,
     /// Matrix A dimensions (rows, cols)
     pub a_shape: (usize, usize),
-    /// Matrix B data (column-major, flattened)
+    /// Matrix B data (flattened; column-major, or row-major under `Dataflow::WeightStationary`)
     pub matrix_b: Vec<i64>,
     /// Matrix B dimensions (rows, cols)
     pub b_shape: (usize, usize),
@@ -73,6 +541,241 @@ pub struct SystolicPass {
     pub output_tile: TileCoord,
     /// Operation type
     pub operation: PassOperation,
+    /// Memory address (element offset) of this pass's A tile
+    #[serde(default)]
+    pub a_addr: usize,
+    /// Memory address (element offset) of this pass's B tile
+    #[serde(default)]
+    pub b_addr: usize,
+    /// Factor A was multiplied by before quantization
+    #[serde(default = "unit_scale")]
+    pub a_scale: f64,
+    /// Factor B was multiplied by before quantization
+    #[serde(default = "unit_scale")]
+    pub b_scale: f64,
+    /// Accumulator contents (row-major, padded) once a `Final` pass completes, saturated to
+    /// the accumulator range; empty otherwise
+    #[serde(default)]
+    pub expected_output: Vec<i64>,
+    /// Accumulator contents (row-major, padded) loaded before the first K pass of
+    /// an output tile, or the partial sums a `reload` pass reads back; empty means
+    /// the accumulators start at zero
+    #[serde(default)]
+    pub acc_init: Vec<i64>,
+    /// Partial sums are written to DRAM after this pass
+    #[serde(default)]
+    pub spill: bool,
+    /// Partial sums spilled by an earlier pass are read back into the accumulators first
+    #[serde(default)]
+    pub reload: bool,
+    /// Element-wise ops applied to the accumulators as they drain, in order; `Final` passes only
+    #[serde(default)]
+    pub epilogue: Vec<EpilogueOp>,
+}
+
+/// One element-wise step of a fused epilogue
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum EpilogueOp {
+    /// Divide by the accumulator scale, giving real units
+    Dequantize { scale: f64 },
+    /// Add a bias tile (row-major, padded like the accumulators)
+    Bias(Vec<f64>),
+    /// Multiply by a constant
+    Scale(f64),
+    /// Apply an activation function
+    Activation(Activation),
+    /// Clamp into `[min, max]`
+    Clip { min: f64, max: f64 },
+}
+
+impl EpilogueOp {
+    /// Apply this step to a drained tile in place
+    pub fn apply(&self, values: &mut [f64]) {
+        match self {
+            EpilogueOp::Dequantize { scale } => values.iter_mut().for_each(|v| *v /= scale),
+            EpilogueOp::Bias(bias) => values.iter_mut().zip(bias).for_each(|(v, b)| *v += b),
+            EpilogueOp::Scale(factor) => values.iter_mut().for_each(|v| *v *= factor),
+            EpilogueOp::Activation(kind) => values.iter_mut().for_each(|v| *v = kind.apply(*v)),
+            EpilogueOp::Clip { min, max } => values.iter_mut().for_each(|v| *v = v.clamp(*min, *max)),
+        }
+    }
+    
+    fn hash_into(&self, hash: &mut Fnv1a) {
+        match self {
+            EpilogueOp::Dequantize { scale } => {
+                hash.write_u64(0);
+                hash.write_u64(scale.to_bits());
+            }
+            EpilogueOp::Bias(bias) => {
+                hash.write_u64(1);
+                hash.write_u64(bias.len() as u64);
+                bias.iter().for_each(|b| hash.write_u64(b.to_bits()));
+            }
+            EpilogueOp::Scale(factor) => {
+                hash.write_u64(2);
+                hash.write_u64(factor.to_bits());
+            }
+            EpilogueOp::Activation(kind) => {
+                hash.write_u64(3);
+                hash.write_u64(*kind as u64);
+            }
+            EpilogueOp::Clip { min, max } => {
+                hash.write_u64(4);
+                hash.write_u64(min.to_bits());
+                hash.write_u64(max.to_bits());
+            }
+        }
+    }
+}
+
+/// An operand element entering one edge lane of the array
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LaneInput {
+    /// Row of the array for A (left edge), column for B (top edge)
+    pub lane: usize,
+    /// Position along the shared K dimension
+    pub k: usize,
+    pub value: i64,
+}
+
+/// Edge inputs driven on one cycle of a pass's fill
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CycleInputs {
+    pub cycle: usize,
+    /// `A[lane][k]` entering from the left
+    pub a: Vec<LaneInput>,
+    /// `B[k][lane]` entering from the top
+    pub b: Vec<LaneInput>,
+}
+
+impl SystolicPass {
+    /// Multiply-accumulates doing useful work: the unpadded `a_shape @ b_shape` product
+    pub fn mac_count(&self) -> usize {
+        self.a_shape.0 * self.a_shape.1 * self.b_shape.1
+    }
+    
+    /// Drained accumulator values with the fused epilogue applied, in order
+    pub fn apply_epilogue(&self, acc: &[i64]) -> Vec<f64> {
+        let mut values: Vec<f64> = acc.iter().map(|&v| v as f64).collect();
+        self.epilogue.iter().for_each(|op| op.apply(&mut values));
+        values
+    }
+    
+    /// Staggered edge inputs, cycle by cycle, for driving this pass from a testbench
+    ///
+    /// Lane `i` is skewed by `i` cycles, so `A[i][k]` and `B[k][i]` enter on cycle
+    /// `i + k` and the fill takes `2N - 1` cycles. Under weight-stationary dataflow
+    /// B is preloaded rather than streamed, so `b` is empty and `A[i][k]` enters
+    /// array row `k` on cycle `i + k`.
+    pub fn fill_schedule(&self, config: &SystolicConfig) -> Vec<CycleInputs> {
+        let n = config.array_size;
+        if config.dataflow == Dataflow::WeightStationary {
+            return (0..(2 * n).saturating_sub(1))
+                .map(|cycle| CycleInputs {
+                    cycle,
+                    a: (0..n)
+                        .filter(|&k| cycle >= k && cycle - k < n)
+                        .map(|k| LaneInput { lane: k, k, value: self.matrix_a[(cycle - k) * n + k] })
+                        .collect(),
+                    b: Vec::new(),
+                })
+                .collect();
+        }
+        let lanes = |data: &[i64], cycle: usize| -> Vec<LaneInput> {
+            (0..n)
+                .filter(|&lane| cycle >= lane && cycle - lane < n)
+                .map(|lane| {
+                    let k = cycle - lane;
+                    LaneInput { lane, k, value: data[lane * n + k] }
+                })
+                .collect()
+        };
+        
+        // A is row-major and B column-major, so both index as [lane * n + k]
+        (0..(2 * n).saturating_sub(1))
+            .map(|cycle| CycleInputs {
+                cycle,
+                a: lanes(&self.matrix_a, cycle),
+                b: lanes(&self.matrix_b, cycle),
+            })
+            .collect()
+    }
+    
+    /// Multiply-accumulates the array spends on the pass, padding included
+    pub fn padded_mac_count(&self, config: &SystolicConfig) -> usize {
+        config.array_size.pow(3)
+    }
+    
+    /// Rows and columns of PEs doing real (unpadded) work: those accumulating an output
+    /// element, or under weight-stationary dataflow those holding a K x N weight
+    pub fn busy_pes(&self, config: &SystolicConfig) -> (usize, usize) {
+        match config.dataflow {
+            Dataflow::OutputStationary => (self.a_shape.0, self.b_shape.1),
+            Dataflow::WeightStationary => self.b_shape,
+        }
+    }
+    
+    /// Fraction of the array's PEs doing real (unpadded) work, per `busy_pes`
+    pub fn utilization(&self, config: &SystolicConfig) -> f64 {
+        let (rows, cols) = self.busy_pes(config);
+        (rows * cols) as f64 / config.array_size.pow(2) as f64
+    }
+    
+    /// Stable hash of what the pass computes: its operation, shapes, scales, and data
+    ///
+    /// The id, description, tile position, and addresses are left out, so identical
+    /// tiles hash equally wherever they appear. Uses FNV-1a, which is the same across
+    /// builds and platforms, so hashes can key a persistent cache.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write_u64(match self.operation {
+            PassOperation::Initialize => 0,
+            PassOperation::Accumulate => 1,
+            PassOperation::Final => 2,
+        });
+        for (rows, cols) in [self.a_shape, self.b_shape, self.output_shape] {
+            hash.write_u64(rows as u64);
+            hash.write_u64(cols as u64);
+        }
+        hash.write_u64(self.a_scale.to_bits());
+        hash.write_u64(self.b_scale.to_bits());
+        hash.write_u64(self.spill as u64 | (self.reload as u64) << 1);
+        for data in [&self.matrix_a, &self.matrix_b, &self.acc_init] {
+            // Length-prefixed so data cannot shift between fields
+            hash.write_u64(data.len() as u64);
+            data.iter().for_each(|&v| hash.write_u64(v as u64));
+        }
+        hash.write_u64(self.epilogue.len() as u64);
+        self.epilogue.iter().for_each(|op| op.hash_into(&mut hash));
+        hash.finish()
+    }
+}
+
+/// 64-bit FNV-1a, a simple hash that is stable across builds, unlike `DefaultHasher`
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+    
+    fn write_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+fn default_max_passes() -> usize {
+    DEFAULT_MAX_PASSES
 }
 
 /// Coordinate of a tile in a larger matrix
@@ -106,6 +809,16 @@ impl TileCoord {
             start_col,
         }
     }
+    
+    /// Rows of the full matrix this tile covers, clamped to its `rows`
+    pub fn row_range(&self, tile_size: usize, rows: usize) -> Range<usize> {
+        self.start_row.min(rows)..(self.start_row + tile_size).min(rows)
+    }
+    
+    /// Columns of the full matrix this tile covers, clamped to its `cols`
+    pub fn col_range(&self, tile_size: usize, cols: usize) -> Range<usize> {
+        self.start_col.min(cols)..(self.start_col + tile_size).min(cols)
+    }
 }
 
 /// Type of operation for a pass
@@ -119,6 +832,107 @@ pub enum PassOperation {
     Final,
 }
 
+/// Phase of a pass in the cycle model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TracePhase {
+    /// Operands are fed into the array edges
+    Load,
+    /// Partial sums propagate through the array
+    Compute,
+    /// Results are shifted out of the accumulators
+    Drain,
+}
+
+/// One phase of one pass on the serial schedule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Pass this event belongs to
+    pub pass_id: usize,
+    /// Phase of the pass
+    pub phase: TracePhase,
+    /// First cycle of the phase
+    pub start_cycle: usize,
+    /// Cycle after the last one of the phase (exclusive)
+    pub end_cycle: usize,
+}
+
+/// Post-processing the host must apply to a buffer after the array passes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EpilogueStep {
+    /// Buffer produced by this step
+    pub target: String,
+    /// Buffer the step reads
+    pub source: String,
+    /// Activation to apply element-wise
+    pub activation: Activation,
+    /// Shape of the buffer
+    pub shape: (usize, usize),
+}
+
+/// The passes of one tiled matmul and the matmuls whose results it consumes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MatMulGroup {
+    /// Buffer the matmul produces
+    pub target: String,
+    /// Index of the group's first pass in `HardwareProgram::passes`
+    pub first_pass: usize,
+    /// Number of consecutive passes in the group
+    pub num_passes: usize,
+    /// Indices of groups that must finish before this one starts
+    pub depends_on: Vec<usize>,
+    /// Shape of the product
+    #[serde(default)]
+    pub shape: (usize, usize),
+}
+
+/// A distinct operand buffer in the program's memory plan
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OperandBuffer {
+    /// Source buffer name; loads of the same variable share one buffer
+    pub name: String,
+    /// Base address (element offset)
+    pub addr: usize,
+    pub shape: (usize, usize),
+}
+
+/// Speedup of a program over a scalar processor doing one MAC per cycle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct SpeedupEstimate {
+    /// Useful MACs per cycle over `total_cycles`
+    pub speedup: f64,
+    /// MACs per cycle with every PE busy: `array_size²`
+    pub peak: f64,
+    /// `speedup / peak`
+    pub fraction_of_peak: f64,
+}
+
+/// How much of the array each pass keeps busy (see `SystolicPass::utilization`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UtilizationReport {
+    /// Utilization of each pass, in order
+    pub passes: Vec<f64>,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl std::fmt::Display for UtilizationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Utilization over {} pass(es): min {:.1}%, mean {:.1}%, max {:.1}%",
+            self.passes.len(),
+            self.min * 100.0,
+            self.mean * 100.0,
+            self.max * 100.0
+        )?;
+        for (i, utilization) in self.passes.iter().enumerate() {
+            writeln!(f, "  Pass {}: {:.1}%", i, utilization * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
 /// A complete hardware program
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HardwareProgram {
@@ -126,6 +940,15 @@ pub struct HardwareProgram {
     pub config: SystolicConfig,
     /// Sequence of passes to execute
     pub passes: Vec<SystolicPass>,
+    /// Host-side epilogue steps, in execution order
+    #[serde(default)]
+    pub epilogues: Vec<EpilogueStep>,
+    /// Matmul dependency DAG, in topological (emission) order
+    #[serde(default)]
+    pub groups: Vec<MatMulGroup>,
+    /// Operand buffers read by the passes, in allocation order
+    #[serde(default)]
+    pub inputs: Vec<OperandBuffer>,
     /// Final output shape
     pub output_shape: (usize, usize),
     /// Total estimated cycles
@@ -139,6 +962,9 @@ impl HardwareProgram {
         Self {
             config,
             passes: Vec::new(),
+            epilogues: Vec::new(),
+            groups: Vec::new(),
+            inputs: Vec::new(),
             output_shape: (0, 0),
             total_cycles: 0,
             summary: String::new(),
@@ -147,22 +973,176 @@ impl HardwareProgram {
     
     /// Add a pass to the program
     pub fn add_pass(&mut self, pass: SystolicPass) {
-        self.total_cycles += self.config.cycles_for_matmul();
+        self.total_cycles += self.config.pass_cycles(&pass);
         self.passes.push(pass);
     }
     
+    /// Bytes of memory holding the distinct input operands at `data_width` bits each
+    pub fn input_memory_bytes(&self) -> usize {
+        let elements: usize = self.inputs.iter()
+            .map(|input| self.config.memory_layout.buffer_size(input.shape, self.config.array_size))
+            .sum();
+        (elements * self.config.data_width).div_ceil(8)
+    }
+    
+    /// Bytes of memory holding every matmul result at `acc_width` bits each
+    pub fn output_memory_bytes(&self) -> usize {
+        let elements: usize = self.groups.iter().map(|group| group.shape.0 * group.shape.1).sum();
+        (elements * self.config.acc_width).div_ceil(8)
+    }
+    
+    /// Bytes of DRAM traffic from spilling and reloading partial sums
+    ///
+    /// Every spill writes a full NxN tile of `acc_width`-bit partial sums and every
+    /// reload reads one back.
+    pub fn spill_traffic_bytes(&self) -> usize {
+        let transfers = self.passes.iter().filter(|pass| pass.spill).count()
+            + self.passes.iter().filter(|pass| pass.reload).count();
+        (transfers * self.config.array_size.pow(2) * self.config.acc_width).div_ceil(8)
+    }
+    
+    /// Fraction of array MACs spent on padding: (padded - useful) / padded
+    ///
+    /// Each pass occupies the full NxN array for an NxN @ NxN product, while only
+    /// the unpadded `a_shape` and `b_shape` region does useful work.
+    pub fn padding_overhead(&self) -> f64 {
+        let padded_macs: usize = self.passes.iter().map(|pass| pass.padded_mac_count(&self.config)).sum();
+        if padded_macs == 0 {
+            return 0.0;
+        }
+        
+        let useful_macs: usize = self.passes.iter().map(SystolicPass::mac_count).sum();
+        
+        (padded_macs - useful_macs) as f64 / padded_macs as f64
+    }
+    
+    /// Length in cycles of the longest chain of dependent matmuls
+    ///
+    /// Unlike `total_cycles`, which assumes every pass runs back to back, this
+    /// lets independent matmuls overlap, e.g. on multiple arrays.
+    pub fn critical_path_cycles(&self) -> usize {
+        let mut finish = vec![0usize; self.groups.len()];
+        
+        for (i, group) in self.groups.iter().enumerate() {
+            let start = group.depends_on.iter()
+                .map(|&dep| finish[dep])
+                .max()
+                .unwrap_or(0);
+            let passes = &self.passes[group.first_pass..group.first_pass + group.num_passes];
+            finish[i] = start + passes.iter().map(|pass| self.config.pass_cycles(pass)).sum::<usize>();
+        }
+        
+        finish.into_iter().max().unwrap_or(0)
+    }
+    
+    /// Fraction of PEs doing real work, averaged over all passes
+    pub fn utilization(&self) -> f64 {
+        if self.passes.is_empty() {
+            return 0.0;
+        }
+        self.passes.iter().map(|pass| pass.utilization(&self.config)).sum::<f64>() / self.passes.len() as f64
+    }
+    
+    /// Per-pass utilization with its min, mean and max; all 0.0 without passes
+    pub fn utilization_report(&self) -> UtilizationReport {
+        let passes: Vec<f64> = self.passes.iter().map(|pass| pass.utilization(&self.config)).collect();
+        if passes.is_empty() {
+            return UtilizationReport { passes, min: 0.0, mean: 0.0, max: 0.0 };
+        }
+        UtilizationReport {
+            min: passes.iter().copied().fold(f64::INFINITY, f64::min),
+            mean: passes.iter().sum::<f64>() / passes.len() as f64,
+            max: passes.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            passes,
+        }
+    }
+    
+    /// Stable hash of the whole pass stream and the array it targets
+    ///
+    /// Equal hashes mean every pass's `content_hash` matches in order; when they differ,
+    /// comparing pass hashes shows which passes a cache must re-emit.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        let dataflow = self.config.dataflow as usize;
+        for value in [self.config.array_size, self.config.data_width, self.config.acc_width, dataflow, self.passes.len()] {
+            hash.write_u64(value as u64);
+        }
+        self.passes.iter().for_each(|pass| hash.write_u64(pass.content_hash()));
+        hash.finish()
+    }
+    
+    /// Estimated speedup over a scalar processor, and how close it comes to the array's peak
+    ///
+    /// Only unpadded MACs count. A full pass spends `cycles_for_matmul()` cycles around N
+    /// MACs per PE, so even fully used tiles reach at most `N / cycles_for_matmul()` of
+    /// peak: `N / (3N - 1)` output-stationary, `N / (4N - 2)` weight-stationary.
+    pub fn theoretical_peak_speedup(&self) -> SpeedupEstimate {
+        let n = self.config.array_size as f64;
+        let peak = n * n;
+        let useful_macs: usize = self.passes.iter().map(SystolicPass::mac_count).sum();
+        let speedup = if self.total_cycles == 0 {
+            0.0
+        } else {
+            useful_macs as f64 / self.total_cycles as f64
+        };
+        SpeedupEstimate { speedup, peak, fraction_of_peak: speedup / peak }
+    }
+    
+    /// One-line summary for logs, e.g. `3x3 int8 | 8 passes | 64 cycles | out (6,6)`
+    ///
+    /// The format is stable so it can be used in golden tests.
+    pub fn compact_description(&self) -> String {
+        format!(
+            "{}x{} int{} | {} passes | {} cycles | out ({},{})",
+            self.config.array_size,
+            self.config.array_size,
+            self.config.data_width,
+            self.passes.len(),
+            self.total_cycles,
+            self.output_shape.0,
+            self.output_shape.1
+        )
+    }
+    
+    /// Explanation for when the whole matmul fits in one pass on an oversized array
+    pub fn oversized_array_note(&self) -> Option<String> {
+        let [pass] = self.passes.as_slice() else {
+            return None;
+        };
+        let n = self.config.array_size;
+        let (rows, cols) = pass.busy_pes(&self.config);
+        if rows == n && cols == n {
+            return None;
+        }
+        Some(format!(
+            "The {}x{} array is larger than the whole matmul: one pass fills {}x{} of its PEs ({:.1}% utilization)",
+            n,
+            n,
+            rows,
+            cols,
+            self.utilization() * 100.0
+        ))
+    }
+    
     /// Generate a summary of the program
     pub fn generate_summary(&mut self) {
         let num_passes = self.passes.len();
         let cycles_per_pass = self.config.cycles_for_matmul();
+        let speedup = self.theoretical_peak_speedup();
         
         self.summary = format!(
             "Hardware Program Summary:\n\
              =========================\n\
              Target: {}x{} systolic array ({}-bit data, {}-bit accumulator)\n\
              Passes: {}\n\
-             Cycles per pass: {}\n\
+             Cycles per full pass: {}\n\
              Total cycles: {}\n\
+             Critical path cycles: {}\n\
+             Padding overhead: {:.1}%\n\
+             Utilization: {:.1}%\n\
+             Speedup over scalar: {:.1}x ({:.1}% of {}x peak)\n\
+             Input memory: {} bytes\n\
+             Output memory: {} bytes\n\
              Output shape: {:?}\n",
             self.config.array_size,
             self.config.array_size,
@@ -171,8 +1151,53 @@ impl HardwareProgram {
             num_passes,
             cycles_per_pass,
             self.total_cycles,
+            self.critical_path_cycles(),
+            self.padding_overhead() * 100.0,
+            self.utilization() * 100.0,
+            speedup.speedup,
+            speedup.fraction_of_peak * 100.0,
+            speedup.peak,
+            self.input_memory_bytes(),
+            self.output_memory_bytes(),
             self.output_shape
         );
+        let spills = self.passes.iter().filter(|pass| pass.spill).count();
+        if spills > 0 {
+            self.summary.push_str(&format!(
+                "Partial-sum spills: {} ({} bytes of DRAM traffic)\n",
+                spills,
+                self.spill_traffic_bytes()
+            ));
+        }
+        if let Some(note) = self.oversized_array_note() {
+            self.summary.push_str(&note);
+            self.summary.push('\n');
+        }
+    }
+    
+    /// Cycle-by-cycle schedule of every pass phase, running passes back to back
+    pub fn to_trace(&self) -> Vec<TraceEvent> {
+        let mut events = Vec::with_capacity(self.passes.len() * 3);
+        let mut cycle = 0;
+        
+        for pass in &self.passes {
+            for (phase, cycles) in self.config.pass_phase_cycles(pass) {
+                events.push(TraceEvent {
+                    pass_id: pass.id,
+                    phase,
+                    start_cycle: cycle,
+                    end_cycle: cycle + cycles,
+                });
+                cycle += cycles;
+            }
+        }
+        
+        events
+    }
+    
+    /// Export the trace to JSON format
+    pub fn to_trace_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&self.to_trace())
     }
     
     /// Export to JSON format
@@ -204,7 +1229,7 @@ impl HardwareProgram {
             }
             output.push_str("\n))\n\n");
             
-            // Matrix B (column-major)
+            // Matrix B (layout per config.dataflow)
             output.push_str(&format!("val matrixB_{} = VecInit(Seq(\n", i));
             for (j, val) in pass.matrix_b.iter().enumerate() {
                 if j > 0 {
@@ -220,6 +1245,286 @@ impl HardwareProgram {
         
         output
     }
+    
+    /// Export the pass operands as a `$readmemh` file, one hex word per line
+    ///
+    /// Each pass contributes its A tile (row-major) then its B tile (layout per `config.dataflow`),
+    /// packed per `config.packing`.
+    pub fn to_mem(&self) -> String {
+        let data_width = self.config.data_width;
+        let digits = self.config.packing.word_bits(data_width).div_ceil(4);
+        let mut output = String::new();
+        
+        for (i, pass) in self.passes.iter().enumerate() {
+            output.push_str(&format!("// Pass {}: {}\n", i, pass.description));
+            for data in [&pass.matrix_a, &pass.matrix_b] {
+                for word in self.config.packing.pack(data, data_width) {
+                    output.push_str(&format!("{:0width$x}\n", word, width = digits));
+                }
+            }
+        }
+        
+        output
+    }
+    
+    /// Export Verilog test vectors: a `$readmemh` image plus a driver stub that loads it
+    ///
+    /// The output holds two files, each introduced by a `// file: NAME` line.
+    /// `systolic_vectors.hex` has every pass's A then B tile as two's-complement
+    /// words of `data_width` bits, each array starting at an `@` address;
+    /// `systolic_driver.v` is a module parameterized by the array size and widths
+    /// that presents one pass's tiles at a time.
+    pub fn to_verilog_testbench(&self) -> String {
+        let n = self.config.array_size;
+        let data_width = self.config.data_width;
+        let digits = data_width.div_ceil(4);
+        let mask = if data_width >= 64 { u64::MAX } else { (1u64 << data_width) - 1 };
+        let mut output = String::new();
+        
+        output.push_str("// Auto-generated Verilog test vectors for SystolicArrayTop\n");
+        output.push_str(&format!("// Array size: {}x{}\n", n, n));
+        output.push_str("// Split at the `// file:` lines before simulating\n\n");
+        
+        output.push_str("// file: systolic_vectors.hex\n");
+        for (i, pass) in self.passes.iter().enumerate() {
+            output.push_str(&format!("// Pass {}: {}\n", i, pass.description));
+            for (j, data) in [&pass.matrix_a, &pass.matrix_b].into_iter().enumerate() {
+                output.push_str(&format!("@{:x}\n", (2 * i + j) * n * n));
+                for &value in data {
+                    output.push_str(&format!("{:0width$x}\n", value as u64 & mask, width = digits));
+                }
+            }
+        }
+        
+        output.push_str("\n// file: systolic_driver.v\n");
+        output.push_str(&format!(
+            r#"module systolic_driver #(
+    parameter ARRAY_SIZE = {},
+    parameter DATA_WIDTH = {},
+    parameter ACC_WIDTH = {},
+    parameter NUM_PASSES = {}
+) (
+    input  wire clk,
+    input  wire rst,
+    input  wire next_pass,
+    output reg  [31:0] pass,
+    output wire done,
+    output wire [ARRAY_SIZE*ARRAY_SIZE*DATA_WIDTH-1:0] matrix_a,
+    output wire [ARRAY_SIZE*ARRAY_SIZE*DATA_WIDTH-1:0] matrix_b
+);
+    localparam TILE = ARRAY_SIZE * ARRAY_SIZE;
+
+    reg [DATA_WIDTH-1:0] vectors [0:2*NUM_PASSES*TILE-1];
+    initial $readmemh("systolic_vectors.hex", vectors);
+
+    genvar i;
+    generate
+        for (i = 0; i < TILE; i = i + 1) begin : unpack
+            assign matrix_a[i*DATA_WIDTH +: DATA_WIDTH] = vectors[2*pass*TILE + i];
+            assign matrix_b[i*DATA_WIDTH +: DATA_WIDTH] = vectors[(2*pass+1)*TILE + i];
+        end
+    endgenerate
+
+    assign done = pass == NUM_PASSES;
+
+    always @(posedge clk) begin
+        if (rst)
+            pass <= 0;
+        else if (next_pass && !done)
+            pass <= pass + 1;
+    end
+endmodule
+"#,
+            n,
+            data_width,
+            self.config.acc_width,
+            self.passes.len(),
+        ));
+        
+        output
+    }
+    
+    /// Export as a C header of test vectors for bare-metal drivers
+    ///
+    /// Each pass gets `passN_a` (row-major) and `passN_b` (layout per `config.dataflow`) arrays
+    /// whose element type fits `data_width`, plus an entry in `systolic_passes`.
+    /// With `config.packing`, the arrays hold packed unsigned words instead.
+    pub fn to_c_header(&self) -> String {
+        let packing = self.config.packing;
+        let data_width = self.config.data_width;
+        let ty = if packing.is_packed() {
+            c_word_type(packing.word_bits(data_width))
+        } else {
+            c_int_type(data_width)
+        };
+        let mut output = String::new();
+        
+        output.push_str("/* Auto-generated test vectors for SystolicArrayTop */\n");
+        output.push_str("#ifndef SYSTOLIC_PROGRAM_H\n#define SYSTOLIC_PROGRAM_H\n\n");
+        output.push_str("#include <stdint.h>\n\n");
+        output.push_str(&format!("#define SYSTOLIC_ARRAY_SIZE {}\n", self.config.array_size));
+        output.push_str(&format!("#define SYSTOLIC_NUM_PASSES {}\n", self.passes.len()));
+        if packing.is_packed() {
+            output.push_str(&format!("#define SYSTOLIC_ELEMENTS_PER_WORD {}\n", packing.elements_per_word));
+        }
+        output.push('\n');
+        output.push_str("typedef enum { PASS_INITIALIZE, PASS_ACCUMULATE, PASS_FINAL } systolic_op_t;\n\n");
+        output.push_str("typedef struct {\n");
+        output.push_str(&format!("    const {} *a;\n    const {} *b;\n", ty, ty));
+        output.push_str("    uint32_t a_rows, a_cols;\n    uint32_t b_rows, b_cols;\n");
+        output.push_str("    uint32_t tile_row, tile_col;\n    systolic_op_t operation;\n");
+        output.push_str("} systolic_pass_t;\n\n");
+        
+        let array = |values: &[i64]| {
+            let elements: Vec<String> = if packing.is_packed() {
+                packing.pack(values, data_width).iter().map(|w| format!("0x{:x}", w)).collect()
+            } else {
+                values.iter().map(|v| v.to_string()).collect()
+            };
+            elements
+                .chunks(self.config.array_size.max(1))
+                .map(|row| row.join(", "))
+                .collect::<Vec<_>>()
+                .join(",\n    ")
+        };
+        for (i, pass) in self.passes.iter().enumerate() {
+            output.push_str(&format!("/* Pass {}: {} */\n", i, pass.description));
+            output.push_str(&format!("static const {} pass{}_a[] = {{\n    {}\n}};\n", ty, i, array(&pass.matrix_a)));
+            output.push_str(&format!("static const {} pass{}_b[] = {{\n    {}\n}};\n\n", ty, i, array(&pass.matrix_b)));
+        }
+        
+        output.push_str("static const systolic_pass_t systolic_passes[] = {\n");
+        for (i, pass) in self.passes.iter().enumerate() {
+            let operation = match pass.operation {
+                PassOperation::Initialize => "PASS_INITIALIZE",
+                PassOperation::Accumulate => "PASS_ACCUMULATE",
+                PassOperation::Final => "PASS_FINAL",
+            };
+            output.push_str(&format!(
+                "    {{ pass{i}_a, pass{i}_b, {}, {}, {}, {}, {}, {}, {} }},\n",
+                pass.a_shape.0,
+                pass.a_shape.1,
+                pass.b_shape.0,
+                pass.b_shape.1,
+                pass.output_tile.tile_row,
+                pass.output_tile.tile_col,
+                operation,
+                i = i
+            ));
+        }
+        output.push_str("};\n\n#endif /* SYSTOLIC_PROGRAM_H */\n");
+        
+        output
+    }
+    
+    /// Self-contained Python script redoing each matmul in NumPy
+    ///
+    /// Every group's quantized operands are reassembled from its pass tiles, along
+    /// with any starting accumulators, and their product is checked against the
+    /// `expected_output` of the group's `Final` passes. Epilogues are not applied,
+    /// so the comparison is on raw accumulator values.
+    pub fn to_numpy_reference(&self) -> String {
+        let n = self.config.array_size;
+        let mut output = String::new();
+        
+        output.push_str("# Auto-generated NumPy reference for the systolic program\n");
+        output.push_str("import numpy as np\n");
+        
+        for group in &self.groups {
+            let (rows, cols) = group.shape;
+            let passes = &self.passes[group.first_pass..group.first_pass + group.num_passes];
+            let depth = passes.iter()
+                .filter(|pass| pass.output_tile == passes[0].output_tile)
+                .map(|pass| pass.a_shape.1)
+                .sum();
+            let mut a = vec![vec![0; depth]; rows];
+            let mut b = vec![vec![0; cols]; depth];
+            let mut init = vec![vec![0; cols]; rows];
+            let mut expected = vec![vec![0; cols]; rows];
+            let mut has_init = false;
+            
+            // An output tile's K passes run in order, so each starts where the last one stopped
+            let mut k_offsets: HashMap<(usize, usize), usize> = HashMap::new();
+            for pass in passes {
+                let tile = &pass.output_tile;
+                let k0 = k_offsets.entry((tile.tile_row, tile.tile_col)).or_insert(0);
+                let (row_range, col_range) = (tile.row_range(n, rows), tile.col_range(n, cols));
+                for (i, row) in row_range.clone().enumerate() {
+                    for k in 0..pass.a_shape.1 {
+                        a[row][*k0 + k] = pass.matrix_a[i * n + k];
+                    }
+                }
+                for k in 0..pass.b_shape.0 {
+                    for (j, col) in col_range.clone().enumerate() {
+                        let idx = if self.config.dataflow.b_column_major() { j * n + k } else { k * n + j };
+                        b[*k0 + k][col] = pass.matrix_b[idx];
+                    }
+                }
+                
+                let fill = |target: &mut Vec<Vec<i64>>, values: &[i64]| {
+                    for (i, row) in row_range.clone().enumerate() {
+                        for (j, col) in col_range.clone().enumerate() {
+                            target[row][col] = values[i * n + j];
+                        }
+                    }
+                };
+                if *k0 == 0 && !pass.acc_init.is_empty() {
+                    fill(&mut init, &pass.acc_init);
+                    has_init = true;
+                }
+                if pass.operation == PassOperation::Final && !pass.expected_output.is_empty() {
+                    fill(&mut expected, &pass.expected_output);
+                }
+                *k0 += pass.a_shape.1;
+            }
+            
+            let name = &group.target;
+            output.push_str(&format!(
+                "\n# {}: ({}, {}) @ ({}, {}) over {} passes\n",
+                name, rows, depth, depth, cols, group.num_passes
+            ));
+            output.push_str(&format!("{}_a = {}\n", name, numpy_array(&a)));
+            output.push_str(&format!("{}_b = {}\n", name, numpy_array(&b)));
+            if has_init {
+                output.push_str(&format!("{}_init = {}\n", name, numpy_array(&init)));
+                output.push_str(&format!("{0} = {0}_a @ {0}_b + {0}_init\n", name));
+            } else {
+                output.push_str(&format!("{0} = {0}_a @ {0}_b\n", name));
+            }
+            output.push_str(&format!("np.testing.assert_allclose({}, {})\n", name, numpy_array(&expected)));
+        }
+        
+        output
+    }
+}
+
+/// `np.array` literal of integer rows
+fn numpy_array(rows: &[Vec<i64>]) -> String {
+    let rows: Vec<String> = rows.iter()
+        .map(|row| format!("[{}]", row.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")))
+        .collect();
+    format!("np.array([{}], dtype=np.int64)", rows.join(", "))
+}
+
+/// Smallest fixed-width C integer type holding a `data_width`-bit signed value
+fn c_int_type(data_width: usize) -> &'static str {
+    match data_width {
+        0..=8 => "int8_t",
+        9..=16 => "int16_t",
+        17..=32 => "int32_t",
+        // Quantized values are clamped to i64
+        _ => "int64_t",
+    }
+}
+
+/// Smallest fixed-width unsigned C type holding a `bits`-bit packed word
+fn c_word_type(bits: usize) -> &'static str {
+    match bits {
+        0..=8 => "uint8_t",
+        9..=16 => "uint16_t",
+        17..=32 => "uint32_t",
+        _ => "uint64_t",
+    }
 }
 
 impl std::fmt::Display for HardwareProgram {
@@ -241,27 +1546,65 @@ impl std::fmt::Display for HardwareProgram {
             writeln!(f, "  Output tile: ({}, {})", pass.output_tile.tile_row, pass.output_tile.tile_col)?;
             writeln!(f, "  Operation: {:?}", pass.operation)?;
             writeln!(f, "  Matrix A (row-major): {:?}", pass.matrix_a)?;
-            writeln!(f, "  Matrix B (col-major): {:?}", pass.matrix_b)?;
+            writeln!(f, "  Matrix B ({}): {:?}", self.config.dataflow.b_layout(), pass.matrix_b)?;
             writeln!(f)?;
         }
         
+        for step in &self.epilogues {
+            writeln!(f, "Host epilogue: {} = {}({})", step.target, step.activation, step.source)?;
+        }
+        
         Ok(())
     }
 }
 
-/// Convert floating point matrix to integer values for hardware
+/// In integer-only mode, reject any element of `name` that quantization would change
+pub fn check_integer_data(name: &str, matrix: &[f64], config: &SystolicConfig) -> CompileResult<()> {
+    if !config.integer_only {
+        return Ok(());
+    }
+    let (min, max) = (config.min_value() as f64, config.max_value() as f64);
+    match matrix.iter().find(|&&v| v.fract() != 0.0 || v < min || v > max) {
+        Some(v) => Err(CompileError::invalid_op(format!(
+            "Integer-only mode requires integers in [{}, {}], but '{}' contains {}",
+            config.min_value(),
+            config.max_value(),
+            name,
+            v
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Convert floating point matrix to integer values for hardware, rounding per `config.rounding`
 pub fn quantize_matrix(matrix: &[f64], scale: f64, config: &SystolicConfig) -> Vec<i64> {
-    let max_val = config.max_value();
-    let min_val = config.min_value();
+    quantize_matrix_with_mode(matrix, scale, config, config.rounding)
+}
+
+/// Convert floating point matrix to integer values for hardware with an explicit rounding mode
+pub fn quantize_matrix_with_mode(matrix: &[f64], scale: f64, config: &SystolicConfig, mode: RoundingMode) -> Vec<i64> {
+    // Quantized values are stored as i64, so wider data saturates at the i64 range
+    let max_val = config.max_value().min(i64::MAX as i128) as i64;
+    let min_val = config.min_value().max(i64::MIN as i128) as i64;
     
     matrix.iter()
         .map(|&v| {
-            let scaled = (v * scale).round() as i64;
+            let scaled = mode.round(v * scale) as i64;
             scaled.clamp(min_val, max_val)
         })
         .collect()
 }
 
+/// Product of a padded row-major A tile and a B tile laid out for `dataflow`, as the array computes it
+pub fn tile_product(a: &[i64], b: &[i64], n: usize, dataflow: Dataflow) -> Vec<i64> {
+    let b_at = |k: usize, j: usize| if dataflow.b_column_major() { b[j * n + k] } else { b[k * n + j] };
+    (0..n)
+        .flat_map(|i| (0..n).map(move |j| (i, j)))
+        // Wrapping, so a poison pad value reaching padded outputs cannot panic
+        .map(|(i, j)| (0..n).fold(0i64, |sum, k| sum.wrapping_add(a[i * n + k].wrapping_mul(b_at(k, j)))))
+        .collect()
+}
+
 /// Convert row-major matrix to column-major format
 pub fn row_to_column_major(matrix: &[i64], rows: usize, cols: usize) -> Vec<i64> {
     let mut result = vec![0i64; rows * cols];
@@ -273,9 +1616,26 @@ pub fn row_to_column_major(matrix: &[i64], rows: usize, cols: usize) -> Vec<i64>
     result
 }
 
-/// Pad a matrix to fit the systolic array size
+/// Pad a matrix to fit the systolic array size, filling with 0
 pub fn pad_matrix(matrix: &[i64], rows: usize, cols: usize, target_rows: usize, target_cols: usize) -> Vec<i64> {
-    let mut result = vec![0i64; target_rows * target_cols];
+    pad_matrix_with_value(matrix, rows, cols, target_rows, target_cols, 0)
+}
+
+/// Pad a matrix to fit the systolic array size, filling with `pad_value`
+///
+/// Padding along K meets real data in every output element, so it must be 0 for
+/// products to come out right. Past the edge rows of A and columns of B, `pad_value`
+/// only reaches padded outputs: codegen fills them with `SystolicConfig::zero_point`,
+/// and a poison value there makes `simulate_checked` catch any read of it.
+pub fn pad_matrix_with_value(
+    matrix: &[i64],
+    rows: usize,
+    cols: usize,
+    target_rows: usize,
+    target_cols: usize,
+    pad_value: i64,
+) -> Vec<i64> {
+    let mut result = vec![pad_value; target_rows * target_cols];
     for i in 0..rows.min(target_rows) {
         for j in 0..cols.min(target_cols) {
             result[i * target_cols + j] = matrix[i * cols + j];
@@ -288,14 +1648,76 @@ pub fn pad_matrix(matrix: &[i64], rows: usize, cols: usize, target_rows: usize,
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_z_order_tiles() {
+        let order = TileOrder::ZOrder.output_tiles(3, 4);
+        assert_eq!(&order[..8], &[
+            (0, 0), (0, 1), (1, 0), (1, 1),
+            (0, 2), (0, 3), (1, 2), (1, 3),
+        ]);
+        assert_eq!(&order[8..], &[(2, 0), (2, 1), (2, 2), (2, 3)]);
+    }
+    
     #[test]
     fn test_config() {
         let config = SystolicConfig::new(3, 8, 32);
         assert_eq!(config.max_value(), 127);
         assert_eq!(config.min_value(), -128);
+        assert_eq!(config.max_magnitude(), 128);
         assert_eq!(config.cycles_for_matmul(), 8); // 3*3 - 1 = 8
     }
     
+    #[test]
+    fn test_wide_data_range() {
+        let config = SystolicConfig::new(3, 64, 128);
+        assert_eq!(config.max_value(), i64::MAX as i128);
+        assert_eq!(config.min_value(), i64::MIN as i128);
+        assert!(config.validate().is_ok());
+        
+        let config = SystolicConfig::new(3, 128, 128);
+        assert_eq!(config.max_value(), i128::MAX);
+        assert_eq!(config.min_value(), i128::MIN);
+        assert_eq!(config.max_magnitude(), i128::MAX);
+        
+        assert!(SystolicConfig::new(3, 129, 128).validate().is_err());
+        assert!(SystolicConfig::new(3, 0, 32).validate().is_err());
+    }
+    
+    #[test]
+    fn test_rounding_modes() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let ties = [2.5, 3.5, -2.5];
+        let quantize = |mode| quantize_matrix_with_mode(&ties, 1.0, &config, mode);
+        assert_eq!(quantize(RoundingMode::HalfAwayFromZero), [3, 4, -3]);
+        assert_eq!(quantize(RoundingMode::HalfEven), [2, 4, -2]);
+        assert_eq!(quantize(RoundingMode::Truncate), [2, 3, -2]);
+        
+        // quantize_matrix follows the config, still clamping after rounding
+        assert_eq!(quantize_matrix(&ties, 1.0, &config), [3, 4, -3]);
+        let config = config.with_rounding(RoundingMode::HalfEven);
+        assert_eq!(quantize_matrix(&[2.5, 127.5, -128.5], 1.0, &config), [2, 127, -128]);
+    }
+    
+    #[test]
+    fn test_try_new() {
+        assert!(SystolicConfig::try_new(3, 8, 32).is_ok());
+        assert!(SystolicConfig::try_new(1, 2, 2).is_ok());
+        
+        for (array_size, data_width, acc_width, message) in [
+            (0, 8, 32, "Array size must be at least 1"),
+            (3, 1, 32, "Data width must be between 2 and 128 bits, got 1"),
+            (3, 16, 8, "Accumulator width (8 bits) must be at least the data width (16 bits)"),
+        ] {
+            let error = SystolicConfig::try_new(array_size, data_width, acc_width).unwrap_err();
+            assert!(matches!(error, CompileError::InvalidOperation { .. }));
+            assert!(error.to_string().contains(message), "{}", error);
+        }
+        
+        // A config built with `new` is still caught before tiling divides by its size
+        let error = crate::compile("C = [[1]] @ [[2]]", &SystolicConfig::new(0, 8, 32)).unwrap_err();
+        assert!(error.to_string().contains("Array size must be at least 1"), "{}", error);
+    }
+    
     #[test]
     fn test_row_to_column_major() {
         // 2x3 matrix: [[1,2,3], [4,5,6]]
@@ -310,5 +1732,367 @@ mod tests {
         let matrix = vec![1, 2, 3, 4]; // 2x2
         let padded = pad_matrix(&matrix, 2, 2, 3, 3);
         assert_eq!(padded, vec![1, 2, 0, 3, 4, 0, 0, 0, 0]);
+        
+        let padded = pad_matrix_with_value(&matrix, 2, 2, 3, 3, -128);
+        assert_eq!(padded, vec![1, 2, -128, 3, 4, -128, -128, -128, -128]);
+    }
+    
+    #[test]
+    fn test_zero_point_keeps_product() {
+        let config = SystolicConfig::new(3, 8, 32).with_zero_point(7);
+        let program = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", &config).unwrap();
+        
+        // Only padded output rows and columns see the zero-point; the padded K entry is 0
+        assert_eq!(program.passes[0].matrix_a, vec![1, 2, 0, 3, 4, 0, 7, 7, 7]);
+        assert_eq!(program.passes[0].expected_output[..2], [19, 22]);
+        assert_eq!(program.passes[0].expected_output[3..5], [43, 50]);
+        assert_eq!(program.evaluate().unwrap(), vec![vec![19.0, 22.0], vec![43.0, 50.0]]);
+        
+        let out_of_range = SystolicConfig::new(3, 8, 32).with_zero_point(128);
+        assert!(out_of_range.validate().unwrap_err().to_string().contains("Zero point 128 does not fit in 8-bit data"));
+    }
+    
+    #[test]
+    fn test_padding_overhead() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = vec![("A", (4, 4)), ("B", (4, 4))];
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &config).unwrap();
+        
+        // 8 passes of 27 MACs each, only 64 of which are useful
+        let overhead = program.padding_overhead();
+        assert!(overhead > 0.0);
+        assert!((overhead - (216.0 - 64.0) / 216.0).abs() < 1e-9);
+        assert!(program.summary.contains("Padding overhead"));
+    }
+    
+    #[test]
+    fn test_utilization_report() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (5, 3)), ("B", (3, 5))], &config).unwrap();
+        
+        // A full tile, two 3x2 / 2x3 edge tiles and a 2x2 corner
+        let report = program.utilization_report();
+        assert_eq!(report.passes, vec![1.0, 6.0 / 9.0, 6.0 / 9.0, 4.0 / 9.0]);
+        assert_eq!((report.min, report.max), (4.0 / 9.0, 1.0));
+        assert!((report.mean - program.utilization()).abs() < 1e-12);
+        assert!(report.to_string().starts_with("Utilization over 4 pass(es): min 44.4%, mean 69.4%, max 100.0%\n"));
+        
+        // Weight-stationary PEs are busy holding the K x N weights, not the M x N outputs
+        let config = SystolicConfig::new_with_dataflow(3, 8, 32, Dataflow::WeightStationary);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (5, 3)), ("B", (3, 5))], &config).unwrap();
+        assert_eq!(program.utilization_report().passes, vec![1.0, 6.0 / 9.0, 1.0, 6.0 / 9.0]);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (3, 2)), ("B", (2, 3))], &config).unwrap();
+        assert_eq!(program.utilization(), 6.0 / 9.0);
+    }
+    
+    #[test]
+    fn test_trace_matches_total_cycles() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = vec![("A", (4, 4)), ("B", (4, 4))];
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &config).unwrap();
+        
+        let trace = program.to_trace();
+        assert_eq!(trace.len(), program.passes.len() * 3);
+        assert_eq!(trace[0].phase, TracePhase::Load);
+        assert_eq!(trace[0].start_cycle, 0);
+        assert_eq!(trace.last().unwrap().end_cycle, program.total_cycles);
+        assert!(program.to_trace_json().unwrap().contains("\"phase\": \"Drain\""));
+    }
+    
+    #[test]
+    fn test_edge_tile_cycles() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = vec![("A", (5, 3)), ("B", (3, 5))];
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &config).unwrap();
+        
+        // A full 3x3 tile takes 3N - 1; the 2x2 corner tile only loads, skews and drains two
+        let cycles: Vec<_> = program.passes.iter().map(|pass| config.pass_cycles(pass)).collect();
+        assert_eq!(cycles, [8, 7, 7, 6]);
+        assert_eq!(program.total_cycles, 28);
+        assert_eq!(program.to_trace().last().unwrap().end_cycle, 28);
+        
+        let full = config.with_full_tile_cycles(true);
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &full).unwrap();
+        assert!(program.passes.iter().all(|pass| full.pass_cycles(pass) == 8));
+        assert_eq!(program.total_cycles, 32);
+    }
+    
+    #[test]
+    fn test_critical_path_cycles() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = vec![("A", (3, 3)), ("B", (3, 3)), ("D", (3, 3)), ("E", (3, 3))];
+        
+        // Two independent matmuls can overlap
+        let program = crate::compile_with_shapes("C = A @ B + D @ E", &shapes, &config).unwrap();
+        assert_eq!(program.groups.len(), 2);
+        assert_eq!(program.critical_path_cycles(), 8);
+        assert!(program.critical_path_cycles() < program.total_cycles);
+        
+        // A chain through the add cannot
+        let program = crate::compile_with_shapes("C = (A @ B + D) @ E", &shapes, &config).unwrap();
+        assert_eq!(program.groups[1].depends_on, vec![0]);
+        assert_eq!(program.critical_path_cycles(), program.total_cycles);
+    }
+    
+    #[test]
+    fn test_c_header_types_follow_data_width() {
+        let header = |data_width| {
+            crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &SystolicConfig::new(2, data_width, 40))
+                .unwrap()
+                .to_c_header()
+        };
+        
+        let narrow = header(8);
+        assert!(narrow.contains("static const int8_t pass0_a[] = {"), "{}", narrow);
+        assert!(narrow.contains("const int8_t *a;"), "{}", narrow);
+        assert!(narrow.contains("{ pass0_a, pass0_b, 2, 2, 2, 2, 0, 0, PASS_FINAL },"), "{}", narrow);
+        assert!(!narrow.contains("int16_t"));
+        
+        let wide = header(16);
+        assert!(wide.contains("static const int16_t pass0_b[] = {"), "{}", wide);
+        assert!(!wide.contains("int8_t"));
+    }
+    
+    #[test]
+    fn test_verilog_testbench() {
+        let config = SystolicConfig::new(2, 8, 24);
+        let program = crate::compile("C = [[-1, 2], [3, 4]] @ [[1, 1], [0, 1]]", &config).unwrap();
+        let verilog = program.to_verilog_testbench();
+        
+        // -1 is written as 8-bit two's complement, A at @0 and B right after it
+        assert!(verilog.contains("@0\nff\n02\n03\n04\n@4\n01\n00\n01\n01\n"), "{}", verilog);
+        assert!(verilog.contains("parameter ARRAY_SIZE = 2,"));
+        assert!(verilog.contains("parameter DATA_WIDTH = 8,"));
+        assert!(verilog.contains("parameter ACC_WIDTH = 24,"));
+        assert!(verilog.contains("parameter NUM_PASSES = 1"));
+        assert!(verilog.contains("$readmemh(\"systolic_vectors.hex\", vectors);"));
+    }
+    
+    #[test]
+    fn test_numpy_reference() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let program = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", &config).unwrap();
+        let reference = program.to_numpy_reference();
+        
+        assert!(reference.contains("import numpy as np\n"));
+        assert!(reference.contains("C_a = np.array([[1, 2], [3, 4]], dtype=np.int64)\n"), "{}", reference);
+        assert!(reference.contains("C_b = np.array([[5, 6], [7, 8]], dtype=np.int64)\n"));
+        assert!(reference.contains("C = C_a @ C_b\n"));
+        assert!(reference.contains("np.testing.assert_allclose(C, np.array([[19, 22], [43, 50]], dtype=np.int64))"));
+        
+        // K split over two passes on a 1x1 array still reassembles the full operands
+        let program = crate::compile("C = [[1, 2]] @ [[3], [4]]", &SystolicConfig::new(1, 8, 32)).unwrap();
+        let reference = program.to_numpy_reference();
+        assert!(reference.contains("C_a = np.array([[1, 2]], dtype=np.int64)\n"), "{}", reference);
+        assert!(reference.contains("C_b = np.array([[3], [4]], dtype=np.int64)\n"));
+        assert!(reference.contains("np.testing.assert_allclose(C, np.array([[11]], dtype=np.int64))"));
+    }
+    
+    #[test]
+    fn test_tile_coord_ranges() {
+        let full = TileCoord::new(1, 0, 3, 0);
+        assert_eq!(full.row_range(3, 7), 3..6);
+        assert_eq!(full.col_range(3, 5), 0..3);
+        
+        // Bottom-right tile of a 7x5 output on a 3x3 array
+        let edge = TileCoord::new(2, 1, 6, 3);
+        assert_eq!(edge.row_range(3, 7), 6..7);
+        assert_eq!(edge.col_range(3, 5), 3..5);
+    }
+    
+    #[test]
+    fn test_memory_bytes() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (4, 5)), ("B", (5, 6))], &config).unwrap();
+        
+        // 8-bit operands, 32-bit results
+        assert_eq!(program.input_memory_bytes(), 4 * 5 + 5 * 6);
+        assert_eq!(program.output_memory_bytes(), 4 * 6 * 4);
+        assert!(program.summary.contains("Input memory: 50 bytes"), "{}", program.summary);
+        
+        // The shared weight is stored once
+        let shapes = [("A", (4, 5)), ("B", (4, 5)), ("W", (5, 6))];
+        let program = crate::compile_with_shapes("C = A @ W; D = B @ W", &shapes, &config).unwrap();
+        let names: Vec<_> = program.inputs.iter().map(|input| input.name.as_str()).collect();
+        assert_eq!(names, ["A", "W", "B"]);
+        assert_eq!(program.input_memory_bytes(), 2 * 4 * 5 + 5 * 6);
+        assert_eq!(program.output_memory_bytes(), 2 * 4 * 6 * 4);
+    }
+    
+    #[test]
+    fn test_pass_mac_counts() {
+        let config = SystolicConfig::new(4, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (5, 4)), ("B", (4, 5))], &config).unwrap();
+        
+        let full = &program.passes[0];
+        assert_eq!(full.mac_count(), 64);
+        assert_eq!(full.padded_mac_count(&config), 64);
+        
+        // The bottom-right tile is a single row times a single column
+        let edge = program.passes.last().unwrap();
+        assert_eq!(edge.mac_count(), 4);
+        assert_eq!(edge.padded_mac_count(&config), 64);
+    }
+    
+    #[test]
+    fn test_fill_schedule_skew() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile("C = [[1, 2, 3], [4, 5, 6], [7, 8, 9]] @ [[1, 2, 3], [4, 5, 6], [7, 8, 10]]", &config).unwrap();
+        let schedule = program.passes[0].fill_schedule(&config);
+        
+        assert_eq!(schedule.len(), 5);
+        assert_eq!(schedule[0].a, vec![LaneInput { lane: 0, k: 0, value: 1 }]);
+        assert_eq!(schedule[0].b, vec![LaneInput { lane: 0, k: 0, value: 1 }]);
+        
+        // The full diagonal is in flight on cycle N - 1
+        let a: Vec<_> = schedule[2].a.iter().map(|input| (input.lane, input.k, input.value)).collect();
+        assert_eq!(a, vec![(0, 2, 3), (1, 1, 5), (2, 0, 7)]);
+        let b: Vec<_> = schedule[2].b.iter().map(|input| (input.lane, input.k, input.value)).collect();
+        assert_eq!(b, vec![(0, 2, 7), (1, 1, 5), (2, 0, 3)]);
+        
+        assert_eq!(schedule[4].a, vec![LaneInput { lane: 2, k: 2, value: 9 }]);
+        assert_eq!(schedule[4].b, vec![LaneInput { lane: 2, k: 2, value: 10 }]);
+    }
+    
+    #[test]
+    fn test_weight_stationary_dataflow() {
+        let source = "C = [[1, 2, 3], [4, 5, 6], [7, 8, 9]] @ [[1, 2, 3], [4, 5, 6], [7, 8, 10]]";
+        let os = crate::compile(source, &SystolicConfig::new(3, 8, 32)).unwrap();
+        let config = SystolicConfig::new_with_dataflow(3, 8, 32, Dataflow::WeightStationary);
+        let ws = crate::compile(source, &config).unwrap();
+        
+        // B is preloaded row-major, and each pass takes 4N - 2 cycles instead of 3N - 1
+        assert_eq!(os.passes[0].matrix_b, vec![1, 4, 7, 2, 5, 8, 3, 6, 10]);
+        assert_eq!(ws.passes[0].matrix_b, vec![1, 2, 3, 4, 5, 6, 7, 8, 10]);
+        assert_eq!(os.total_cycles, 8);
+        assert_eq!(ws.total_cycles, 10);
+        assert_eq!(ws.evaluate().unwrap(), os.evaluate().unwrap());
+        
+        // Only A streams in; A[i][k] enters array row k on cycle i + k
+        let schedule = ws.passes[0].fill_schedule(&config);
+        assert_eq!(schedule.len(), 5);
+        assert!(schedule.iter().all(|inputs| inputs.b.is_empty()));
+        let a: Vec<_> = schedule[2].a.iter().map(|input| (input.lane, input.value)).collect();
+        assert_eq!(a, vec![(0, 7), (1, 5), (2, 3)]);
+        
+        // Tiled programs agree as well
+        let config = SystolicConfig::new_with_dataflow(2, 8, 32, Dataflow::WeightStationary);
+        let tiled = crate::compile(source, &config).unwrap();
+        assert_eq!(tiled.evaluate().unwrap(), os.evaluate().unwrap());
+    }
+    
+    #[test]
+    fn test_oversized_array() {
+        let config = SystolicConfig::new(8, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).unwrap();
+        
+        assert_eq!(program.passes.len(), 1);
+        assert_eq!(program.utilization(), 1.0 / 16.0);
+        assert_eq!(program.passes[0].description, "C[0:2, 0:2] += A[0:2, 0:2] @ B[0:2, 0:2]");
+        let note = program.oversized_array_note().unwrap();
+        assert!(note.contains("one pass fills 2x2 of its PEs (6.2% utilization)"), "{}", note);
+        assert!(program.summary.contains(&note));
+        
+        // A matmul that fills the array exactly has nothing to report
+        let config = SystolicConfig::new(2, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).unwrap();
+        assert_eq!(program.utilization(), 1.0);
+        assert!(program.oversized_array_note().is_none());
+    }
+    
+    #[test]
+    fn test_word_packing() {
+        let values = [0x11, 0x22, 0x33, -1];
+        
+        let little = WordPacking::new(4, Endianness::Little);
+        assert_eq!(little.pack(&values, 8), vec![0xff33_2211]);
+        let big = WordPacking::new(4, Endianness::Big);
+        assert_eq!(big.pack(&values, 8), vec![0x1122_33ff]);
+        // A short last word is zero-filled
+        assert_eq!(little.pack(&values[..3], 8), vec![0x0033_2211]);
+        
+        let config = SystolicConfig::new(2, 8, 32).with_packing(WordPacking::new(16, Endianness::Little));
+        assert!(config.validate().is_err());
+    }
+    
+    #[test]
+    fn test_packed_exports() {
+        let config = SystolicConfig::new(2, 8, 32).with_packing(WordPacking::new(4, Endianness::Little));
+        let program = crate::compile("C = [[1, 2], [3, -4]] @ [[5, 6], [7, 8]]", &config).unwrap();
+        
+        // A is row-major, B column-major
+        assert_eq!(program.to_mem(), "// Pass 0: C[0:2, 0:2] += A[0:2, 0:2] @ B[0:2, 0:2]\nfc030201\n08060705\n");
+        let header = program.to_c_header();
+        assert!(header.contains("#define SYSTOLIC_ELEMENTS_PER_WORD 4"), "{}", header);
+        assert!(header.contains("static const uint32_t pass0_a[] = {\n    0xfc030201\n};"), "{}", header);
+        
+        // Unpacked, each element gets its own data_width-bit word
+        let program = crate::compile("C = [[1, 2], [3, -4]] @ [[5, 6], [7, 8]]", &SystolicConfig::new(2, 8, 32)).unwrap();
+        assert!(program.to_mem().contains("\n01\n02\n03\nfc\n05\n"));
+    }
+    
+    #[test]
+    fn test_compact_description() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (6, 6)), ("B", (6, 6))], &config).unwrap();
+        assert_eq!(program.compact_description(), "3x3 int8 | 8 passes | 64 cycles | out (6,6)");
+        
+        let program = crate::compile_with_shapes("y = A @ x", &[("A", (2, 5)), ("x", (5, 1))], &SystolicConfig::new(4, 16, 40)).unwrap();
+        assert_eq!(program.compact_description(), "4x4 int16 | 2 passes | 9 cycles | out (2,1)");
+    }
+    
+    #[test]
+    fn test_content_hash() {
+        let config = SystolicConfig::new(2, 8, 32);
+        // Both output tiles multiply the same A by the same B column block
+        let program = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6, 5, 6], [7, 8, 7, 8]]", &config).unwrap();
+        let (first, second) = (&program.passes[0], &program.passes[1]);
+        assert_ne!(first.output_tile, second.output_tile);
+        assert_eq!(first.content_hash(), second.content_hash());
+        
+        let mut changed = program.clone();
+        changed.passes[1].matrix_b[3] += 1;
+        assert_ne!(changed.passes[1].content_hash(), first.content_hash());
+        assert_ne!(changed.content_hash(), program.content_hash());
+        
+        // Stable across runs and builds
+        let again = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6, 5, 6], [7, 8, 7, 8]]", &config).unwrap();
+        assert_eq!(again.content_hash(), program.content_hash());
+        assert_eq!(Fnv1a::new().finish(), 0xcbf2_9ce4_8422_2325);
+    }
+    
+    #[test]
+    fn test_scalar_mode() {
+        let config = SystolicConfig::new(4, 8, 32).with_scalar_mode(true);
+        let program = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", &config).unwrap();
+        
+        // One pass per output element and K step
+        assert_eq!(program.passes.len(), 8);
+        assert!(program.passes.iter().all(|pass| pass.output_shape == (1, 1)));
+        assert_eq!(program.passes[3].description, "C[0:1, 1:2] += A[0:1, 1:2] @ B[1:2, 1:2]");
+        
+        let finals: Vec<i64> = program.simulate_checked().unwrap().into_iter().flatten().collect();
+        assert_eq!(finals, vec![19, 22, 43, 50]);
+    }
+    
+    #[test]
+    fn test_theoretical_peak_speedup() {
+        let config = SystolicConfig::new(4, 8, 32);
+        let shapes = [("A", (16, 16)), ("B", (16, 16))];
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &config).unwrap();
+        
+        // Every tile is full, so the array runs at its per-pass ceiling of N / (3N - 1)
+        let estimate = program.theoretical_peak_speedup();
+        assert_eq!(estimate.peak, 16.0);
+        assert_eq!(estimate.speedup, 4096.0 / (64.0 * 11.0));
+        assert!((estimate.fraction_of_peak - 4.0 / 11.0).abs() < 1e-12);
+        assert!(program.summary.contains("Speedup over scalar: 5.8x (36.4% of 16x peak)"), "{}", program.summary);
+        
+        // A 2x2 product padded into an 8x8 array leaves almost every PE idle
+        let config = SystolicConfig::new(8, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).unwrap();
+        let estimate = program.theoretical_peak_speedup();
+        assert_eq!(estimate.speedup, 8.0 / 5.0);
+        assert_eq!(estimate.fraction_of_peak, 1.6 / 64.0);
     }
 }
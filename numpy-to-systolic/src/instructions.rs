@@ -0,0 +1,187 @@
+//! Compact instruction stream for the array's control FSM
+//!
+//! Where `SystolicPass` describes each pass in terms of matrix data, the
+//! instruction stream describes what the controller does, in order:
+//!
+//! ```text
+//! LOAD_A 0, LOAD_B 0, MATMUL, LOAD_A 1, LOAD_B 1, ACC, WRITEBACK (0, 0), ACT relu
+//! ```
+//!
+//! An activation fused into a matmul's final pass comes just before its `WRITEBACK`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::Activation;
+use crate::hardware::{EpilogueOp, HardwareProgram, PassOperation};
+
+/// One controller instruction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Instruction {
+    /// Load the A operand of the given pass into the array edge buffers
+    LoadA { pass: usize },
+    /// Load the B operand of the given pass into the array edge buffers
+    LoadB { pass: usize },
+    /// Clear the accumulators and multiply
+    Matmul,
+    /// Multiply and add into the existing accumulators
+    Acc,
+    /// Write the accumulators to the output tile starting at (row, col)
+    Writeback { row: usize, col: usize },
+    /// Apply an activation on the host, or to the accumulators before a writeback
+    Act { activation: Activation },
+    /// Write the partial sums of the output tile at (row, col) to DRAM
+    Spill { row: usize, col: usize },
+    /// Read the spilled partial sums of the output tile at (row, col) back into the accumulators
+    Reload { row: usize, col: usize },
+}
+
+impl Instruction {
+    /// Numeric opcode used by the binary encoding
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Instruction::LoadA { .. } => 0x01,
+            Instruction::LoadB { .. } => 0x02,
+            Instruction::Matmul => 0x03,
+            Instruction::Acc => 0x04,
+            Instruction::Writeback { .. } => 0x05,
+            Instruction::Act { .. } => 0x06,
+            Instruction::Spill { .. } => 0x07,
+            Instruction::Reload { .. } => 0x08,
+        }
+    }
+
+    /// Append the binary form: opcode byte followed by little-endian u32 operands
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.push(self.opcode());
+        match self {
+            Instruction::LoadA { pass } | Instruction::LoadB { pass } => {
+                out.extend_from_slice(&(*pass as u32).to_le_bytes());
+            }
+            Instruction::Matmul | Instruction::Acc => {}
+            Instruction::Writeback { row, col }
+            | Instruction::Spill { row, col }
+            | Instruction::Reload { row, col } => {
+                out.extend_from_slice(&(*row as u32).to_le_bytes());
+                out.extend_from_slice(&(*col as u32).to_le_bytes());
+            }
+            Instruction::Act { activation } => {
+                let code: u8 = match activation {
+                    Activation::Relu => 0,
+                    Activation::Tanh => 1,
+                    Activation::Sigmoid => 2,
+                    Activation::Gelu => 3,
+                };
+                out.push(code);
+            }
+        }
+    }
+}
+
+impl HardwareProgram {
+    /// Derive the controller instruction stream from the pass list and epilogues
+    pub fn to_instructions(&self) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        // Whether the accumulators hold a partial sum from an earlier K tile
+        let mut accumulating = false;
+
+        for pass in &self.passes {
+            let (row, col) = (pass.output_tile.start_row, pass.output_tile.start_col);
+            if pass.reload {
+                instructions.push(Instruction::Reload { row, col });
+                accumulating = true;
+            }
+            instructions.push(Instruction::LoadA { pass: pass.id });
+            instructions.push(Instruction::LoadB { pass: pass.id });
+            instructions.push(if accumulating {
+                Instruction::Acc
+            } else {
+                Instruction::Matmul
+            });
+
+            match pass.operation {
+                PassOperation::Initialize | PassOperation::Accumulate if pass.spill => {
+                    instructions.push(Instruction::Spill { row, col });
+                    accumulating = false;
+                }
+                PassOperation::Initialize | PassOperation::Accumulate => accumulating = true,
+                PassOperation::Final => {
+                    for op in &pass.epilogue {
+                        if let EpilogueOp::Activation(activation) = op {
+                            instructions.push(Instruction::Act { activation: *activation });
+                        }
+                    }
+                    instructions.push(Instruction::Writeback { row, col });
+                    accumulating = false;
+                }
+            }
+        }
+
+        for step in &self.epilogues {
+            instructions.push(Instruction::Act { activation: step.activation });
+        }
+
+        instructions
+    }
+
+    /// Export the instruction stream to JSON format
+    pub fn to_instructions_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&self.to_instructions())
+    }
+
+    /// Export the instruction stream in its binary encoding
+    pub fn to_instruction_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for instruction in self.to_instructions() {
+            instruction.encode(&mut bytes);
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::SystolicConfig;
+
+    #[test]
+    fn test_single_matmul_instructions() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = vec![("A", (2, 2)), ("B", (2, 2))];
+        let program = crate::compile_with_shapes("C = relu(A @ B)", &shapes, &config).unwrap();
+
+        assert_eq!(
+            program.to_instructions(),
+            vec![
+                Instruction::LoadA { pass: 0 },
+                Instruction::LoadB { pass: 0 },
+                Instruction::Matmul,
+                // The relu is fused into the pass, so it acts on the accumulators
+                Instruction::Act { activation: Activation::Relu },
+                Instruction::Writeback { row: 0, col: 0 },
+            ]
+        );
+        assert_eq!(program.to_instruction_bytes().len(), 5 + 5 + 1 + 2 + 9);
+        assert!(program.to_instructions_json().unwrap().contains("\"op\": \"LOAD_A\""));
+    }
+
+    #[test]
+    fn test_k_tiled_matmul_accumulates() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = vec![("A", (3, 6)), ("B", (6, 3))];
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &config).unwrap();
+
+        let ops: Vec<_> = program.to_instructions().iter().map(Instruction::opcode).collect();
+        assert_eq!(ops, vec![0x01, 0x02, 0x03, 0x01, 0x02, 0x04, 0x05]);
+    }
+
+    #[test]
+    fn test_spilled_partial_sums_are_reloaded() {
+        let config = SystolicConfig::new(3, 8, 32).with_accumulator_k_tiles(1);
+        let shapes = vec![("A", (3, 6)), ("B", (6, 3))];
+        let program = crate::compile_with_shapes("C = A @ B", &shapes, &config).unwrap();
+
+        let ops: Vec<_> = program.to_instructions().iter().map(Instruction::opcode).collect();
+        assert_eq!(ops, vec![0x01, 0x02, 0x03, 0x07, 0x08, 0x01, 0x02, 0x04, 0x05]);
+    }
+}
@@ -2,19 +2,31 @@
 //!
 //! Supports tokens like:
 //! - Identifiers: A, B, matrix_name
-//! - Numbers: 1, 2.5, -3.14
-//! - Operators: @, +, -, *, /
+//! - Numbers: 1, 2.5, -3.14, 1e3, 1_000, 0xFF, 0b1010
+//! - Operators: @, +, -, *, **, /
 //! - Punctuation: (, ), [, ], ,, =
 //! - Keywords: np (for numpy functions)
 
 use logos::Logos;
 
+/// Text the lexer could not turn into a token
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LexError {
+    /// A character that starts no token
+    #[default]
+    UnexpectedCharacter,
+    /// A hex or binary literal with missing or invalid digits, e.g. `0x`
+    MalformedNumber,
+}
+
 /// Token types for the NumPy expression language
 #[derive(Logos, Debug, Clone, PartialEq)]
 #[logos(skip r"[ \t\n\r]+")]  // Skip whitespace
+#[logos(error = LexError)]
 pub enum Token {
-    // Literals
-    #[regex(r"-?[0-9]+\.?[0-9]*([eE][+-]?[0-9]+)?", |lex| lex.slice().parse::<f64>().ok())]
+    // Literals; `_` may separate digits, as in `1_000`
+    #[regex(r"-?[0-9](_?[0-9])*(\.([0-9](_?[0-9])*)?)?([eE][+-]?[0-9]+)?", |lex| lex.slice().replace('_', "").parse::<f64>().ok())]
+    #[regex(r"-?0[xXbB][0-9a-zA-Z_]*", radix_number)]
     Number(f64),
     
     // Identifiers and keywords
@@ -31,6 +43,9 @@ pub enum Token {
     #[token("-")]
     Minus,
     
+    #[token("**")]
+    Power,
+    
     #[token("*")]
     Star,
     
@@ -74,6 +89,7 @@ impl std::fmt::Display for Token {
             Token::MatMul => write!(f, "@"),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
+            Token::Power => write!(f, "**"),
             Token::Star => write!(f, "*"),
             Token::Slash => write!(f, "/"),
             Token::Equals => write!(f, "="),
@@ -89,10 +105,27 @@ impl std::fmt::Display for Token {
     }
 }
 
+/// Value of a hex (`0x1A`) or binary (`0b1010`) literal
+fn radix_number(lex: &mut logos::Lexer<Token>) -> Result<f64, LexError> {
+    let slice = lex.slice();
+    let (sign, literal) = match slice.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, slice),
+    };
+    let radix = if literal[1..2].eq_ignore_ascii_case("x") { 16 } else { 2 };
+    let digits = &literal[2..];
+    if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Err(LexError::MalformedNumber);
+    }
+    u64::from_str_radix(&digits.replace('_', ""), radix)
+        .map(|value| sign * value as f64)
+        .map_err(|_| LexError::MalformedNumber)
+}
+
 /// Lexer wrapper that provides a stream of tokens
 pub struct Lexer<'source> {
     inner: logos::Lexer<'source, Token>,
-    peeked: Option<Option<Result<Token, ()>>>,
+    peeked: Option<Option<Result<Token, LexError>>>,
 }
 
 impl<'source> Lexer<'source> {
@@ -108,8 +141,18 @@ impl<'source> Lexer<'source> {
         self.inner.span()
     }
     
+    /// Source text of the most recently lexed token
+    pub fn slice(&self) -> &'source str {
+        self.inner.slice()
+    }
+    
+    /// The whole source being lexed
+    pub fn source(&self) -> &'source str {
+        self.inner.source()
+    }
+    
     /// Peek at the next token without consuming it
-    pub fn peek(&mut self) -> Option<&Result<Token, ()>> {
+    pub fn peek(&mut self) -> Option<&Result<Token, LexError>> {
         if self.peeked.is_none() {
             self.peeked = Some(self.inner.next());
         }
@@ -136,7 +179,7 @@ impl<'source> Lexer<'source> {
 }
 
 impl<'source> Iterator for Lexer<'source> {
-    type Item = Result<Token, ()>;
+    type Item = Result<Token, LexError>;
     
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(peeked) = self.peeked.take() {
@@ -160,6 +203,10 @@ mod tests {
             Token::MatMul,
             Token::Ident("B".to_string()),
         ]);
+        
+        let tokens: Vec<_> = Lexer::new("A ** 2 * B").filter_map(Result::ok).collect();
+        assert_eq!(tokens[1], Token::Power);
+        assert_eq!(tokens[3], Token::Star);
     }
     
     #[test]
@@ -216,6 +263,20 @@ mod tests {
         ]);
     }
     
+    #[test]
+    fn test_radix_and_separated_literals() {
+        let tokens: Vec<_> = Lexer::new("0xFF @ B").collect();
+        assert_eq!(tokens, vec![Ok(Token::Number(255.0)), Ok(Token::MatMul), Ok(Token::Ident("B".to_string()))]);
+        
+        let tokens: Vec<_> = Lexer::new("0b1010 0x1a_2B -0x10 1_000 1e3 2.5 1_0.2_5").collect();
+        let numbers = [10.0, 6699.0, -16.0, 1000.0, 1000.0, 2.5, 10.25];
+        assert_eq!(tokens, numbers.iter().map(|&n| Ok(Token::Number(n))).collect::<Vec<_>>());
+        
+        for malformed in ["0x", "0b", "0b102", "0xG", "0x_1", "0x1__0", "0xFFFFFFFFFFFFFFFFF"] {
+            assert_eq!(Lexer::new(malformed).next(), Some(Err(LexError::MalformedNumber)), "{}", malformed);
+        }
+    }
+    
     #[test]
     fn test_transpose() {
         let source = "A.T";
@@ -18,18 +18,35 @@ pub mod ast;
 pub mod lexer;
 pub mod parser;
 pub mod analyzer;
+pub mod optimizer;
 pub mod tiling;
 pub mod codegen;
 pub mod hardware;
+pub mod instructions;
+pub mod diff;
+pub mod explain;
 pub mod error;
+pub mod compiler;
+pub mod simulator;
+pub mod repl;
+#[cfg(feature = "proto")]
+pub mod proto;
 
 pub use ast::*;
 pub use parser::Parser;
 pub use analyzer::Analyzer;
-pub use tiling::TilingStrategy;
-pub use codegen::CodeGenerator;
-pub use hardware::{SystolicConfig, SystolicPass, HardwareProgram};
+pub use optimizer::{OptimizationReport, RewriteKind};
+pub use tiling::{TiledProgram, TilingStrategy};
+pub use codegen::{CodeGenerator, OperandCalibration};
+pub use hardware::{Dataflow, Endianness, EpilogueOp, RoundingMode, SystolicConfig, SystolicPass, HardwareProgram, MemoryLayout, TileOrder, UtilizationReport, WordPacking};
+pub use instructions::Instruction;
+pub use diff::{PassChange, ProgramDiff};
+pub use explain::{explain, Explanation};
 pub use error::{CompileError, CompileResult};
+pub use compiler::Compiler;
+pub use simulator::{SimMatrix, Simulator};
+#[cfg(feature = "proto")]
+pub use proto::ProtoError;
 
 /// Main compilation function that takes a NumPy expression and produces hardware instructions
 pub fn compile(source: &str, config: &SystolicConfig) -> CompileResult<HardwareProgram> {
@@ -41,6 +58,9 @@ pub fn compile(source: &str, config: &SystolicConfig) -> CompileResult<HardwareP
     let mut analyzer = Analyzer::new();
     let typed_program = analyzer.analyze(program)?;
     
+    // Simplify identity products and constant scales
+    let typed_program = optimizer::optimize(typed_program);
+    
     // Generate tiling strategy
     let tiler = TilingStrategy::new(config.clone());
     let tiled_ops = tiler.tile_program(&typed_program)?;
@@ -58,20 +78,199 @@ pub fn compile_with_shapes(
     shapes: &[(&str, (usize, usize))],
     config: &SystolicConfig,
 ) -> CompileResult<HardwareProgram> {
-    let mut parser = Parser::new(source);
-    let program = parser.parse_program()?;
+    let tiled_ops = tile_source(source, shapes, config)?;
+    
+    let mut codegen = CodeGenerator::new(config.clone());
+    codegen.generate(tiled_ops)
+}
+
+/// Parse and shape-check `source`, stopping before optimization
+pub fn analyze_source(source: &str, shapes: &[(&str, (usize, usize))]) -> CompileResult<TypedProgram> {
+    let program = Parser::new(source).parse_program()?;
+    
+    let mut analyzer = Analyzer::new();
+    for (name, shape) in shapes {
+        analyzer.define_matrix(name, *shape);
+    }
+    analyzer.analyze(program)
+}
+
+/// Run `source` through optimization and tiling, stopping before code generation
+pub fn tile_source(
+    source: &str,
+    shapes: &[(&str, (usize, usize))],
+    config: &SystolicConfig,
+) -> CompileResult<TiledProgram> {
+    let typed_program = optimizer::optimize(analyze_source(source, shapes)?);
+    
+    let tiler = TilingStrategy::new(config.clone());
+    tiler.tile_program(&typed_program)
+}
+
+/// Compile with explicit matrix dimensions, reporting what the optimizer changed
+pub fn compile_optimized(
+    source: &str,
+    shapes: &[(&str, (usize, usize))],
+    config: &SystolicConfig,
+) -> CompileResult<(HardwareProgram, OptimizationReport)> {
+    let program = Parser::new(source).parse_program()?;
+    let mut analyzer = Analyzer::new();
+    for (name, shape) in shapes {
+        analyzer.define_matrix(name, *shape);
+    }
+    let typed_program = analyzer.analyze(program)?;
+    
+    let (optimized, mut rewrites) = optimizer::optimize_logged(typed_program.clone());
+    let (optimized, reused) = tiling::reuse_common_subexpressions(&optimized);
+    rewrites.extend(reused);
+    let tiler = TilingStrategy::new(config.clone());
+    let report = OptimizationReport::new(&typed_program, &optimized, &rewrites, &tiler)?;
     
+    let tiled_ops = tiler.tile_program(&optimized)?;
+    let mut codegen = CodeGenerator::new(config.clone());
+    Ok((codegen.generate(tiled_ops)?, report))
+}
+
+/// Compile each top-level statement of `source` as its own program
+///
+/// The statements share only the `shapes` definitions; an assignment in one does
+/// not define a variable for the next.
+pub fn compile_batch(
+    source: &str,
+    shapes: &[(&str, (usize, usize))],
+    config: &SystolicConfig,
+) -> CompileResult<Vec<HardwareProgram>> {
+    let mut compiler = Compiler::new(config.clone());
+    for (name, shape) in shapes {
+        compiler.define_matrix(name, *shape);
+    }
+    compiler.compile_batch(source)
+}
+
+/// Compile an AST built without the parser, e.g. by another front end
+pub fn compile_program(
+    program: Program,
+    shapes: &[(&str, (usize, usize))],
+    config: &SystolicConfig,
+) -> CompileResult<HardwareProgram> {
     let mut analyzer = Analyzer::new();
     for (name, shape) in shapes {
         analyzer.define_matrix(name, *shape);
     }
     let typed_program = analyzer.analyze(program)?;
     
+    compile_typed(typed_program, config)
+}
+
+/// Compile an already shape-checked program
+pub fn compile_typed(program: TypedProgram, config: &SystolicConfig) -> CompileResult<HardwareProgram> {
+    let typed_program = optimizer::optimize(program);
+    
     let tiler = TilingStrategy::new(config.clone());
     let tiled_ops = tiler.tile_program(&typed_program)?;
     
     let mut codegen = CodeGenerator::new(config.clone());
-    let hardware_program = codegen.generate(tiled_ops)?;
+    codegen.generate(tiled_ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
     
-    Ok(hardware_program)
+    #[test]
+    fn test_compile_program_from_hand_built_ast() {
+        let program = Program {
+            statements: vec![Statement::Assignment {
+                target: "C".to_string(),
+                value: Expr::MatMul(
+                    Box::new(Expr::Variable("A".to_string())),
+                    Box::new(Expr::Variable("B".to_string())),
+                ),
+            }],
+        };
+        let config = SystolicConfig::new(3, 8, 32);
+        
+        let result = compile_program(program, &[("A", (4, 3)), ("B", (3, 2))], &config).unwrap();
+        
+        let expected = compile_with_shapes("C = A @ B", &[("A", (4, 3)), ("B", (3, 2))], &config).unwrap();
+        assert_eq!(result.output_shape, (4, 2));
+        assert_eq!(result.passes.len(), expected.passes.len());
+    }
+    
+    #[test]
+    fn test_stage_functions() {
+        let shapes = [("A", (4, 3)), ("B", (3, 2))];
+        let typed = analyze_source("C = A @ B", &shapes).unwrap();
+        assert_eq!(typed.statements[0].target, "C");
+        assert_eq!(typed.statements[0].value.shape, Shape::matrix(4, 2));
+        assert!(analyze_source("C = A @ A", &shapes).is_err());
+        
+        // Tiling sees the optimized program, so the identity product is gone
+        let config = SystolicConfig::new(3, 8, 32);
+        let tiled = tile_source("C = A @ np.eye(3) @ B", &shapes, &config).unwrap();
+        assert_eq!(tiled.estimated_passes(), 2);
+        let program = compile_with_shapes("C = A @ np.eye(3) @ B", &shapes, &config).unwrap();
+        assert_eq!(program.passes.len(), tiled.estimated_passes());
+    }
+    
+    #[test]
+    fn test_compile_optimized_reports_identity_fold() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = [("A", (6, 6)), ("B", (6, 3))];
+        let (program, report) = compile_optimized("C = (A @ np.eye(6)) @ B", &shapes, &config).unwrap();
+        
+        assert_eq!(report.rewrites.len(), 1);
+        let rewrite = &report.rewrites[0];
+        assert_eq!((rewrite.statement.as_str(), rewrite.kind), ("C", RewriteKind::IdentityElimination));
+        // The folded A @ I alone was 2x2 output tiles with 2 K steps each
+        assert_eq!((rewrite.passes_before, rewrite.passes_after), (8, 0));
+        assert_eq!((report.passes_before, report.passes_after), (12, 4));
+        assert_eq!(program.passes.len(), report.passes_after);
+        assert!(report.to_string().contains("C: identity elimination (8 -> 0 passes)"), "{}", report);
+        
+        let (_, report) = compile_optimized("C = A @ A", &shapes, &config).unwrap();
+        assert!(report.rewrites.is_empty());
+    }
+    
+    #[test]
+    fn test_compile_optimized_reports_tiler_rewrites() {
+        let config = SystolicConfig::new(3, 8, 32)
+            .with_eliminate_dead_statements(true)
+            .with_reorder_matmul_chains(true);
+        let shapes = [("A", (6, 6)), ("B", (6, 3))];
+        let source = "X = A @ B; D = B.T @ A; Z = A @ A @ B; Y = A @ B + X + Z";
+        let (program, report) = compile_optimized(source, &shapes, &config).unwrap();
+        
+        let rewrites: Vec<_> = report.rewrites.iter()
+            .map(|rewrite| (rewrite.statement.as_str(), rewrite.kind, rewrite.passes_before, rewrite.passes_after))
+            .collect();
+        assert_eq!(rewrites, [
+            ("Y", RewriteKind::CommonSubexpressionElimination, 4, 0),
+            ("D", RewriteKind::DeadStatementElimination, 4, 0),
+            // (A @ A) @ B is 8 + 4 passes, A @ (A @ B) is 4 + 4
+            ("Z", RewriteKind::ChainReassociation, 12, 8),
+        ]);
+        assert_eq!((report.passes_before, report.passes_after), (24, 12));
+        assert_eq!(program.passes.len(), report.passes_after);
+        assert!(report.to_string().contains("D: dead statement elimination (4 -> 0 passes)"), "{}", report);
+    }
+    
+    #[test]
+    fn test_compile_batch_is_independent_per_statement() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let shapes = [("A", (4, 3)), ("B", (3, 2))];
+        
+        let programs = compile_batch("C = A @ B; D = A @ A.T", &shapes, &config).unwrap();
+        assert_eq!(programs.len(), 2);
+        assert_eq!(programs[0].output_shape, (4, 2));
+        assert_eq!(programs[1].output_shape, (4, 4));
+        
+        // C from the first program is not in scope for the second
+        assert!(compile_batch("C = A @ B; E = C @ B.T", &shapes, &config).is_err());
+        
+        // Function definitions are visible to every later statement
+        let programs = compile_batch("def f(x) = x @ B; C = f(A); D = f(A.T @ A)", &shapes, &config).unwrap();
+        assert_eq!(programs.len(), 2);
+        assert_eq!(programs[1].output_shape, (3, 2));
+    }
 }
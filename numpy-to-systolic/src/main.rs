@@ -4,15 +4,18 @@
 //!   numpy2systolic "C = A @ B" --shape A=3x4 --shape B=4x5
 //!   numpy2systolic -f program.numpy --array-size 4
 //!   numpy2systolic "[[1,2],[3,4]] @ [[5,6],[7,8]]" --json
+//!   numpy2systolic repl --array-size 4
 
-use clap::Parser as ClapParser;
+use clap::{Parser as ClapParser, Subcommand};
 use colored::Colorize;
 use std::fs;
 use std::io::{self, Read};
 
+use numpy_to_systolic::analyzer::DEFAULT_MAX_ELEMENTS;
+use numpy_to_systolic::hardware::DEFAULT_MAX_PASSES;
 use numpy_to_systolic::{
-    compile_with_shapes, Analyzer, CodeGenerator, HardwareProgram, Parser,
-    SystolicConfig, TilingStrategy,
+    optimizer, repl, Analyzer, CodeGenerator, CompileError, Compiler, Endianness, Explanation,
+    HardwareProgram, Parser, Simulator, SystolicConfig, TilingStrategy, WordPacking,
 };
 
 #[derive(ClapParser, Debug)]
@@ -21,6 +24,9 @@ use numpy_to_systolic::{
 #[command(version = "0.1.0")]
 #[command(about = "Compiles NumPy expressions to systolic array passes")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// NumPy expression to compile (e.g., "C = A @ B")
     #[arg(value_name = "EXPR")]
     expression: Option<String>,
@@ -30,34 +36,149 @@ struct Args {
     input_file: Option<String>,
 
     /// Define matrix shapes (e.g., "A=3x4")
-    #[arg(short = 's', long = "shape", value_parser = parse_shape)]
+    #[arg(short = 's', long = "shape", value_parser = parse_shape, global = true)]
     shapes: Vec<(String, (usize, usize))>,
 
+    /// Give a shaped matrix row-major contents (e.g., "A=1,2,3,4,5,6")
+    #[arg(long = "data", value_parser = parse_data, global = true)]
+    data: Vec<(String, Vec<f64>)>,
+
     /// Systolic array size (NxN)
-    #[arg(short = 'n', long = "array-size", default_value = "3")]
+    #[arg(short = 'n', long = "array-size", default_value = "3", global = true)]
     array_size: usize,
 
     /// Data width in bits
-    #[arg(short = 'd', long = "data-width", default_value = "8")]
+    #[arg(short = 'd', long = "data-width", default_value = "8", global = true)]
     data_width: usize,
 
     /// Accumulator width in bits
-    #[arg(short = 'a', long = "acc-width", default_value = "32")]
+    #[arg(short = 'a', long = "acc-width", default_value = "32", global = true)]
     acc_width: usize,
 
+    /// Largest matrix (in elements) accepted before compilation aborts
+    #[arg(long = "max-elements", default_value_t = DEFAULT_MAX_ELEMENTS, global = true)]
+    max_elements: usize,
+
+    /// Treat shape redefinitions as errors
+    #[arg(long = "strict", global = true)]
+    strict: bool,
+
+    /// Reject non-integer or out-of-range data instead of quantizing it
+    #[arg(long = "integer-only", global = true)]
+    integer_only: bool,
+
+    /// Compute `A @ B + X` by starting the accumulators from X
+    #[arg(long = "fuse-residual", global = true)]
+    fuse_residual: bool,
+
+    /// Apply bias, scale, activation and clip after a matmul on its final passes
+    #[arg(long = "fuse-epilogue", global = true)]
+    fuse_epilogue: bool,
+
+    /// Let accumulators saturate instead of rejecting a K that could overflow them
+    #[arg(long = "saturate-accumulators", global = true)]
+    saturate_accumulators: bool,
+
+    /// Skip statements whose results are never used
+    #[arg(long = "eliminate-dead-statements", global = true)]
+    eliminate_dead_statements: bool,
+
+    /// Count every pass as a full tile when estimating cycles
+    #[arg(long = "full-tile-cycles", global = true)]
+    full_tile_cycles: bool,
+
+    /// Compute `@` chains in the association order needing the fewest tiles
+    #[arg(long = "reorder-matmul-chains", global = true)]
+    reorder_matmul_chains: bool,
+
+    /// Tile for a 1x1 array so every output element is its own pass (for debugging)
+    #[arg(long = "scalar-mode", global = true)]
+    scalar_mode: bool,
+
+    /// Pad edge tiles' rows and columns with this affine zero-point instead of 0
+    #[arg(long = "zero-point", default_value = "0", allow_hyphen_values = true, global = true)]
+    zero_point: i64,
+
+    /// Spill partial sums to DRAM after every N K tiles (0 keeps them in the accumulators)
+    #[arg(long = "accumulator-k-tiles", value_name = "N", default_value = "0", global = true)]
+    accumulator_k_tiles: usize,
+
+    /// Fail before code generation if the program would need more than N passes
+    /// (any one matmul is held to 1000000 when not given)
+    #[arg(long = "max-passes", value_name = "N", global = true)]
+    max_passes: Option<usize>,
+
     /// Output as JSON
     #[arg(short = 'j', long = "json")]
     json_output: bool,
 
+    /// Compile each top-level statement as its own program and output a JSON array
+    #[arg(long = "batch")]
+    batch: bool,
+
+    /// Output the staggered edge-input schedule of one pass as JSON
+    #[arg(long = "fill-schedule", value_name = "PASS")]
+    fill_schedule: Option<usize>,
+
     /// Output Chisel test format
     #[arg(long = "chisel")]
     chisel_output: bool,
 
+    /// Output Verilog test vectors and a driver stub
+    #[arg(long = "verilog")]
+    verilog_output: bool,
+
+    /// Output a Python script checking each matmul with NumPy
+    #[arg(long = "numpy")]
+    numpy_reference: bool,
+
+    /// Output the analyzed expression trees, with inferred shapes, as GraphViz DOT
+    #[arg(long = "dot")]
+    dot: bool,
+
+    /// Output a C header of per-pass test vectors
+    #[arg(long = "c-header")]
+    c_header: bool,
+
+    /// Output pass operands as a $readmemh file
+    #[arg(long = "mem")]
+    mem: bool,
+
+    /// Pack N elements into each word of --mem and --c-header output
+    #[arg(long = "pack", value_name = "N", default_value = "1")]
+    pack: usize,
+
+    /// Put the first packed element in the most significant bits
+    #[arg(long = "big-endian")]
+    big_endian: bool,
+
+    /// Narrate each compilation phase's decisions instead of printing the program
+    #[arg(long = "explain")]
+    explain: bool,
+
+    /// Run the program on its literal data and print the result instead of passes
+    #[arg(long = "simulate")]
+    simulate: bool,
+
+    /// With --simulate, print the result as an aligned grid
+    #[arg(long = "pretty-matrix", requires = "simulate")]
+    pretty_matrix: bool,
+
+    /// Print how much of the array each pass uses
+    #[arg(long = "profile")]
+    profile: bool,
+
     /// Verbose output
     #[arg(short = 'v', long = "verbose")]
     verbose: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Interactive session that keeps variables across lines
+    Repl,
+}
+
 fn parse_shape(s: &str) -> Result<(String, (usize, usize)), String> {
     let parts: Vec<&str> = s.split('=').collect();
     if parts.len() != 2 {
@@ -80,9 +201,62 @@ fn parse_shape(s: &str) -> Result<(String, (usize, usize)), String> {
     Ok((name, (rows, cols)))
 }
 
+fn parse_data(s: &str) -> Result<(String, Vec<f64>), String> {
+    let (name, values) = s.split_once('=').ok_or_else(|| format!("Invalid data format: {}", s))?;
+    let values = values
+        .split(',')
+        .map(|value| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid value '{}' in data for {}", value, name))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((name.to_string(), values))
+}
+
 fn main() {
     let args = Args::parse();
 
+    if let Some(Command::Repl) = args.command {
+        let config = SystolicConfig::new(args.array_size, args.data_width, args.acc_width)
+            .with_integer_only(args.integer_only)
+            .with_fuse_residual(args.fuse_residual)
+            .with_fuse_epilogue(args.fuse_epilogue)
+            .with_saturate_accumulators(args.saturate_accumulators)
+            .with_eliminate_dead_statements(args.eliminate_dead_statements)
+            .with_full_tile_cycles(args.full_tile_cycles)
+            .with_reorder_matmul_chains(args.reorder_matmul_chains)
+            .with_max_passes(args.max_passes.unwrap_or(DEFAULT_MAX_PASSES))
+            .with_zero_point(args.zero_point)
+            .with_scalar_mode(args.scalar_mode)
+            .with_accumulator_k_tiles(args.accumulator_k_tiles);
+        if let Err(e) = config.validate() {
+            eprintln!("{}: {}", "Configuration error".red(), e);
+            std::process::exit(1);
+        }
+        let mut compiler = Compiler::new(config)
+            .with_max_elements(args.max_elements)
+            .with_strict(args.strict);
+        if let Some(max_passes) = args.max_passes {
+            compiler = compiler.with_max_passes(max_passes);
+        }
+        for (name, shape) in &args.shapes {
+            compiler.define_matrix(name, *shape);
+        }
+        for (name, values) in &args.data {
+            if let Err(e) = compiler.define_matrix_with_data(name, data_shape(&args.shapes, name), values) {
+                eprintln!("{}: {}", "Error".red(), e);
+                std::process::exit(1);
+            }
+        }
+        if let Err(e) = repl::run(&mut compiler, io::stdin().lock(), io::stdout()) {
+            eprintln!("{}: {}", "Error".red(), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Get expression from argument, file, or stdin
     let expression = if let Some(expr) = args.expression {
         expr
@@ -101,6 +275,11 @@ fn main() {
         buffer
     };
 
+    if expression.trim().is_empty() {
+        eprintln!("{}: No expression provided", "Error".red());
+        std::process::exit(1);
+    }
+
     if args.verbose {
         println!("{}", "NumPy to Systolic Array Compiler".bold().blue());
         println!("{}", "=".repeat(35));
@@ -118,65 +297,185 @@ fn main() {
     }
 
     // Create configuration
-    let config = SystolicConfig::new(args.array_size, args.data_width, args.acc_width);
-
-    // Convert shapes to the required format
-    let shapes: Vec<(&str, (usize, usize))> = args
-        .shapes
-        .iter()
-        .map(|(name, shape)| (name.as_str(), *shape))
-        .collect();
-
-    // Compile
-    let result = if shapes.is_empty() {
-        // Try to compile without explicit shapes (using literal matrices)
-        let mut parser = Parser::new(&expression);
-        let program = match parser.parse_program() {
-            Ok(p) => p,
-            Err(e) => {
-                eprintln!("{}: {}", "Parse error".red(), e);
-                std::process::exit(1);
-            }
-        };
+    let config = SystolicConfig::new(args.array_size, args.data_width, args.acc_width)
+        .with_integer_only(args.integer_only)
+        .with_fuse_residual(args.fuse_residual)
+        .with_fuse_epilogue(args.fuse_epilogue)
+        .with_saturate_accumulators(args.saturate_accumulators)
+        .with_eliminate_dead_statements(args.eliminate_dead_statements)
+        .with_full_tile_cycles(args.full_tile_cycles)
+        .with_reorder_matmul_chains(args.reorder_matmul_chains)
+        .with_max_passes(args.max_passes.unwrap_or(DEFAULT_MAX_PASSES))
+        .with_zero_point(args.zero_point)
+        .with_scalar_mode(args.scalar_mode)
+        .with_accumulator_k_tiles(args.accumulator_k_tiles)
+        .with_packing(WordPacking::new(
+            args.pack,
+            if args.big_endian { Endianness::Big } else { Endianness::Little },
+        ));
+    if let Err(e) = config.validate() {
+        eprintln!("{}: {}", "Configuration error".red(), e);
+        std::process::exit(1);
+    }
 
-        let mut analyzer = Analyzer::new();
-        let typed = match analyzer.analyze(program) {
-            Ok(t) => t,
-            Err(e) => {
-                eprintln!("{}: {}", "Type error".red(), e);
+    if args.batch {
+        let mut compiler = Compiler::new(config.clone())
+            .with_max_elements(args.max_elements)
+            .with_strict(args.strict);
+        if let Some(max_passes) = args.max_passes {
+            compiler = compiler.with_max_passes(max_passes);
+        }
+        for (name, shape) in &args.shapes {
+            compiler.define_matrix(name, *shape);
+        }
+        for (name, values) in &args.data {
+            if let Err(e) = compiler.define_matrix_with_data(name, data_shape(&args.shapes, name), values) {
+                eprintln!("{}: {}", "Error".red(), e);
                 std::process::exit(1);
             }
-        };
-
-        let tiler = TilingStrategy::new(config.clone());
-        let tiled = match tiler.tile_program(&typed) {
-            Ok(t) => t,
+        }
+        let programs = compiler.compile_batch(&expression).unwrap_or_else(|e| {
+            eprintln!("{}: {}", "Error".red(), e);
+            std::process::exit(1);
+        });
+        match serde_json::to_string_pretty(&programs) {
+            Ok(json) => println!("{}", json),
             Err(e) => {
-                eprintln!("{}: {}", "Tiling error".red(), e);
+                eprintln!("{}: Failed to serialize to JSON: {}", "Error".red(), e);
                 std::process::exit(1);
             }
-        };
+        }
+        return;
+    }
 
-        let mut codegen = CodeGenerator::new(config);
-        match codegen.generate(tiled) {
-            Ok(p) => p,
-            Err(e) => {
-                eprintln!("{}: {}", "Code generation error".red(), e);
-                std::process::exit(1);
+    // Compile, recording each phase's decisions for --explain
+    let mut explanation = args.explain.then(Explanation::new);
+    let mut parser = Parser::new(&expression);
+    let program = match parser.parse_program() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}: {}", "Parse error".red(), e.render(&expression));
+            std::process::exit(1);
+        }
+    };
+    if let Some(explanation) = &mut explanation {
+        explanation.record_parse(&program);
+    }
+
+    let mut analyzer = Analyzer::new()
+        .with_max_elements(args.max_elements)
+        .with_strict(args.strict);
+    for (name, shape) in &args.shapes {
+        analyzer.define_matrix(name, *shape);
+    }
+    for (name, values) in &args.data {
+        if let Err(e) = analyzer.define_matrix_with_data(name, data_shape(&args.shapes, name), values) {
+            eprintln!("{}: {}", "Error".red(), e);
+            std::process::exit(1);
+        }
+    }
+    let typed = match analyzer.analyze(program) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", "Type error".red(), e);
+            if let CompileError::UndefinedVariable { name } = &e {
+                eprintln!("Define it with --shape {}=RxC, or assign it earlier in the program", name);
             }
+            std::process::exit(1);
         }
-    } else {
-        match compile_with_shapes(&expression, &shapes, &config) {
-            Ok(p) => p,
+    };
+
+    print_warnings(analyzer.warnings());
+    if args.dot {
+        print!("{}", typed.to_dot());
+        return;
+    }
+    let typed = optimizer::optimize(typed);
+    if let Some(explanation) = &mut explanation {
+        explanation.record_analysis(&typed);
+    }
+
+    let tiler = TilingStrategy::new(config.clone());
+    let tiled = match tiler.tile_program(&typed) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", "Tiling error".red(), e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(explanation) = &mut explanation {
+        explanation.record_tiling(&tiled, &config);
+    }
+
+    if let Some(max_passes) = args.max_passes {
+        let estimate = tiled.estimated_passes();
+        if estimate > max_passes {
+            eprintln!(
+                "{}: program needs {} passes, more than --max-passes {}; \
+                 use a larger --array-size or smaller matrices",
+                "Error".red(),
+                estimate,
+                max_passes
+            );
+            std::process::exit(1);
+        }
+    }
+
+    if args.simulate {
+        let acc_width = config.acc_width;
+        let mut simulator = Simulator::new(config);
+        match simulator.run(&tiled) {
+            Ok(result) if args.pretty_matrix => println!("{}", result.to_pretty_string()),
+            Ok(result) => println!("{:?}", result.to_rows()),
             Err(e) => {
-                eprintln!("{}: {}", "Compilation error".red(), e);
+                eprintln!("{}: {}", "Simulation error".red(), e);
                 std::process::exit(1);
             }
         }
+        if simulator.saturated() > 0 {
+            eprintln!(
+                "{}: {} output element(s) saturated the {}-bit accumulator",
+                "Warning".yellow(),
+                simulator.saturated(),
+                acc_width
+            );
+        }
+        return;
+    }
+
+    let mut codegen = CodeGenerator::new(config);
+    let result = match codegen.generate(tiled) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}: {}", "Code generation error".red(), e);
+            std::process::exit(1);
+        }
     };
+    if let Some(mut explanation) = explanation {
+        explanation.record_codegen(&result);
+        print!("{}", explanation);
+        return;
+    }
 
     // Output
-    if args.json_output {
+    if let Some(pass_id) = args.fill_schedule {
+        let Some(pass) = result.passes.get(pass_id) else {
+            eprintln!(
+                "{}: pass {} does not exist ({} passes)",
+                "Error".red(),
+                pass_id,
+                result.passes.len()
+            );
+            std::process::exit(1);
+        };
+        match serde_json::to_string_pretty(&pass.fill_schedule(&result.config)) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("{}: Failed to serialize to JSON: {}", "Error".red(), e);
+                std::process::exit(1);
+            }
+        }
+    } else if args.json_output {
         match result.to_json() {
             Ok(json) => println!("{}", json),
             Err(e) => {
@@ -186,11 +485,44 @@ fn main() {
         }
     } else if args.chisel_output {
         println!("{}", result.to_chisel_test_format());
+    } else if args.verilog_output {
+        print!("{}", result.to_verilog_testbench());
+    } else if args.numpy_reference {
+        print!("{}", result.to_numpy_reference());
+    } else if args.c_header {
+        print!("{}", result.to_c_header());
+    } else if args.mem {
+        print!("{}", result.to_mem());
+    } else if args.profile {
+        print!("{}", result.utilization_report());
     } else {
         print_program(&result, args.verbose);
     }
 }
 
+/// Shape given with `--shape` for a `--data` matrix; the last one wins
+fn data_shape(shapes: &[(String, (usize, usize))], name: &str) -> (usize, usize) {
+    let Some((_, shape)) = shapes.iter().rev().find(|(shaped, _)| shaped == name) else {
+        eprintln!("{}: --data {} needs a --shape for {}", "Error".red(), name, name);
+        std::process::exit(1);
+    };
+    *shape
+}
+
+/// Summarize problems the lenient analyzer let through (stderr, so JSON output stays clean)
+fn print_warnings(warnings: &[String]) {
+    if warnings.is_empty() {
+        return;
+    }
+    for warning in warnings {
+        eprintln!("{}: {}", "Warning".yellow(), warning);
+    }
+    eprintln!(
+        "{} warning(s) suppressed; pass --strict to treat them as errors",
+        warnings.len()
+    );
+}
+
 fn print_program(program: &HardwareProgram, verbose: bool) {
     println!("{}", "Compilation Results".bold().green());
     println!("{}", "=".repeat(50));
@@ -208,13 +540,43 @@ fn print_program(program: &HardwareProgram, verbose: bool) {
         program.config.data_width,
         program.config.acc_width
     );
-    println!("{}: {}", "Output shape".cyan(), format!("{:?}", program.output_shape));
+    println!("{}: {:?}", "Output shape".cyan(), program.output_shape);
     println!("{}: {}", "Total passes".cyan(), program.passes.len());
     println!(
         "{}: {} cycles",
         "Total cycles".cyan(),
         program.total_cycles
     );
+    println!(
+        "{}: {:.1}%",
+        "Padding overhead".cyan(),
+        program.padding_overhead() * 100.0
+    );
+    println!("{}: {:.1}%", "Utilization".cyan(), program.utilization() * 100.0);
+    let speedup = program.theoretical_peak_speedup();
+    println!(
+        "{}: {:.1}x over scalar ({:.1}% of {}x peak)",
+        "Speedup".cyan(),
+        speedup.speedup,
+        speedup.fraction_of_peak * 100.0,
+        speedup.peak
+    );
+    if program.spill_traffic_bytes() > 0 {
+        println!(
+            "{}: {} bytes of partial-sum DRAM traffic",
+            "Spills".cyan(),
+            program.spill_traffic_bytes()
+        );
+    }
+    if let Some(note) = program.oversized_array_note() {
+        println!("{}: {}", "Note".yellow(), note);
+    }
+    println!(
+        "{}: {} bytes in, {} bytes out",
+        "Memory".cyan(),
+        program.input_memory_bytes(),
+        program.output_memory_bytes()
+    );
     println!();
 
     if verbose || program.passes.len() <= 8 {
@@ -234,6 +596,12 @@ fn print_program(program: &HardwareProgram, verbose: bool) {
                 pass.output_tile.tile_col
             );
             println!("  {}: {:?}", "Operation".cyan(), pass.operation);
+            println!(
+                "  {}: A @ {}, B @ {}",
+                "Operand addresses".cyan(),
+                pass.a_addr,
+                pass.b_addr
+            );
 
             if verbose {
                 println!(
@@ -243,7 +611,7 @@ fn print_program(program: &HardwareProgram, verbose: bool) {
                 );
                 println!(
                     "  {}: {:?}",
-                    "Matrix B (col-major)".cyan(),
+                    format!("Matrix B ({})", program.config.dataflow.b_layout()).cyan(),
                     pass.matrix_b
                 );
             }
@@ -0,0 +1,407 @@
+//! Algebraic simplification of typed programs
+//!
+//! Runs between shape analysis and tiling. Rewrites are bottom-up and
+//! shape-preserving:
+//! - `s * M` with a literal `M` is folded into a new literal
+//! - `s * X` written with `*` becomes a scalar multiply rather than an element-wise one
+//! - nested scalar multiplies collapse into one (`a * (b * X)` -> `(a*b) * X`)
+//! - `X @ (c * I)` and `(c * I) @ X` become `c * X`, so no array passes are emitted
+//!
+//! `optimize_logged` also returns the rewrites, which `OptimizationReport` turns
+//! into pass counts alongside the tiler's common subexpression elimination, dead
+//! statement elimination and matmul chain reassociation.
+
+use std::fmt;
+
+use crate::ast::{Shape, TypedExpr, TypedExprKind, TypedProgram, TypedStatement};
+use crate::error::CompileResult;
+use crate::tiling::{live_statements, TilingStrategy};
+
+/// Simplify every statement of a program
+pub fn optimize(program: TypedProgram) -> TypedProgram {
+    optimize_logged(program).0
+}
+
+/// Simplify every statement, also returning each rewrite applied
+pub fn optimize_logged(program: TypedProgram) -> (TypedProgram, Vec<Rewrite>) {
+    let mut rewrites = Vec::new();
+    let mut statements = Vec::with_capacity(program.statements.len());
+    for stmt in program.statements {
+        let mut log = Vec::new();
+        let value = optimize_into(stmt.value, &mut log);
+        rewrites.extend(log.into_iter().map(|(kind, before, after)| Rewrite {
+            statement: stmt.target.clone(),
+            kind,
+            before,
+            after,
+        }));
+        statements.push(TypedStatement { target: stmt.target, value });
+    }
+    (TypedProgram { statements, input_scales: program.input_scales, input_data: program.input_data }, rewrites)
+}
+
+/// Simplify an expression, children first
+pub fn optimize_expr(expr: TypedExpr) -> TypedExpr {
+    optimize_into(expr, &mut Vec::new())
+}
+
+/// Kind of simplification the optimizer applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewriteKind {
+    /// A product with `c * I`, or a multiply by 1, dropped
+    IdentityElimination,
+    /// A constant multiplied into a literal
+    ConstantFolding,
+    /// Nested scalar multiplies merged into one
+    ScaleCollapse,
+    /// An element-wise multiply by a scalar turned into a scalar multiply
+    ScalarMulCanonicalization,
+    /// A subexpression replaced by a read of an earlier statement's result
+    CommonSubexpressionElimination,
+    /// A statement skipped because nothing reads its result
+    DeadStatementElimination,
+    /// An `@` chain tiled in a cheaper association order
+    ChainReassociation,
+}
+
+impl fmt::Display for RewriteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewriteKind::IdentityElimination => write!(f, "identity elimination"),
+            RewriteKind::ConstantFolding => write!(f, "constant folding"),
+            RewriteKind::ScaleCollapse => write!(f, "scale collapse"),
+            RewriteKind::ScalarMulCanonicalization => write!(f, "scalar multiply canonicalization"),
+            RewriteKind::CommonSubexpressionElimination => write!(f, "common subexpression elimination"),
+            RewriteKind::DeadStatementElimination => write!(f, "dead statement elimination"),
+            RewriteKind::ChainReassociation => write!(f, "chain reassociation"),
+        }
+    }
+}
+
+/// One rewrite, with the subexpression it replaced and what replaced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rewrite {
+    /// Target of the statement the rewrite happened in
+    pub statement: String,
+    pub kind: RewriteKind,
+    pub before: TypedExpr,
+    pub after: TypedExpr,
+}
+
+/// A rewrite with the array passes its subexpression needed before and after
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedRewrite {
+    pub statement: String,
+    pub kind: RewriteKind,
+    pub passes_before: usize,
+    pub passes_after: usize,
+}
+
+/// Everything the optimizer and tiler changed in a program, and what it saved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizationReport {
+    /// Rewrites in the order they were applied, innermost first, then the statements
+    /// the tiler eliminated or reassociated
+    pub rewrites: Vec<AppliedRewrite>,
+    /// Passes the program would need without optimization
+    pub passes_before: usize,
+    pub passes_after: usize,
+}
+
+impl OptimizationReport {
+    /// Count the passes each rewrite and the whole program needed before and after
+    ///
+    /// Dead statements and chain reassociation come from `tiler`'s config, applied to
+    /// `optimized`. Every count leaves out the other tiler options, so no saving is
+    /// credited twice.
+    pub fn new(
+        original: &TypedProgram,
+        optimized: &TypedProgram,
+        rewrites: &[Rewrite],
+        tiler: &TilingStrategy,
+    ) -> CompileResult<Self> {
+        let config = tiler.config();
+        let plain = TilingStrategy::new(
+            config.clone().with_eliminate_dead_statements(false).with_reorder_matmul_chains(false),
+        );
+        let reordering = TilingStrategy::new(config.clone().with_eliminate_dead_statements(false));
+        let passes = |tiler: &TilingStrategy, expr: &TypedExpr| -> CompileResult<usize> {
+            let program = TypedProgram {
+                statements: vec![TypedStatement { target: "_".to_string(), value: expr.clone() }],
+                input_scales: original.input_scales.clone(),
+                input_data: original.input_data.clone(),
+            };
+            Ok(tiler.tile_program(&program)?.estimated_passes())
+        };
+        let mut applied = rewrites.iter()
+            .map(|rewrite| {
+                Ok(AppliedRewrite {
+                    statement: rewrite.statement.clone(),
+                    kind: rewrite.kind,
+                    passes_before: passes(&plain, &rewrite.before)?,
+                    passes_after: passes(&plain, &rewrite.after)?,
+                })
+            })
+            .collect::<CompileResult<Vec<_>>>()?;
+        
+        let live = if config.eliminate_dead_statements {
+            live_statements(&optimized.statements)
+        } else {
+            vec![true; optimized.statements.len()]
+        };
+        for (stmt, live) in optimized.statements.iter().zip(live) {
+            let statement = stmt.target.clone();
+            if !live {
+                let passes_before = passes(&plain, &stmt.value)?;
+                let kind = RewriteKind::DeadStatementElimination;
+                applied.push(AppliedRewrite { statement, kind, passes_before, passes_after: 0 });
+            } else if config.reorder_matmul_chains {
+                // The search may keep the order as written, which is no rewrite
+                let (passes_before, passes_after) = (passes(&plain, &stmt.value)?, passes(&reordering, &stmt.value)?);
+                if passes_after < passes_before {
+                    let kind = RewriteKind::ChainReassociation;
+                    applied.push(AppliedRewrite { statement, kind, passes_before, passes_after });
+                }
+            }
+        }
+        
+        Ok(Self {
+            rewrites: applied,
+            passes_before: plain.tile_program(original)?.estimated_passes(),
+            passes_after: tiler.tile_program(optimized)?.estimated_passes(),
+        })
+    }
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Optimization: {} -> {} passes", self.passes_before, self.passes_after)?;
+        for rewrite in &self.rewrites {
+            writeln!(
+                f,
+                "  {}: {} ({} -> {} passes)",
+                rewrite.statement, rewrite.kind, rewrite.passes_before, rewrite.passes_after
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Log entry: the rewrite and the subexpression before and after it
+type LogEntry = (RewriteKind, TypedExpr, TypedExpr);
+
+fn optimize_into(expr: TypedExpr, log: &mut Vec<LogEntry>) -> TypedExpr {
+    let TypedExpr { expr: kind, shape } = expr;
+    let mut opt = |e: Box<TypedExpr>| Box::new(optimize_into(*e, log));
+
+    match kind {
+        TypedExprKind::MatMul(left, right) => {
+            let (left, right) = (opt(left), opt(right));
+            let (c, other) = match (scaled_identity(&right), scaled_identity(&left)) {
+                (Some(c), _) => (c, &left),
+                (None, Some(c)) => (c, &right),
+                (None, None) => return TypedExpr { expr: TypedExprKind::MatMul(left, right), shape },
+            };
+            let after = scale(other.as_ref().clone(), c);
+            let before = TypedExpr { expr: TypedExprKind::MatMul(left, right), shape };
+            record(log, RewriteKind::IdentityElimination, before, after)
+        }
+        TypedExprKind::Mul(left, right) => {
+            let (left, right) = (opt(left), opt(right));
+            let (s, other) = match (scalar_value(&left), scalar_value(&right)) {
+                (Some(s), None) => (s, &right),
+                (None, Some(s)) => (s, &left),
+                _ => return TypedExpr { expr: TypedExprKind::Mul(left, right), shape },
+            };
+            let kind = scale_rewrite(other, s);
+            let after = scale(other.as_ref().clone(), s);
+            record(log, kind, TypedExpr { expr: TypedExprKind::Mul(left, right), shape }, after)
+        }
+        TypedExprKind::Div(left, right) => TypedExpr { expr: TypedExprKind::Div(opt(left), opt(right)), shape },
+        TypedExprKind::Outer(left, right) => TypedExpr { expr: TypedExprKind::Outer(opt(left), opt(right)), shape },
+        TypedExprKind::ScalarMul(scalar, matrix) => {
+            let (scalar, matrix) = (opt(scalar), opt(matrix));
+            match scalar_value(&scalar) {
+                Some(s) => {
+                    let kind = scale_rewrite(&matrix, s);
+                    let after = scale(matrix.as_ref().clone(), s);
+                    record(log, kind, TypedExpr { expr: TypedExprKind::ScalarMul(scalar, matrix), shape }, after)
+                }
+                None => TypedExpr { expr: TypedExprKind::ScalarMul(scalar, matrix), shape },
+            }
+        }
+        TypedExprKind::Add(l, r) => TypedExpr { expr: TypedExprKind::Add(opt(l), opt(r)), shape },
+        TypedExprKind::Sub(l, r) => TypedExpr { expr: TypedExprKind::Sub(opt(l), opt(r)), shape },
+        TypedExprKind::Transpose(inner) => TypedExpr { expr: TypedExprKind::Transpose(opt(inner)), shape },
+        TypedExprKind::Grouped(inner) => TypedExpr { expr: TypedExprKind::Grouped(opt(inner)), shape },
+        TypedExprKind::Index(inner, subs) => TypedExpr { expr: TypedExprKind::Index(opt(inner), subs), shape },
+        TypedExprKind::Activation(kind, inner) => {
+            TypedExpr { expr: TypedExprKind::Activation(kind, opt(inner)), shape }
+        }
+        TypedExprKind::Reshape(inner) => TypedExpr { expr: TypedExprKind::Reshape(opt(inner)), shape },
+        TypedExprKind::Diag(inner) => TypedExpr { expr: TypedExprKind::Diag(opt(inner)), shape },
+        TypedExprKind::Out(buffer, inner) => TypedExpr { expr: TypedExprKind::Out(buffer, opt(inner)), shape },
+        TypedExprKind::Split { source, axis, sections, part } => TypedExpr {
+            expr: TypedExprKind::Split { source: opt(source), axis, sections, part },
+            shape,
+        },
+        TypedExprKind::Concat { left, right, axis } => TypedExpr {
+            expr: TypedExprKind::Concat { left: opt(left), right: opt(right), axis },
+            shape,
+        },
+        TypedExprKind::Roll { source, shift, axis } => TypedExpr {
+            expr: TypedExprKind::Roll { source: opt(source), shift, axis },
+            shape,
+        },
+        TypedExprKind::Norm { source, axis } => TypedExpr {
+            expr: TypedExprKind::Norm { source: opt(source), axis },
+            shape,
+        },
+        TypedExprKind::Clip { source, min, max } => TypedExpr {
+            expr: TypedExprKind::Clip { source: opt(source), min, max },
+            shape,
+        },
+        TypedExprKind::Let { name, value, body } => TypedExpr {
+            expr: TypedExprKind::Let { name, value: opt(value), body: opt(body) },
+            shape,
+        },
+        leaf @ (TypedExprKind::Variable(_) | TypedExprKind::Scalar(_) | TypedExprKind::Matrix(_)) => {
+            TypedExpr { expr: leaf, shape }
+        }
+    }
+}
+
+/// Log a rewrite unless it left the expression unchanged, and return the result
+fn record(log: &mut Vec<LogEntry>, kind: RewriteKind, before: TypedExpr, after: TypedExpr) -> TypedExpr {
+    if before != after {
+        log.push((kind, before, after.clone()));
+    }
+    after
+}
+
+/// What `scale(expr, s)` will do to `expr`
+fn scale_rewrite(expr: &TypedExpr, s: f64) -> RewriteKind {
+    match &expr.expr {
+        _ if s == 1.0 => RewriteKind::IdentityElimination,
+        TypedExprKind::Scalar(_) | TypedExprKind::Matrix(_) => RewriteKind::ConstantFolding,
+        TypedExprKind::ScalarMul(inner, _) if scalar_value(inner).is_some() => RewriteKind::ScaleCollapse,
+        _ => RewriteKind::ScalarMulCanonicalization,
+    }
+}
+
+fn scalar_value(expr: &TypedExpr) -> Option<f64> {
+    match expr.expr {
+        TypedExprKind::Scalar(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// `c` if the expression is a literal `c * I`
+fn scaled_identity(expr: &TypedExpr) -> Option<f64> {
+    let TypedExprKind::Matrix(data) = &expr.expr else {
+        return None;
+    };
+    let n = data.len();
+    if n == 0 || data.iter().any(|row| row.len() != n) {
+        return None;
+    }
+
+    let c = data[0][0];
+    let is_scaled_identity = data.iter().enumerate().all(|(i, row)| {
+        row.iter().enumerate().all(|(j, &v)| if i == j { v == c } else { v == 0.0 })
+    });
+    is_scaled_identity.then_some(c)
+}
+
+/// Multiply an already-optimized expression by a constant
+fn scale(expr: TypedExpr, s: f64) -> TypedExpr {
+    if s == 1.0 {
+        return expr;
+    }
+
+    let shape = expr.shape.clone();
+    match expr.expr {
+        TypedExprKind::Scalar(v) => TypedExpr { expr: TypedExprKind::Scalar(s * v), shape },
+        TypedExprKind::Matrix(data) => {
+            let data = data.into_iter()
+                .map(|row| row.into_iter().map(|v| s * v).collect())
+                .collect();
+            TypedExpr { expr: TypedExprKind::Matrix(data), shape }
+        }
+        TypedExprKind::ScalarMul(inner_scalar, inner) if scalar_value(&inner_scalar).is_some() => {
+            let combined = s * scalar_value(&inner_scalar).unwrap_or(1.0);
+            scale(*inner, combined)
+        }
+        kind => TypedExpr {
+            expr: TypedExprKind::ScalarMul(
+                Box::new(TypedExpr { expr: TypedExprKind::Scalar(s), shape: Shape::Scalar }),
+                Box::new(TypedExpr { expr: kind, shape: shape.clone() }),
+            ),
+            shape,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+    use crate::hardware::SystolicConfig;
+    use crate::parser::Parser;
+
+    fn optimized(source: &str, shapes: &[(&str, (usize, usize))]) -> TypedExpr {
+        let program = Parser::new(source).parse_program().unwrap();
+        let mut analyzer = Analyzer::new();
+        for (name, shape) in shapes {
+            analyzer.define_matrix(name, *shape);
+        }
+        let typed = analyzer.analyze(program).unwrap();
+        optimize(typed).statements.pop().unwrap().value
+    }
+
+    #[test]
+    fn test_scaled_identity_becomes_scalar_mul() {
+        let expr = optimized("C = A @ (3 * np.eye(3))", &[("A", (2, 3))]);
+        match expr.expr {
+            TypedExprKind::ScalarMul(scalar, inner) => {
+                assert_eq!(scalar.expr, TypedExprKind::Scalar(3.0));
+                assert_eq!(inner.expr, TypedExprKind::Variable("A".to_string()));
+            }
+            other => panic!("expected a scalar multiply, got {:?}", other),
+        }
+        assert_eq!(expr.shape, Shape::matrix(2, 3));
+
+        let config = SystolicConfig::new(3, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ (3 * np.eye(3))", &[("A", (3, 3))], &config).unwrap();
+        assert!(program.passes.is_empty());
+    }
+
+    #[test]
+    fn test_plain_identity_and_nested_scales() {
+        let expr = optimized("C = np.eye(2) @ A", &[("A", (2, 2))]);
+        assert_eq!(expr.expr, TypedExprKind::Variable("A".to_string()));
+
+        let expr = optimized("C = 2 * (A @ (0.5 * np.eye(2)))", &[("A", (2, 2))]);
+        assert_eq!(expr.expr, TypedExprKind::Variable("A".to_string()));
+    }
+
+    #[test]
+    fn test_rewrites_are_logged() {
+        let program = Parser::new("C = 2 * (3 * A) + [[1, 2]] * 2").parse_program().unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.define_matrix("A", (1, 2));
+        let (_, rewrites) = optimize_logged(analyzer.analyze(program).unwrap());
+        
+        let kinds: Vec<_> = rewrites.iter().map(|rewrite| rewrite.kind).collect();
+        // `3 * A` parses as an element-wise multiply
+        let expected = [RewriteKind::ScalarMulCanonicalization, RewriteKind::ScaleCollapse, RewriteKind::ConstantFolding];
+        assert_eq!(kinds, expected);
+        assert_eq!(rewrites[2].after.expr, TypedExprKind::Matrix(vec![vec![2.0, 4.0]]));
+    }
+
+    #[test]
+    fn test_non_identity_literal_kept() {
+        let expr = optimized("C = A @ [[1, 1], [0, 1]]", &[("A", (2, 2))]);
+        assert!(matches!(expr.expr, TypedExprKind::MatMul(..)));
+    }
+}
@@ -6,30 +6,59 @@
 //! - `E = np.transpose(A) @ B`
 //! - `F = [[1, 2], [3, 4]] @ G`
 
-use crate::ast::{Expr, MatrixLiteral, Program, Statement};
+use std::ops::Range;
+
+use crate::ast::{Expr, KeywordArgs, MatrixLiteral, Program, Statement, Subscript};
 use crate::error::{CompileError, CompileResult};
-use crate::lexer::{Lexer, Token};
+use crate::lexer::{LexError, Lexer, Token};
 
 /// Parser for NumPy expressions
 pub struct Parser<'source> {
     lexer: Lexer<'source>,
     current: Option<Token>,
+    /// Byte range of `current`; empty at the end of the source once input runs out
+    span: Range<usize>,
+    /// First lexer error; input ends there, and it is reported over any parse error
+    lex_error: Option<CompileError>,
 }
 
 impl<'source> Parser<'source> {
     pub fn new(source: &'source str) -> Self {
-        let mut lexer = Lexer::new(source);
-        let current = lexer.next().and_then(Result::ok);
-        Self { lexer, current }
+        let mut parser = Self { lexer: Lexer::new(source), current: None, span: 0..0, lex_error: None };
+        parser.current = parser.next_token();
+        parser
     }
     
     /// Advance to the next token
     fn advance(&mut self) -> Option<Token> {
         let prev = self.current.take();
-        self.current = self.lexer.next().and_then(Result::ok);
+        self.current = self.next_token();
         prev
     }
     
+    /// Lex the next token; a lexer error ends the input
+    fn next_token(&mut self) -> Option<Token> {
+        let Some(next) = self.lexer.next() else {
+            let end = self.lexer.source().len();
+            self.span = end..end;
+            return None;
+        };
+        // Taken before any peek moves the lexer on
+        self.span = self.lexer.span();
+        match next {
+            Ok(token) => Some(token),
+            Err(err) => {
+                let text = self.lexer.slice();
+                let message = match err {
+                    LexError::UnexpectedCharacter => format!("Unexpected character '{}'", text),
+                    LexError::MalformedNumber => format!("Malformed number literal '{}'", text),
+                };
+                self.lex_error.get_or_insert(CompileError::parse_error(message).with_span(self.span.clone()));
+                None
+            }
+        }
+    }
+    
     /// Check if current token matches expected
     fn check(&self, expected: &Token) -> bool {
         match &self.current {
@@ -44,14 +73,33 @@ impl<'source> Parser<'source> {
             Ok(self.advance().unwrap())
         } else {
             Err(CompileError::parse_error(format!(
-                "Expected {:?}, got {:?}",
-                expected, self.current
+                "Expected '{}', got {}",
+                expected, self.describe_current()
             )))
         }
     }
     
+    /// Describe the current token for error messages
+    fn describe_current(&self) -> String {
+        match &self.current {
+            Some(tok) => format!("'{}'", tok),
+            None => "end of input".to_string(),
+        }
+    }
+    
     /// Parse a complete program (multiple statements)
+    ///
+    /// Errors point at the token the parser stopped on (see `CompileError::render`).
     pub fn parse_program(&mut self) -> CompileResult<Program> {
+        let program = self.parse_statements();
+        match (self.lex_error.take(), program) {
+            (Some(err), _) => Err(err),
+            (None, Err(err)) if err.span().is_none() => Err(err.with_span(self.span.clone())),
+            (None, program) => program,
+        }
+    }
+    
+    fn parse_statements(&mut self) -> CompileResult<Program> {
         let mut statements = Vec::new();
         
         while self.current.is_some() {
@@ -68,19 +116,21 @@ impl<'source> Parser<'source> {
     
     /// Parse a single statement
     fn parse_statement(&mut self) -> CompileResult<Statement> {
+        // `def name(params) = expr`; a variable named `def` is still assignable
+        if matches!(&self.current, Some(Token::Ident(kw)) if kw == "def")
+            && matches!(self.lexer.peek(), Some(Ok(Token::Ident(_))))
+        {
+            return self.parse_def();
+        }
+        
         // Check for assignment: identifier = expr
         if let Some(Token::Ident(name)) = &self.current {
-            let name = name.clone();
-            self.advance();
-            
-            if self.check(&Token::Equals) {
+            if matches!(self.lexer.peek(), Some(Ok(Token::Equals))) {
+                let name = name.clone();
+                self.advance();
                 self.advance();
                 let value = self.parse_expr()?;
                 return Ok(Statement::Assignment { target: name, value });
-            } else {
-                // Not an assignment, put the identifier back as an expression
-                let expr = self.parse_expr_with_prefix(Expr::Variable(name))?;
-                return Ok(Statement::Expression(expr));
             }
         }
         
@@ -89,15 +139,55 @@ impl<'source> Parser<'source> {
         Ok(Statement::Expression(expr))
     }
     
+    /// Parse a function template: `def ffn(x) = relu(x @ W1) @ W2`
+    fn parse_def(&mut self) -> CompileResult<Statement> {
+        self.advance();
+        let Some(Token::Ident(name)) = self.advance() else {
+            return Err(CompileError::parse_error("Expected a function name after 'def'"));
+        };
+        self.expect(Token::LParen)?;
+        let mut params = Vec::new();
+        while !self.check(&Token::RParen) {
+            match self.advance() {
+                Some(Token::Ident(param)) => params.push(param),
+                _ => return Err(CompileError::parse_error(format!("Expected a parameter name in 'def {}'", name))),
+            }
+            if !self.check(&Token::RParen) {
+                self.expect(Token::Comma)?;
+            }
+        }
+        self.expect(Token::RParen)?;
+        self.expect(Token::Equals)?;
+        let body = self.parse_expr()?;
+        Ok(Statement::Def { name, params, body })
+    }
+    
     /// Parse an expression (handles operator precedence)
     pub fn parse_expr(&mut self) -> CompileResult<Expr> {
+        if matches!(&self.current, Some(Token::Ident(kw)) if kw == "let") {
+            return self.parse_let();
+        }
         self.parse_additive()
     }
     
-    /// Continue parsing an expression with a prefix already parsed
-    fn parse_expr_with_prefix(&mut self, prefix: Expr) -> CompileResult<Expr> {
-        let prefix = self.parse_postfix_with_prefix(prefix)?;
-        self.parse_matmul_with_prefix(prefix)
+    /// Parse a scoped temporary: `let t = expr in expr`
+    fn parse_let(&mut self) -> CompileResult<Expr> {
+        self.advance();
+        let name = match self.advance() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err(CompileError::parse_error("Expected a name after 'let'")),
+        };
+        self.expect(Token::Equals)?;
+        let value = self.parse_expr()?;
+        if !matches!(&self.current, Some(Token::Ident(kw)) if kw == "in") {
+            return Err(CompileError::parse_error(format!(
+                "Expected 'in' after let binding, got {}",
+                self.describe_current()
+            )));
+        }
+        self.advance();
+        let body = self.parse_expr()?;
+        Ok(Expr::Let { name, value: Box::new(value), body: Box::new(body) })
     }
     
     /// Parse additive expressions: a + b, a - b
@@ -130,6 +220,10 @@ impl<'source> Parser<'source> {
                 self.advance();
                 let right = self.parse_matmul()?;
                 left = Expr::Mul(Box::new(left), Box::new(right));
+            } else if self.check(&Token::Slash) {
+                self.advance();
+                let right = self.parse_matmul()?;
+                left = Expr::Div(Box::new(left), Box::new(right));
             } else {
                 break;
             }
@@ -140,14 +234,17 @@ impl<'source> Parser<'source> {
     
     /// Parse matrix multiplication: a @ b
     fn parse_matmul(&mut self) -> CompileResult<Expr> {
+        let parenthesized = self.check(&Token::LParen);
         let left = self.parse_unary()?;
+        let left = if self.check(&Token::MatMul) { paren_matmul(left, parenthesized) } else { left };
         self.parse_matmul_with_prefix(left)
     }
     
     fn parse_matmul_with_prefix(&mut self, mut left: Expr) -> CompileResult<Expr> {
         while self.check(&Token::MatMul) {
             self.advance();
-            let right = self.parse_unary()?;
+            let parenthesized = self.check(&Token::LParen);
+            let right = paren_matmul(self.parse_unary()?, parenthesized);
             left = Expr::MatMul(Box::new(left), Box::new(right));
         }
         Ok(left)
@@ -158,13 +255,36 @@ impl<'source> Parser<'source> {
         if self.check(&Token::Minus) {
             self.advance();
             let operand = self.parse_unary()?;
-            Ok(Expr::ScalarMul(Box::new(Expr::Scalar(-1.0)), Box::new(operand)))
+            Ok(negate(operand))
         } else {
-            self.parse_postfix()
+            self.parse_power()
+        }
+    }
+    
+    /// Parse powers: a ** 3, right-associative and binding tighter than unary minus
+    fn parse_power(&mut self) -> CompileResult<Expr> {
+        let base = self.parse_postfix()?;
+        if !self.check(&Token::Power) {
+            return Ok(base);
         }
+        self.advance();
+        let exponent = match self.parse_unary()? {
+            Expr::Scalar(n) if n >= 0.0 && n.fract() == 0.0 && n <= u32::MAX as f64 => n as u32,
+            Expr::Scalar(n) => {
+                return Err(CompileError::parse_error(format!(
+                    "Exponent of '**' must be a non-negative integer, got {}",
+                    n
+                )));
+            }
+            _ => return Err(CompileError::parse_error("Exponent of '**' must be an integer literal")),
+        };
+        Ok(match base {
+            Expr::Scalar(n) => Expr::Scalar(n.powf(exponent as f64)),
+            base => Expr::MatPow(Box::new(base), exponent),
+        })
     }
     
-    /// Parse postfix expressions: a.T, a.method()
+    /// Parse postfix expressions: a.T, a.method(), a[i]
     fn parse_postfix(&mut self) -> CompileResult<Expr> {
         let primary = self.parse_primary()?;
         self.parse_postfix_with_prefix(primary)
@@ -184,10 +304,11 @@ impl<'source> Parser<'source> {
                         expr = Expr::Transpose(Box::new(expr));
                     } else if self.check(&Token::LParen) {
                         // Method call
-                        let args = self.parse_args()?;
+                        let (args, kwargs) = self.parse_args()?;
                         expr = Expr::FunctionCall {
                             name: format!(".{}", name),
                             args: std::iter::once(expr).chain(args).collect(),
+                            kwargs,
                         };
                     } else {
                         return Err(CompileError::parse_error(format!(
@@ -198,6 +319,9 @@ impl<'source> Parser<'source> {
                 } else {
                     return Err(CompileError::parse_error("Expected identifier after '.'"));
                 }
+            } else if self.check(&Token::LBracket) {
+                let subscripts = self.parse_subscripts()?;
+                expr = Expr::Index(Box::new(expr), subscripts);
             } else {
                 break;
             }
@@ -206,11 +330,70 @@ impl<'source> Parser<'source> {
         Ok(expr)
     }
     
+    /// Parse a subscript list: [i], [:, j], [1:3, :]
+    fn parse_subscripts(&mut self) -> CompileResult<Vec<Subscript>> {
+        self.expect(Token::LBracket)?;
+        
+        let mut subscripts = vec![self.parse_subscript()?];
+        while self.check(&Token::Comma) {
+            self.advance();
+            subscripts.push(self.parse_subscript()?);
+        }
+        
+        self.expect(Token::RBracket)?;
+        Ok(subscripts)
+    }
+    
+    /// Parse a single subscript: an integer index or a `start:stop[:step]` slice
+    fn parse_subscript(&mut self) -> CompileResult<Subscript> {
+        let start = self.parse_subscript_bound()?;
+        
+        if self.check(&Token::Colon) {
+            self.advance();
+            let stop = self.parse_subscript_bound()?;
+            let step = if self.check(&Token::Colon) {
+                self.advance();
+                self.parse_subscript_bound()?
+            } else {
+                None
+            };
+            Ok(Subscript::Slice { start, stop, step })
+        } else {
+            start
+                .map(Subscript::Index)
+                .ok_or_else(|| CompileError::parse_error("Expected index or ':' in subscript"))
+        }
+    }
+    
+    /// Parse an optional (possibly negative) integer inside a subscript
+    fn parse_subscript_bound(&mut self) -> CompileResult<Option<i64>> {
+        let negate = self.check(&Token::Minus);
+        if negate {
+            self.advance();
+        }
+        
+        match &self.current {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                if n.fract() != 0.0 {
+                    return Err(CompileError::parse_error(format!(
+                        "Subscript must be an integer, got {}",
+                        n
+                    )));
+                }
+                self.advance();
+                Ok(Some(if negate { -(n as i64) } else { n as i64 }))
+            }
+            _ if negate => Err(CompileError::parse_error("Expected number after '-'")),
+            _ => Ok(None),
+        }
+    }
+    
     /// Parse primary expressions: literals, variables, parenthesized, function calls
     fn parse_primary(&mut self) -> CompileResult<Expr> {
         match &self.current {
             Some(Token::Number(n)) => {
-                let n = *n;
+                let n = finite(*n)?;
                 self.advance();
                 Ok(Expr::Scalar(n))
             }
@@ -219,28 +402,33 @@ impl<'source> Parser<'source> {
                 let name = name.clone();
                 self.advance();
                 
-                // Check for numpy function: np.func()
+                // Check for numpy function: np.func() or np.module.func()
                 if name == "np" && self.check(&Token::Dot) {
-                    self.advance();
-                    if let Some(Token::Ident(func_name)) = &self.current {
-                        let func_name = func_name.clone();
+                    let mut path = name;
+                    while self.check(&Token::Dot) {
+                        self.advance();
+                        let Some(Token::Ident(part)) = &self.current else {
+                            return Err(CompileError::parse_error(format!("Expected function name after '{}.'", path)));
+                        };
+                        path = format!("{}.{}", path, part);
                         self.advance();
-                        let args = self.parse_args()?;
-                        return Ok(Expr::FunctionCall {
-                            name: format!("np.{}", func_name),
-                            args,
-                        });
-                    } else {
-                        return Err(CompileError::parse_error("Expected function name after 'np.'"));
                     }
+                    if is_non_finite_name(&path) && !self.check(&Token::LParen) {
+                        return Err(non_finite_error(&path));
+                    }
+                    let (args, kwargs) = self.parse_args()?;
+                    return Ok(Expr::FunctionCall { name: path, args, kwargs });
                 }
                 
                 // Check for function call: name()
                 if self.check(&Token::LParen) {
-                    let args = self.parse_args()?;
-                    return Ok(Expr::FunctionCall { name, args });
+                    let (args, kwargs) = self.parse_args()?;
+                    return Ok(Expr::FunctionCall { name, args, kwargs });
                 }
                 
+                if is_non_finite_name(&name) {
+                    return Err(non_finite_error(&name));
+                }
                 Ok(Expr::Variable(name))
             }
             
@@ -275,33 +463,59 @@ impl<'source> Parser<'source> {
             
             None => Err(CompileError::parse_error("Unexpected end of input")),
             
-            other => Err(CompileError::parse_error(format!(
-                "Unexpected token: {:?}",
-                other
+            Some(_) => Err(CompileError::parse_error(format!(
+                "Unexpected token: {}",
+                self.describe_current()
             ))),
         }
     }
     
-    /// Parse function arguments: (arg1, arg2, ...)
-    fn parse_args(&mut self) -> CompileResult<Vec<Expr>> {
+    /// Parse function arguments: (arg1, arg2, ..., key=value, ...)
+    fn parse_args(&mut self) -> CompileResult<(Vec<Expr>, KeywordArgs)> {
         self.expect(Token::LParen)?;
         
         let mut args = Vec::new();
+        let mut kwargs = Vec::new();
         
         if !self.check(&Token::RParen) {
-            args.push(self.parse_expr()?);
+            self.parse_arg(&mut args, &mut kwargs)?;
             
             while self.check(&Token::Comma) {
                 self.advance();
                 if self.check(&Token::RParen) {
                     break; // Trailing comma
                 }
-                args.push(self.parse_expr()?);
+                self.parse_arg(&mut args, &mut kwargs)?;
             }
         }
         
         self.expect(Token::RParen)?;
-        Ok(args)
+        Ok((args, kwargs))
+    }
+    
+    /// Parse one positional or keyword argument
+    fn parse_arg(
+        &mut self,
+        args: &mut Vec<Expr>,
+        kwargs: &mut KeywordArgs,
+    ) -> CompileResult<()> {
+        if let Some(Token::Ident(name)) = &self.current {
+            if matches!(self.lexer.peek(), Some(Ok(Token::Equals))) {
+                let name = name.clone();
+                self.advance();
+                self.advance();
+                kwargs.push((name, self.parse_expr()?));
+                return Ok(());
+            }
+        }
+        
+        if !kwargs.is_empty() {
+            return Err(CompileError::parse_error(
+                "Positional argument follows keyword argument"
+            ));
+        }
+        args.push(self.parse_expr()?);
+        Ok(())
     }
     
     /// Parse a matrix literal: [[1, 2], [3, 4]]
@@ -316,9 +530,14 @@ impl<'source> Parser<'source> {
             while !self.check(&Token::RBracket) {
                 rows.push(self.parse_row()?);
                 
-                if self.check(&Token::Comma) {
-                    self.advance();
+                // Without a comma the next token must close the matrix, as in Python
+                if !self.check(&Token::Comma) {
+                    if self.check(&Token::LBracket) {
+                        return Err(CompileError::parse_error("Expected ',' between matrix rows"));
+                    }
+                    break;
                 }
+                self.advance();
             }
             
             self.expect(Token::RBracket)?;
@@ -338,10 +557,10 @@ impl<'source> Parser<'source> {
             
             Ok(Expr::Matrix(MatrixLiteral::new(rows)))
         } else {
-            // 1D array - treat as row vector
-            let row = self.parse_number_list()?;
+            // 1D array, like NumPy's shape (n,)
+            let values = self.parse_number_list()?;
             self.expect(Token::RBracket)?;
-            Ok(Expr::Matrix(MatrixLiteral::new(vec![row])))
+            Ok(Expr::Vector(values))
         }
     }
     
@@ -358,46 +577,78 @@ impl<'source> Parser<'source> {
         let mut values = Vec::new();
         
         if !self.check(&Token::RBracket) {
-            if let Some(Token::Number(n)) = &self.current {
-                values.push(*n);
-                self.advance();
-            } else if self.check(&Token::Minus) {
-                self.advance();
-                if let Some(Token::Number(n)) = &self.current {
-                    values.push(-*n);
-                    self.advance();
-                } else {
-                    return Err(CompileError::parse_error("Expected number after '-'"));
-                }
-            } else {
-                return Err(CompileError::parse_error("Expected number in matrix literal"));
-            }
+            values.push(self.parse_literal_number()?);
             
             while self.check(&Token::Comma) {
                 self.advance();
                 if self.check(&Token::RBracket) {
                     break; // Trailing comma
                 }
-                
-                if let Some(Token::Number(n)) = &self.current {
-                    values.push(*n);
-                    self.advance();
-                } else if self.check(&Token::Minus) {
-                    self.advance();
-                    if let Some(Token::Number(n)) = &self.current {
-                        values.push(-*n);
-                        self.advance();
-                    } else {
-                        return Err(CompileError::parse_error("Expected number after '-'"));
-                    }
-                } else {
-                    return Err(CompileError::parse_error("Expected number in matrix literal"));
-                }
+                values.push(self.parse_literal_number()?);
             }
         }
         
         Ok(values)
     }
+    
+    /// Parse one optionally negated element of a matrix literal
+    fn parse_literal_number(&mut self) -> CompileResult<f64> {
+        let negate = self.check(&Token::Minus);
+        if negate {
+            self.advance();
+        }
+        
+        match &self.current {
+            Some(Token::Number(n)) => {
+                let n = finite(*n)?;
+                self.advance();
+                Ok(if negate { -n } else { n })
+            }
+            Some(Token::Ident(name)) if is_non_finite_name(name) => Err(non_finite_error(name)),
+            _ if negate => Err(CompileError::parse_error("Expected number after '-'")),
+            _ => Err(CompileError::parse_error("Expected number in matrix literal")),
+        }
+    }
+}
+
+/// Mark an `@` operand that was a parenthesized matmul, so its grouping stays fixed
+fn paren_matmul(operand: Expr, parenthesized: bool) -> Expr {
+    match operand {
+        Expr::MatMul(..) if parenthesized => Expr::Paren(Box::new(operand)),
+        operand => operand,
+    }
+}
+
+/// Negate `expr`, cancelling a negation it already carries so `--A` and `-(-A)` are `A`
+fn negate(expr: Expr) -> Expr {
+    match expr {
+        Expr::ScalarMul(scalar, inner) if *scalar == Expr::Scalar(-1.0) => *inner,
+        other => Expr::ScalarMul(Box::new(Expr::Scalar(-1.0)), Box::new(other)),
+    }
+}
+
+/// Reject literals too large for `f64`, which would quantize to a silently clamped maximum
+fn finite(n: f64) -> CompileResult<f64> {
+    if n.is_finite() {
+        Ok(n)
+    } else {
+        Err(CompileError::parse_error(format!(
+            "Number literal overflows to {}; matrix data must be finite",
+            n
+        )))
+    }
+}
+
+/// Spellings of infinity and NaN, as bare names or `np.` constants
+fn is_non_finite_name(name: &str) -> bool {
+    matches!(
+        name.strip_prefix("np.").unwrap_or(name).to_ascii_lowercase().as_str(),
+        "inf" | "infty" | "infinity" | "nan"
+    )
+}
+
+fn non_finite_error(name: &str) -> CompileError {
+    CompileError::parse_error(format!("Non-finite value '{}' is not supported; matrix data must be finite", name))
 }
 
 #[cfg(test)]
@@ -416,6 +667,18 @@ mod tests {
         } else {
             panic!("Expected MatMul expression");
         }
+        
+        // Only a parenthesized product is marked; `(A) @ B` and `(A + B) @ C` are plain operands
+        let program = Parser::new("A @ (B @ C); (A @ B) @ C; (A) @ B").parse_program().unwrap();
+        let exprs: Vec<_> = program.statements.iter()
+            .map(|stmt| match stmt {
+                Statement::Expression(Expr::MatMul(left, right)) => (left.as_ref(), right.as_ref()),
+                _ => panic!("Expected MatMul expression"),
+            })
+            .collect();
+        assert!(matches!(exprs[0].1, Expr::Paren(_)));
+        assert!(matches!(exprs[1].0, Expr::Paren(_)));
+        assert!(matches!(exprs[2], (Expr::Variable(_), Expr::Variable(_))));
     }
     
     #[test]
@@ -430,6 +693,14 @@ mod tests {
         } else {
             panic!("Expected Assignment");
         }
+        
+        // Submodule functions keep their full dotted path
+        let program = Parser::new("np.linalg.norm(A, axis=1)").parse_program().unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            Statement::Expression(Expr::FunctionCall { name, .. }) if name == "np.linalg.norm"
+        ));
+        assert!(Parser::new("np.linalg.(A)").parse_program().is_err());
     }
     
     #[test]
@@ -443,6 +714,29 @@ mod tests {
         } else {
             panic!("Expected Matrix literal");
         }
+        
+        let program = Parser::new("[1, 2, 3]").parse_program().unwrap();
+        assert_eq!(program.statements[0], Statement::Expression(Expr::Vector(vec![1.0, 2.0, 3.0])));
+    }
+    
+    #[test]
+    fn test_parse_power() {
+        let parse = |source: &str| Parser::new(source).parse_program().map(|program| program.statements[0].clone());
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        
+        // Right-associative, tighter than `*` and `@`, with constant exponents folded
+        assert_eq!(
+            parse("A ** 2 ** 3 @ B").unwrap(),
+            Statement::Expression(Expr::MatMul(Box::new(Expr::MatPow(var("A"), 8)), var("B")))
+        );
+        assert_eq!(
+            parse("2 * A.T ** 2").unwrap(),
+            Statement::Expression(Expr::Mul(Box::new(Expr::Scalar(2.0)), Box::new(Expr::MatPow(Box::new(Expr::Transpose(var("A"))), 2))))
+        );
+        
+        for bad in ["A ** -1", "A ** 1.5", "A ** B"] {
+            assert!(parse(bad).unwrap_err().to_string().contains("Exponent of '**'"), "{}", bad);
+        }
     }
     
     #[test]
@@ -458,6 +752,32 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_parse_index() {
+        let mut parser = Parser::new("A[0]; A[:, -1]; A[::-1, 1:3:2]");
+        let program = parser.parse_program().unwrap();
+        
+        assert_eq!(program.statements.len(), 3);
+        if let Statement::Expression(Expr::Index(_, subscripts)) = &program.statements[0] {
+            assert_eq!(subscripts, &vec![Subscript::Index(0)]);
+        } else {
+            panic!("Expected Index expression");
+        }
+        if let Statement::Expression(Expr::Index(_, subscripts)) = &program.statements[1] {
+            assert_eq!(subscripts, &vec![Subscript::full(), Subscript::Index(-1)]);
+        } else {
+            panic!("Expected Index expression");
+        }
+        if let Statement::Expression(Expr::Index(_, subscripts)) = &program.statements[2] {
+            assert_eq!(subscripts, &vec![
+                Subscript::Slice { start: None, stop: None, step: Some(-1) },
+                Subscript::Slice { start: Some(1), stop: Some(3), step: Some(2) },
+            ]);
+        } else {
+            panic!("Expected Index expression");
+        }
+    }
+    
     #[test]
     fn test_parse_complex_expr() {
         let mut parser = Parser::new("C = A @ B + D @ E.T");
@@ -472,6 +792,61 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_parse_division() {
+        let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let program = Parser::new("A / B; A / 2 * B; A @ B / 2").parse_program().unwrap();
+        
+        // `/` binds like `*`, left to right, and looser than `@`
+        assert_eq!(program.statements[0], Statement::Expression(Expr::Div(var("A"), var("B"))));
+        assert_eq!(
+            program.statements[1],
+            Statement::Expression(Expr::Mul(Box::new(Expr::Div(var("A"), Box::new(Expr::Scalar(2.0)))), var("B")))
+        );
+        assert_eq!(
+            program.statements[2],
+            Statement::Expression(Expr::Div(Box::new(Expr::MatMul(var("A"), var("B"))), Box::new(Expr::Scalar(2.0))))
+        );
+    }
+    
+    #[test]
+    fn test_parse_error_messages_are_readable() {
+        let err = Parser::new("@ A").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Unexpected token: '@'");
+        
+        let err = Parser::new("A =").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Unexpected end of input");
+        
+        let err = Parser::new("(A @ B").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Expected ')', got end of input");
+    }
+    
+    #[test]
+    fn test_parse_keyword_argument() {
+        let mut parser = Parser::new("np.matmul(A, B, out=C)");
+        let program = parser.parse_program().unwrap();
+        
+        assert_eq!(program.statements.len(), 1);
+        if let Statement::Expression(Expr::FunctionCall { name, args, kwargs }) = &program.statements[0] {
+            assert_eq!(name, "np.matmul");
+            assert_eq!(args.len(), 2);
+            assert_eq!(kwargs, &vec![("out".to_string(), Expr::Variable("C".to_string()))]);
+        } else {
+            panic!("Expected FunctionCall");
+        }
+        
+        assert!(Parser::new("np.matmul(A, out=C, B)").parse_program().is_err());
+    }
+    
+    #[test]
+    fn test_parse_expression_statement_with_leading_ident() {
+        let mut parser = Parser::new("A + B");
+        let program = parser.parse_program().unwrap();
+        
+        assert_eq!(program.statements.len(), 1);
+        assert!(matches!(&program.statements[0], Statement::Expression(Expr::Add(_, _))));
+    }
+    
     #[test]
     fn test_parse_numpy_function() {
         let mut parser = Parser::new("A = np.zeros((3, 4))");
@@ -480,9 +855,10 @@ mod tests {
         assert_eq!(program.statements.len(), 1);
         if let Statement::Assignment { target, value } = &program.statements[0] {
             assert_eq!(target, "A");
-            if let Expr::FunctionCall { name, args } = value {
+            if let Expr::FunctionCall { name, args, kwargs } = value {
                 assert_eq!(name, "np.zeros");
                 assert_eq!(args.len(), 1);
+                assert!(kwargs.is_empty());
             } else {
                 panic!("Expected FunctionCall");
             }
@@ -490,4 +866,111 @@ mod tests {
             panic!("Expected Assignment");
         }
     }
+    
+    #[test]
+    fn test_parse_repeated_negation() {
+        let parse = |source: &str| match Parser::new(source).parse_program().unwrap().statements.remove(0) {
+            Statement::Expression(expr) => expr,
+            other => panic!("Expected expression statement, got {:?}", other),
+        };
+        let a = Expr::Variable("A".to_string());
+        let neg = |e: Expr| Expr::ScalarMul(Box::new(Expr::Scalar(-1.0)), Box::new(e));
+        
+        assert_eq!(parse("--A"), a);
+        assert_eq!(parse("---A"), neg(a.clone()));
+        assert_eq!(parse("-(-A)"), a);
+        assert_eq!(parse("-(--A)"), neg(a.clone()));
+        
+        let product = Expr::MatMul(Box::new(a), Box::new(Expr::Variable("B".to_string())));
+        assert_eq!(parse("-(A @ B)"), neg(product.clone()));
+        assert_eq!(parse("--(A @ B)"), product);
+    }
+    
+    #[test]
+    fn test_parse_let() {
+        let program = Parser::new("C = let t = A @ B in t + t").parse_program().unwrap();
+        let Statement::Assignment { value: Expr::Let { name, value, body }, .. } = &program.statements[0] else {
+            panic!("Expected let binding");
+        };
+        assert_eq!(name, "t");
+        assert!(matches!(value.as_ref(), Expr::MatMul(..)));
+        assert!(matches!(body.as_ref(), Expr::Add(..)));
+        
+        let err = Parser::new("C = let t = A @ B t + t").parse_program().unwrap_err();
+        assert!(err.to_string().contains("Expected 'in'"), "{}", err);
+    }
+    
+    #[test]
+    fn test_parse_def() {
+        let program = Parser::new("def ffn(x, w) = relu(x @ w) @ W2; def = 1").parse_program().unwrap();
+        let Statement::Def { name, params, body } = &program.statements[0] else {
+            panic!("Expected def, got {:?}", program.statements[0]);
+        };
+        assert_eq!(name, "ffn");
+        assert_eq!(params, &["x", "w"]);
+        assert!(matches!(body, Expr::MatMul(..)));
+        // `def` alone is still an ordinary variable name
+        assert!(matches!(&program.statements[1], Statement::Assignment { target, .. } if target == "def"));
+        
+        assert!(Parser::new("def f(x y) = x").parse_program().is_err());
+    }
+    
+    #[test]
+    fn test_non_finite_literals_rejected() {
+        let err = Parser::new("A = [[1, 1e999]]").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Number literal overflows to inf; matrix data must be finite");
+        assert!(Parser::new("C = -1e999 * A").parse_program().is_err());
+        
+        for source in ["A = [[1, inf]]", "A = [[-inf, 0]]", "C = np.nan * A", "C = A + NaN"] {
+            let err = Parser::new(source).parse_program().unwrap_err();
+            assert!(err.to_string().contains("is not supported; matrix data must be finite"), "{}: {}", source, err);
+        }
+        assert!(Parser::new("A = [[1, 1e300]]").parse_program().is_ok());
+    }
+    
+    #[test]
+    fn test_radix_literals_and_lexer_errors() {
+        let program = Parser::new("W = [[0xFF, 0b10], [1_000, -0x1]]").parse_program().unwrap();
+        assert_eq!(program.statements[0], Statement::Assignment {
+            target: "W".to_string(),
+            value: Expr::Matrix(MatrixLiteral::new(vec![vec![255.0, 2.0], vec![1000.0, -1.0]])),
+        });
+        
+        let err = Parser::new("C = 0x @ B").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Malformed number literal '0x'");
+        // Input after a bad character is no longer silently dropped
+        let err = Parser::new("C = A @ B $ D").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Unexpected character '$'");
+        assert_eq!(err.span(), Some(10..11));
+    }
+    
+    #[test]
+    fn test_error_spans() {
+        let source = "[[1, 2], [3, 4]";
+        let err = Parser::new(source).parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Expected ']', got end of input");
+        assert_eq!(err.span(), Some(15..15));
+        assert_eq!(err.render(source), [
+            "Parser error: Expected ']', got end of input",
+            " --> line 1, column 16",
+            "  |",
+            "1 | [[1, 2], [3, 4]",
+            "  |                ^",
+        ].join("\n"));
+        
+        // The caret covers the whole offending token, on its own line
+        let source = "A = [[1]]\nC = A @ B)";
+        let err = Parser::new(source).parse_program().unwrap_err();
+        assert_eq!(err.span(), Some(19..20));
+        assert!(err.render(source).ends_with("2 | C = A @ B)\n  |          ^"), "{}", err.render(source));
+        
+        // Rows need a comma between them
+        let err = Parser::new("[[1, 2] [3, 4]]").parse_program().unwrap_err();
+        assert_eq!(err.to_string(), "Parser error: Expected ',' between matrix rows");
+        assert_eq!(err.span(), Some(8..9));
+        
+        // Errors from later phases have no span and render as plain messages
+        let err = CompileError::type_error("bad");
+        assert_eq!(err.render(source), err.to_string());
+    }
 }
@@ -0,0 +1,539 @@
+//! Compact protobuf encoding of a `HardwareProgram`
+//!
+//! Every field has a fixed field number, so readers in other languages can be
+//! generated from the schema below and old files stay readable as fields are
+//! added. Field numbers must never be reused; append new ones instead.
+//!
+//! ```proto
+//! message Program {
+//!   Config config = 1;
+//!   repeated Pass passes = 2;
+//!   repeated Epilogue epilogues = 3;
+//!   repeated Group groups = 4;
+//!   repeated Buffer inputs = 5;
+//!   Shape output_shape = 6;
+//!   uint64 total_cycles = 7;
+//!   string summary = 8;
+//! }
+//! ```
+//!
+//! The nested messages follow the fields of their Rust counterparts in order.
+//! Enums are encoded as `uint32`, in declaration order starting from 0. A fused
+//! epilogue op is its variant number plus its numbers in `values` (the bias tile,
+//! the scale, or min and max) or its `activation`.
+
+use prost::Message;
+
+use crate::ast::Activation;
+use crate::hardware::{
+    Dataflow, Endianness, EpilogueOp, EpilogueStep, HardwareProgram, MatMulGroup, MemoryLayout, OperandBuffer, PassOperation,
+    RoundingMode, SystolicConfig, SystolicPass, TileCoord, TileOrder, WordPacking,
+};
+
+/// Failure to read a protobuf-encoded program
+#[derive(Debug, thiserror::Error)]
+pub enum ProtoError {
+    #[error("Malformed protobuf: {0}")]
+    Decode(#[from] prost::DecodeError),
+
+    #[error("Invalid program: {0}")]
+    Invalid(String),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProgramProto {
+    #[prost(message, optional, tag = "1")]
+    config: Option<ConfigProto>,
+    #[prost(message, repeated, tag = "2")]
+    passes: Vec<PassProto>,
+    #[prost(message, repeated, tag = "3")]
+    epilogues: Vec<EpilogueProto>,
+    #[prost(message, repeated, tag = "4")]
+    groups: Vec<GroupProto>,
+    #[prost(message, repeated, tag = "5")]
+    inputs: Vec<BufferProto>,
+    #[prost(message, optional, tag = "6")]
+    output_shape: Option<ShapeProto>,
+    #[prost(uint64, tag = "7")]
+    total_cycles: u64,
+    #[prost(string, tag = "8")]
+    summary: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ConfigProto {
+    #[prost(uint64, tag = "1")]
+    array_size: u64,
+    #[prost(uint64, tag = "2")]
+    data_width: u64,
+    #[prost(uint64, tag = "3")]
+    acc_width: u64,
+    #[prost(uint32, tag = "4")]
+    tile_order: u32,
+    #[prost(uint32, tag = "5")]
+    memory_layout: u32,
+    #[prost(bool, tag = "6")]
+    integer_only: bool,
+    #[prost(bool, tag = "7")]
+    fuse_residual: bool,
+    #[prost(sint64, tag = "8")]
+    zero_point: i64,
+    /// 0 means unpacked, so files written before packing existed read as 1
+    #[prost(uint64, tag = "9")]
+    elements_per_word: u64,
+    #[prost(bool, tag = "10")]
+    big_endian: bool,
+    #[prost(uint64, tag = "11")]
+    accumulator_k_tiles: u64,
+    #[prost(bool, tag = "12")]
+    fuse_epilogue: bool,
+    #[prost(uint32, tag = "13")]
+    dataflow: u32,
+    #[prost(bool, tag = "14")]
+    saturate_accumulators: bool,
+    #[prost(bool, tag = "15")]
+    eliminate_dead_statements: bool,
+    #[prost(bool, tag = "16")]
+    full_tile_cycles: bool,
+    #[prost(bool, tag = "17")]
+    reorder_matmul_chains: bool,
+    #[prost(uint32, tag = "18")]
+    rounding: u32,
+    #[prost(uint64, tag = "19")]
+    max_passes: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ShapeProto {
+    #[prost(uint64, tag = "1")]
+    rows: u64,
+    #[prost(uint64, tag = "2")]
+    cols: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TileProto {
+    #[prost(uint64, tag = "1")]
+    tile_row: u64,
+    #[prost(uint64, tag = "2")]
+    tile_col: u64,
+    #[prost(uint64, tag = "3")]
+    start_row: u64,
+    #[prost(uint64, tag = "4")]
+    start_col: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PassProto {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(string, tag = "2")]
+    description: String,
+    #[prost(sint64, repeated, tag = "3")]
+    matrix_a: Vec<i64>,
+    #[prost(message, optional, tag = "4")]
+    a_shape: Option<ShapeProto>,
+    #[prost(sint64, repeated, tag = "5")]
+    matrix_b: Vec<i64>,
+    #[prost(message, optional, tag = "6")]
+    b_shape: Option<ShapeProto>,
+    #[prost(message, optional, tag = "7")]
+    output_shape: Option<ShapeProto>,
+    #[prost(message, optional, tag = "8")]
+    output_tile: Option<TileProto>,
+    #[prost(uint32, tag = "9")]
+    operation: u32,
+    #[prost(uint64, tag = "10")]
+    a_addr: u64,
+    #[prost(uint64, tag = "11")]
+    b_addr: u64,
+    #[prost(double, tag = "12")]
+    a_scale: f64,
+    #[prost(double, tag = "13")]
+    b_scale: f64,
+    #[prost(sint64, repeated, tag = "14")]
+    expected_output: Vec<i64>,
+    #[prost(sint64, repeated, tag = "15")]
+    acc_init: Vec<i64>,
+    #[prost(bool, tag = "16")]
+    spill: bool,
+    #[prost(bool, tag = "17")]
+    reload: bool,
+    #[prost(message, repeated, tag = "18")]
+    epilogue: Vec<EpilogueOpProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EpilogueOpProto {
+    #[prost(uint32, tag = "1")]
+    kind: u32,
+    #[prost(double, repeated, tag = "2")]
+    values: Vec<f64>,
+    #[prost(uint32, tag = "3")]
+    activation: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EpilogueProto {
+    #[prost(string, tag = "1")]
+    target: String,
+    #[prost(string, tag = "2")]
+    source: String,
+    #[prost(uint32, tag = "3")]
+    activation: u32,
+    #[prost(message, optional, tag = "4")]
+    shape: Option<ShapeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GroupProto {
+    #[prost(string, tag = "1")]
+    target: String,
+    #[prost(uint64, tag = "2")]
+    first_pass: u64,
+    #[prost(uint64, tag = "3")]
+    num_passes: u64,
+    #[prost(uint64, repeated, tag = "4")]
+    depends_on: Vec<u64>,
+    #[prost(message, optional, tag = "5")]
+    shape: Option<ShapeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct BufferProto {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(uint64, tag = "2")]
+    addr: u64,
+    #[prost(message, optional, tag = "3")]
+    shape: Option<ShapeProto>,
+}
+
+impl HardwareProgram {
+    /// Encode as a protobuf `Program` message
+    pub fn to_proto(&self) -> Vec<u8> {
+        let config = &self.config;
+        ProgramProto {
+            config: Some(ConfigProto {
+                array_size: config.array_size as u64,
+                data_width: config.data_width as u64,
+                acc_width: config.acc_width as u64,
+                tile_order: match config.tile_order {
+                    TileOrder::RowMajor => 0,
+                    TileOrder::ColMajor => 1,
+                    TileOrder::ZOrder => 2,
+                },
+                memory_layout: match config.memory_layout {
+                    MemoryLayout::Contiguous => 0,
+                    MemoryLayout::Tiled => 1,
+                },
+                integer_only: config.integer_only,
+                fuse_residual: config.fuse_residual,
+                zero_point: config.zero_point,
+                elements_per_word: config.packing.elements_per_word as u64,
+                big_endian: config.packing.endianness == Endianness::Big,
+                accumulator_k_tiles: config.accumulator_k_tiles as u64,
+                fuse_epilogue: config.fuse_epilogue,
+                dataflow: match config.dataflow {
+                    Dataflow::OutputStationary => 0,
+                    Dataflow::WeightStationary => 1,
+                },
+                saturate_accumulators: config.saturate_accumulators,
+                eliminate_dead_statements: config.eliminate_dead_statements,
+                full_tile_cycles: config.full_tile_cycles,
+                reorder_matmul_chains: config.reorder_matmul_chains,
+                rounding: match config.rounding {
+                    RoundingMode::HalfAwayFromZero => 0,
+                    RoundingMode::HalfEven => 1,
+                    RoundingMode::Truncate => 2,
+                },
+                max_passes: config.max_passes as u64,
+            }),
+            passes: self.passes.iter().map(pass_to_proto).collect(),
+            epilogues: self.epilogues.iter()
+                .map(|step| EpilogueProto {
+                    target: step.target.clone(),
+                    source: step.source.clone(),
+                    activation: activation_to_proto(step.activation),
+                    shape: Some(shape_to_proto(step.shape)),
+                })
+                .collect(),
+            groups: self.groups.iter()
+                .map(|group| GroupProto {
+                    target: group.target.clone(),
+                    first_pass: group.first_pass as u64,
+                    num_passes: group.num_passes as u64,
+                    depends_on: group.depends_on.iter().map(|&g| g as u64).collect(),
+                    shape: Some(shape_to_proto(group.shape)),
+                })
+                .collect(),
+            inputs: self.inputs.iter()
+                .map(|buffer| BufferProto {
+                    name: buffer.name.clone(),
+                    addr: buffer.addr as u64,
+                    shape: Some(shape_to_proto(buffer.shape)),
+                })
+                .collect(),
+            output_shape: Some(shape_to_proto(self.output_shape)),
+            total_cycles: self.total_cycles as u64,
+            summary: self.summary.clone(),
+        }
+        .encode_to_vec()
+    }
+
+    /// Decode a program written by `to_proto`
+    pub fn from_proto(bytes: &[u8]) -> Result<Self, ProtoError> {
+        let proto = ProgramProto::decode(bytes)?;
+        let config = proto.config.ok_or_else(|| ProtoError::Invalid("missing config".to_string()))?;
+        let mut system = SystolicConfig::new(
+            config.array_size as usize,
+            config.data_width as usize,
+            config.acc_width as usize,
+        )
+        .with_tile_order(match config.tile_order {
+            0 => TileOrder::RowMajor,
+            1 => TileOrder::ColMajor,
+            2 => TileOrder::ZOrder,
+            other => return Err(invalid_enum("tile order", other)),
+        })
+        .with_memory_layout(match config.memory_layout {
+            0 => MemoryLayout::Contiguous,
+            1 => MemoryLayout::Tiled,
+            other => return Err(invalid_enum("memory layout", other)),
+        })
+        .with_dataflow(match config.dataflow {
+            0 => Dataflow::OutputStationary,
+            1 => Dataflow::WeightStationary,
+            other => return Err(invalid_enum("dataflow", other)),
+        })
+        .with_rounding(match config.rounding {
+            0 => RoundingMode::HalfAwayFromZero,
+            1 => RoundingMode::HalfEven,
+            2 => RoundingMode::Truncate,
+            other => return Err(invalid_enum("rounding", other)),
+        })
+        .with_zero_point(config.zero_point)
+        .with_accumulator_k_tiles(config.accumulator_k_tiles as usize)
+        .with_max_passes(config.max_passes as usize)
+        .with_packing(WordPacking::new(
+            config.elements_per_word.max(1) as usize,
+            if config.big_endian { Endianness::Big } else { Endianness::Little },
+        ));
+        system.integer_only = config.integer_only;
+        system.fuse_residual = config.fuse_residual;
+        system.fuse_epilogue = config.fuse_epilogue;
+        system.saturate_accumulators = config.saturate_accumulators;
+        system.eliminate_dead_statements = config.eliminate_dead_statements;
+        system.full_tile_cycles = config.full_tile_cycles;
+        system.reorder_matmul_chains = config.reorder_matmul_chains;
+
+        let mut program = HardwareProgram::new(system);
+        program.passes = proto.passes.into_iter().map(pass_from_proto).collect::<Result<_, _>>()?;
+        program.epilogues = proto.epilogues.into_iter()
+            .map(|step| {
+                Ok(EpilogueStep {
+                    target: step.target,
+                    source: step.source,
+                    activation: activation_from_proto(step.activation)?,
+                    shape: shape_from_proto(step.shape),
+                })
+            })
+            .collect::<Result<_, ProtoError>>()?;
+        program.groups = proto.groups.into_iter()
+            .map(|group| MatMulGroup {
+                target: group.target,
+                first_pass: group.first_pass as usize,
+                num_passes: group.num_passes as usize,
+                depends_on: group.depends_on.into_iter().map(|g| g as usize).collect(),
+                shape: shape_from_proto(group.shape),
+            })
+            .collect();
+        program.inputs = proto.inputs.into_iter()
+            .map(|buffer| OperandBuffer {
+                name: buffer.name,
+                addr: buffer.addr as usize,
+                shape: shape_from_proto(buffer.shape),
+            })
+            .collect();
+        program.output_shape = shape_from_proto(proto.output_shape);
+        program.total_cycles = proto.total_cycles as usize;
+        program.summary = proto.summary;
+        Ok(program)
+    }
+}
+
+fn pass_to_proto(pass: &SystolicPass) -> PassProto {
+    PassProto {
+        id: pass.id as u64,
+        description: pass.description.clone(),
+        matrix_a: pass.matrix_a.clone(),
+        a_shape: Some(shape_to_proto(pass.a_shape)),
+        matrix_b: pass.matrix_b.clone(),
+        b_shape: Some(shape_to_proto(pass.b_shape)),
+        output_shape: Some(shape_to_proto(pass.output_shape)),
+        output_tile: Some(TileProto {
+            tile_row: pass.output_tile.tile_row as u64,
+            tile_col: pass.output_tile.tile_col as u64,
+            start_row: pass.output_tile.start_row as u64,
+            start_col: pass.output_tile.start_col as u64,
+        }),
+        operation: match pass.operation {
+            PassOperation::Initialize => 0,
+            PassOperation::Accumulate => 1,
+            PassOperation::Final => 2,
+        },
+        a_addr: pass.a_addr as u64,
+        b_addr: pass.b_addr as u64,
+        a_scale: pass.a_scale,
+        b_scale: pass.b_scale,
+        expected_output: pass.expected_output.clone(),
+        acc_init: pass.acc_init.clone(),
+        spill: pass.spill,
+        reload: pass.reload,
+        epilogue: pass.epilogue.iter().map(epilogue_op_to_proto).collect(),
+    }
+}
+
+fn pass_from_proto(pass: PassProto) -> Result<SystolicPass, ProtoError> {
+    let tile = pass.output_tile.unwrap_or_default();
+    Ok(SystolicPass {
+        id: pass.id as usize,
+        description: pass.description,
+        matrix_a: pass.matrix_a,
+        a_shape: shape_from_proto(pass.a_shape),
+        matrix_b: pass.matrix_b,
+        b_shape: shape_from_proto(pass.b_shape),
+        output_shape: shape_from_proto(pass.output_shape),
+        output_tile: TileCoord::new(
+            tile.tile_row as usize,
+            tile.tile_col as usize,
+            tile.start_row as usize,
+            tile.start_col as usize,
+        ),
+        operation: match pass.operation {
+            0 => PassOperation::Initialize,
+            1 => PassOperation::Accumulate,
+            2 => PassOperation::Final,
+            other => return Err(invalid_enum("pass operation", other)),
+        },
+        a_addr: pass.a_addr as usize,
+        b_addr: pass.b_addr as usize,
+        a_scale: pass.a_scale,
+        b_scale: pass.b_scale,
+        expected_output: pass.expected_output,
+        acc_init: pass.acc_init,
+        spill: pass.spill,
+        reload: pass.reload,
+        epilogue: pass.epilogue.into_iter().map(epilogue_op_from_proto).collect::<Result<_, _>>()?,
+    })
+}
+
+fn epilogue_op_to_proto(op: &EpilogueOp) -> EpilogueOpProto {
+    let (kind, values, activation) = match op {
+        EpilogueOp::Dequantize { scale } => (0, vec![*scale], 0),
+        EpilogueOp::Bias(bias) => (1, bias.clone(), 0),
+        EpilogueOp::Scale(factor) => (2, vec![*factor], 0),
+        EpilogueOp::Activation(kind) => (3, Vec::new(), activation_to_proto(*kind)),
+        EpilogueOp::Clip { min, max } => (4, vec![*min, *max], 0),
+    };
+    EpilogueOpProto { kind, values, activation }
+}
+
+fn epilogue_op_from_proto(op: EpilogueOpProto) -> Result<EpilogueOp, ProtoError> {
+    let value = |i: usize| {
+        op.values.get(i).copied()
+            .ok_or_else(|| ProtoError::Invalid(format!("epilogue op {} is missing a value", op.kind)))
+    };
+    Ok(match op.kind {
+        0 => EpilogueOp::Dequantize { scale: value(0)? },
+        1 => EpilogueOp::Bias(op.values.clone()),
+        2 => EpilogueOp::Scale(value(0)?),
+        3 => EpilogueOp::Activation(activation_from_proto(op.activation)?),
+        4 => EpilogueOp::Clip { min: value(0)?, max: value(1)? },
+        other => return Err(invalid_enum("epilogue op", other)),
+    })
+}
+
+fn activation_to_proto(activation: Activation) -> u32 {
+    match activation {
+        Activation::Relu => 0,
+        Activation::Tanh => 1,
+        Activation::Sigmoid => 2,
+        Activation::Gelu => 3,
+    }
+}
+
+fn activation_from_proto(activation: u32) -> Result<Activation, ProtoError> {
+    match activation {
+        0 => Ok(Activation::Relu),
+        1 => Ok(Activation::Tanh),
+        2 => Ok(Activation::Sigmoid),
+        3 => Ok(Activation::Gelu),
+        other => Err(invalid_enum("activation", other)),
+    }
+}
+
+fn shape_to_proto((rows, cols): (usize, usize)) -> ShapeProto {
+    ShapeProto { rows: rows as u64, cols: cols as u64 }
+}
+
+fn shape_from_proto(shape: Option<ShapeProto>) -> (usize, usize) {
+    shape.map_or((0, 0), |s| (s.rows as usize, s.cols as usize))
+}
+
+fn invalid_enum(field: &str, value: u32) -> ProtoError {
+    ProtoError::Invalid(format!("unknown {} {}", field, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proto_round_trip() {
+        let config = SystolicConfig::new(2, 8, 32)
+            .with_tile_order(TileOrder::ZOrder)
+            .with_zero_point(-3)
+            .with_packing(WordPacking::new(4, Endianness::Big))
+            .with_accumulator_k_tiles(2)
+            .with_fuse_epilogue(true)
+            .with_eliminate_dead_statements(true)
+            .with_reorder_matmul_chains(true)
+            .with_rounding(RoundingMode::HalfEven)
+            .with_max_passes(500)
+            .with_dataflow(Dataflow::WeightStationary);
+        let program = crate::compile_with_shapes(
+            "C = relu(A @ B - 1) @ B.T * 2 + 1",
+            &[("A", (3, 5)), ("B", (5, 3))],
+            &config,
+        )
+        .unwrap();
+        assert!(program.passes.len() > 1);
+
+        let bytes = program.to_proto();
+        let decoded = HardwareProgram::from_proto(&bytes).unwrap();
+        // Every field survives, including the export settings
+        assert_eq!(decoded.to_json().unwrap(), program.to_json().unwrap());
+        assert_eq!(decoded.config.packing, program.config.packing);
+        assert!(!decoded.epilogues.is_empty());
+        assert!(decoded.passes.iter().any(|pass| !pass.epilogue.is_empty()));
+        assert_eq!(decoded.content_hash(), program.content_hash());
+
+        // Much smaller than the JSON export
+        assert!(bytes.len() * 2 < program.to_json().unwrap().len());
+    }
+
+    #[test]
+    fn test_from_proto_rejects_bad_input() {
+        assert!(matches!(HardwareProgram::from_proto(&[0xff]), Err(ProtoError::Decode(_))));
+        // An empty message has no config
+        assert!(matches!(HardwareProgram::from_proto(&[]), Err(ProtoError::Invalid(_))));
+
+        let config = SystolicConfig::new(2, 8, 32);
+        let program = crate::compile_with_shapes("C = A @ B", &[("A", (2, 2)), ("B", (2, 2))], &config).unwrap();
+        let mut pass = pass_to_proto(&program.passes[0]);
+        pass.operation = 7;
+        let err = pass_from_proto(pass).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid program: unknown pass operation 7");
+    }
+}
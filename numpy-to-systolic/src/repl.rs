@@ -0,0 +1,107 @@
+//! Interactive read-eval-print loop over a `Compiler`
+//!
+//! Each input line is compiled against the environment built up by the
+//! previous lines. Lines starting with `:` are meta-commands:
+//! - `:shapes` lists the defined variables and their shapes
+//! - `:config` shows the target array configuration
+//! - `:quit` exits the loop
+
+use std::io::{self, BufRead, Write};
+
+use crate::compiler::Compiler;
+use crate::hardware::HardwareProgram;
+
+const PROMPT: &str = ">>> ";
+
+/// Run the REPL until end of input or `:quit`
+pub fn run<R: BufRead, W: Write>(compiler: &mut Compiler, input: R, mut output: W) -> io::Result<()> {
+    write!(output, "{}", PROMPT)?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        match line {
+            "" => {}
+            ":quit" | ":q" => break,
+            ":shapes" => print_shapes(compiler, &mut output)?,
+            ":config" => {
+                let config = compiler.config();
+                writeln!(
+                    output,
+                    "{}x{} systolic array ({}-bit data, {}-bit accumulator)",
+                    config.array_size, config.array_size, config.data_width, config.acc_width
+                )?;
+            }
+            cmd if cmd.starts_with(':') => {
+                writeln!(output, "Unknown command: {} (try :shapes, :config, :quit)", cmd)?;
+            }
+            source => match compiler.compile(source) {
+                Ok(program) => {
+                    for warning in compiler.warnings() {
+                        writeln!(output, "Warning: {}", warning)?;
+                    }
+                    print_summary(&program, &mut output)?
+                }
+                Err(e) => writeln!(output, "Error: {}", e)?,
+            },
+        }
+
+        write!(output, "{}", PROMPT)?;
+        output.flush()?;
+    }
+
+    writeln!(output)?;
+    Ok(())
+}
+
+fn print_shapes<W: Write>(compiler: &Compiler, output: &mut W) -> io::Result<()> {
+    let mut names: Vec<_> = compiler.shapes().iter().collect();
+    names.sort_by(|a, b| a.0.cmp(b.0));
+
+    if names.is_empty() {
+        writeln!(output, "(no variables defined)")?;
+    }
+    for (name, shape) in names {
+        writeln!(output, "{}: {}", name, shape)?;
+    }
+    Ok(())
+}
+
+fn print_summary<W: Write>(program: &HardwareProgram, output: &mut W) -> io::Result<()> {
+    writeln!(
+        output,
+        "output {:?}, {} pass(es), {} cycles",
+        program.output_shape,
+        program.passes.len(),
+        program.total_cycles
+    )?;
+    for pass in &program.passes {
+        writeln!(output, "  Pass {}: {} ({:?})", pass.id, pass.description, pass.operation)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::SystolicConfig;
+
+    #[test]
+    fn test_scripted_session() {
+        let mut compiler = Compiler::new(SystolicConfig::new(3, 8, 32));
+        let input = "A = [[1, 2], [3, 4]]\nC = A @ A\n:shapes\n:config\nD = A @ X\n:quit\nE = A\n";
+        let mut output = Vec::new();
+
+        run(&mut compiler, input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("output (2, 2), 1 pass(es), 5 cycles"));
+        assert!(output.contains("A: (2, 2)\nC: (2, 2)\n"));
+        assert!(output.contains("3x3 systolic array (8-bit data, 32-bit accumulator)"));
+        assert!(output.contains("Error:"));
+        // Input after :quit is never compiled
+        assert!(!compiler.shapes().contains_key("E"));
+    }
+}
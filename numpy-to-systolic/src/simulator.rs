@@ -0,0 +1,900 @@
+//! Numeric simulator for tiled programs
+//!
+//! Executes a `TiledProgram` on concrete data so results can be checked
+//! without a Chisel simulation. Matrix multiplications run tile by tile on
+//! quantized operands, exactly as the systolic array would; everything else
+//! (element-wise ops, transposes, activations) runs in `f64` as the host would.
+//!
+//! `HardwareProgram::simulate_checked` works one level lower, replaying the
+//! generated passes and checking each finished output tile.
+
+use std::collections::HashMap;
+
+use crate::error::{CompileError, CompileResult};
+use crate::hardware::{
+    check_integer_data, quantize_matrix, tile_product, HardwareProgram, PassOperation, SystolicConfig, SystolicPass,
+};
+use crate::tiling::{
+    broadcast_zip, concat_data, diag_data, norm_data, roll_data, split_block, EpilogueStage, MatMulTile, TiledOperation, TiledProgram,
+};
+
+impl HardwareProgram {
+    /// Replay the pass stream on its own operand data, checking every `Final` pass
+    ///
+    /// Each pass's tiles are multiplied and accumulated as the array would. When a
+    /// `Final` pass completes, the accumulators saturate and are compared against its
+    /// `expected_output`, and the first divergence is reported by pass id. A pass
+    /// whose real outputs change when its padding is zeroed reads padding, and is
+    /// reported too. Returns the finished output tiles in pass order.
+    pub fn simulate_checked(&self) -> CompileResult<Vec<Vec<i64>>> {
+        let n = self.config.array_size;
+        let mut acc = vec![0i64; n * n];
+        let mut accumulating = false;
+        let mut outputs = Vec::new();
+
+        for pass in &self.passes {
+            if !accumulating {
+                if pass.acc_init.is_empty() {
+                    acc.fill(0);
+                } else {
+                    acc.copy_from_slice(&pass.acc_init);
+                }
+            }
+            let product = tile_product(&pass.matrix_a, &pass.matrix_b, n, self.config.dataflow);
+            check_padding_reads(pass, &product, &self.config)?;
+            acc.iter_mut().zip(product).for_each(|(sum, p)| *sum = sum.wrapping_add(p));
+
+            if pass.operation != PassOperation::Final {
+                // Spilled partial sums come back through the next pass's `acc_init`
+                accumulating = !pass.spill;
+                continue;
+            }
+            accumulating = false;
+            let (min, max) = (self.config.acc_min(), self.config.acc_max());
+            acc.iter_mut().for_each(|value| *value = (*value).clamp(min, max));
+
+            if !pass.expected_output.is_empty() {
+                if let Some(idx) = (0..acc.len()).find(|&i| pass.expected_output.get(i) != Some(&acc[i])) {
+                    return Err(CompileError::simulation(format!(
+                        "Pass {} diverges from its expected output at element {}: computed {}, expected {:?}",
+                        pass.id,
+                        idx,
+                        acc[idx],
+                        pass.expected_output.get(idx)
+                    )));
+                }
+            }
+            outputs.push(acc.clone());
+        }
+
+        Ok(outputs)
+    }
+
+    /// Run the passes and assemble the last matmul's product in real units
+    ///
+    /// Finished tiles saturate to the accumulator range as they drain, are
+    /// dequantized (or run through their fused epilogue), and land at their tile
+    /// offsets in a matrix of the product's shape. Host-side steps after the
+    /// matmul are not applied; use `Simulator` for the whole program.
+    pub fn evaluate(&self) -> CompileResult<Vec<Vec<f64>>> {
+        let group = self.groups.last()
+            .ok_or_else(|| CompileError::simulation("Program has no matmul passes to evaluate"))?;
+        let tiles = self.simulate_checked()?;
+        let is_final = |pass: &&SystolicPass| pass.operation == PassOperation::Final;
+        let finals_before = self.passes[..group.first_pass].iter().filter(is_final).count();
+
+        let n = self.config.array_size;
+        let (min, max) = (self.config.acc_min(), self.config.acc_max());
+        let (rows, cols) = group.shape;
+        let mut result = vec![vec![0.0; cols]; rows];
+        let passes = &self.passes[group.first_pass..group.first_pass + group.num_passes];
+        for (pass, acc) in passes.iter().filter(is_final).zip(&tiles[finals_before..]) {
+            let drained: Vec<i64> = acc.iter().map(|v| v.clamp(&min, &max)).copied().collect();
+            let values = if pass.epilogue.is_empty() {
+                drained.iter().map(|&v| v as f64 / (pass.a_scale * pass.b_scale)).collect()
+            } else {
+                pass.apply_epilogue(&drained)
+            };
+            let (row0, col0) = (pass.output_tile.start_row, pass.output_tile.start_col);
+            for i in 0..n.min(rows.saturating_sub(row0)) {
+                for j in 0..n.min(cols.saturating_sub(col0)) {
+                    result[row0 + i][col0 + j] = values[i * n + j];
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Error if any real output of `pass` depends on the padding around its operand tiles
+fn check_padding_reads(pass: &SystolicPass, product: &[i64], config: &SystolicConfig) -> CompileResult<()> {
+    let n = config.array_size;
+    let (rows, k, cols) = (pass.a_shape.0, pass.a_shape.1, pass.b_shape.1);
+    let b_index = |k: usize, j: usize| if config.dataflow.b_column_major() { j * n + k } else { k * n + j };
+    let mut a = vec![0i64; n * n];
+    let mut b = vec![0i64; n * n];
+    for i in 0..rows.min(n) {
+        for kk in 0..k.min(n) {
+            a[i * n + kk] = pass.matrix_a[i * n + kk];
+        }
+    }
+    for kk in 0..pass.b_shape.0.min(n) {
+        for j in 0..cols.min(n) {
+            b[b_index(kk, j)] = pass.matrix_b[b_index(kk, j)];
+        }
+    }
+    let unpadded = tile_product(&a, &b, n, config.dataflow);
+    for i in 0..rows.min(n) {
+        for j in 0..cols.min(n) {
+            if unpadded[i * n + j] != product[i * n + j] {
+                return Err(CompileError::simulation(format!(
+                    "Pass {} reads padding into output element ({}, {})",
+                    pass.id, i, j
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Dense row-major matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimMatrix {
+    pub data: Vec<f64>,
+    pub shape: (usize, usize),
+}
+
+impl SimMatrix {
+    pub fn new(data: Vec<f64>, shape: (usize, usize)) -> Self {
+        Self { data, shape }
+    }
+
+    /// Build from nested rows
+    pub fn from_rows(rows: &[Vec<f64>]) -> Self {
+        let m = rows.len();
+        let n = if m > 0 { rows[0].len() } else { 0 };
+        Self::new(rows.iter().flatten().copied().collect(), (m, n))
+    }
+
+    /// Swap rows and columns
+    pub fn transpose(&self) -> Self {
+        let (rows, cols) = self.shape;
+        let data = (0..cols)
+            .flat_map(|j| (0..rows).map(move |i| self.data[i * cols + j]))
+            .collect();
+        Self::new(data, (cols, rows))
+    }
+
+    /// Split back into nested rows
+    pub fn to_rows(&self) -> Vec<Vec<f64>> {
+        if self.shape.1 == 0 {
+            return vec![Vec::new(); self.shape.0];
+        }
+        self.data.chunks(self.shape.1).map(|row| row.to_vec()).collect()
+    }
+
+    /// Aligned grid with every cell right-justified to the widest value
+    pub fn to_pretty_string(&self) -> String {
+        let cells: Vec<String> = self.data.iter().map(|v| v.to_string()).collect();
+        let width = cells.iter().map(String::len).max().unwrap_or(0);
+        if self.shape.1 == 0 {
+            return String::new();
+        }
+
+        cells
+            .chunks(self.shape.1)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| format!("{:>width$}", cell, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Elements at the given rows and columns, in order
+    fn gather(&self, rows: &[usize], cols: &[usize], shape: (usize, usize)) -> Self {
+        let data = rows.iter()
+            .flat_map(|&i| cols.iter().map(move |&j| self.data[i * self.shape.1 + j]))
+            .collect();
+        Self::new(data, shape)
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64) -> Self {
+        Self::new(self.data.iter().map(|&v| f(v)).collect(), self.shape)
+    }
+}
+
+/// Simulator holding named buffers
+pub struct Simulator {
+    config: SystolicConfig,
+    buffers: HashMap<String, SimMatrix>,
+    /// Matmul output elements clamped to the accumulator range during the last run
+    saturated: usize,
+}
+
+impl Simulator {
+    pub fn new(config: SystolicConfig) -> Self {
+        Self {
+            config,
+            buffers: HashMap::new(),
+            saturated: 0,
+        }
+    }
+
+    /// Provide data for a named input matrix
+    pub fn define_input(&mut self, name: &str, matrix: SimMatrix) {
+        self.buffers.insert(name.to_string(), matrix);
+    }
+
+    /// Look up a buffer by name
+    pub fn buffer(&self, name: &str) -> Option<&SimMatrix> {
+        self.buffers.get(name)
+    }
+
+    /// Number of matmul output elements that saturated the accumulator in the last run
+    ///
+    /// Nonzero means `acc_width` is too narrow for the program's data.
+    pub fn saturated(&self) -> usize {
+        self.saturated
+    }
+
+    /// Run every operation and return the value of the last one
+    pub fn run(&mut self, program: &TiledProgram) -> CompileResult<SimMatrix> {
+        self.saturated = 0;
+        let mut last = None;
+        for op in &program.operations {
+            last = Some(self.execute(op)?);
+        }
+        last.ok_or_else(|| CompileError::simulation("Program has no operations"))
+    }
+
+    /// Execute one operation, store its result, and return it
+    fn execute(&mut self, op: &TiledOperation) -> CompileResult<SimMatrix> {
+        let (target, value) = match op {
+            TiledOperation::LoadMatrix { target, source, .. } => {
+                (target, self.get(source)?.clone())
+            }
+            TiledOperation::LoadLiteral { target, data, shape } => {
+                (target, SimMatrix::new(data.clone(), *shape))
+            }
+            TiledOperation::TiledMatMul {
+                target, left_source, right_source, left_shape, right_shape, output_shape, tiles, accumulate, epilogue, transposed, ..
+            } => {
+                let (mut left, mut right) = (self.get(left_source)?, self.get(right_source)?);
+                let transposes;
+                if *transposed {
+                    transposes = (left.transpose(), right.transpose());
+                    (left, right) = (&transposes.0, &transposes.1);
+                }
+                // Operand shapes come from the op: a vector buffer is a row, but may be used as a column
+                let (k, n) = (left_shape.1, right_shape.1);
+                check_integer_data(left_source, &left.data, &self.config)?;
+                check_integer_data(right_source, &right.data, &self.config)?;
+                let init = match accumulate {
+                    Some(name) => Some(self.get(name)?.data.as_slice()),
+                    None => None,
+                };
+                let (value, saturated) = self.matmul(&left.data, &right.data, init, (k, n), *output_shape, tiles);
+                self.saturated += saturated;
+                (target, self.apply_epilogue(value, epilogue)?)
+            }
+            TiledOperation::Add { target, left, right, .. } => {
+                (target, self.zip(left, right, |a, b| a + b)?)
+            }
+            TiledOperation::Sub { target, left, right, .. } => {
+                (target, self.zip(left, right, |a, b| a - b)?)
+            }
+            TiledOperation::ElementMul { target, left, right, .. } => {
+                (target, self.zip(left, right, |a, b| a * b)?)
+            }
+            TiledOperation::ElementDiv { target, left, right, .. } => {
+                if self.get(right)?.data.contains(&0.0) {
+                    return Err(CompileError::invalid_op(format!("Division by zero in '{}'", target)));
+                }
+                (target, self.zip(left, right, |a, b| a / b)?)
+            }
+            TiledOperation::ScalarMul { target, source, scalar, .. } => {
+                (target, self.get(source)?.map(|v| v * scalar))
+            }
+            TiledOperation::Transpose { target, source, .. } => {
+                (target, self.get(source)?.transpose())
+            }
+            TiledOperation::Reshape { target, source, shape, .. } => {
+                (target, SimMatrix::new(self.get(source)?.data.clone(), *shape))
+            }
+            TiledOperation::Index { target, source, rows, cols, shape } => {
+                (target, self.get(source)?.gather(rows, cols, *shape))
+            }
+            TiledOperation::Split { target, source, axis, part, shape, .. } => {
+                let (rows, cols) = split_block(*axis, *part, *shape);
+                (target, self.get(source)?.gather(&rows, &cols, *shape))
+            }
+            TiledOperation::Activation { target, source, kind, .. } => {
+                (target, self.get(source)?.map(|v| kind.apply(v)))
+            }
+            TiledOperation::Concat { target, left, right, axis, shape } => {
+                let data = concat_data(&self.get(left)?.data, &self.get(right)?.data, *axis, *shape);
+                (target, SimMatrix::new(data, *shape))
+            }
+            TiledOperation::Roll { target, source, shift, axis, shape } => {
+                (target, SimMatrix::new(roll_data(&self.get(source)?.data, *shift, *axis, *shape), *shape))
+            }
+            TiledOperation::Norm { target, source, axis, source_shape, shape } => {
+                (target, SimMatrix::new(norm_data(&self.get(source)?.data, *axis, *source_shape), *shape))
+            }
+            TiledOperation::Diag { target, source, source_shape, shape } => {
+                (target, SimMatrix::new(diag_data(&self.get(source)?.data, *source_shape, *shape), *shape))
+            }
+            TiledOperation::Clip { target, source, min, max, .. } => {
+                (target, self.get(source)?.map(|v| v.clamp(*min, *max)))
+            }
+        };
+
+        self.buffers.insert(target.clone(), value.clone());
+        Ok(value)
+    }
+
+    /// Run a matmul's fused element-wise steps over its product, in order
+    fn apply_epilogue(&self, mut value: SimMatrix, epilogue: &[EpilogueStage]) -> CompileResult<SimMatrix> {
+        for stage in epilogue {
+            value = match stage {
+                EpilogueStage::Bias(name) => {
+                    let bias = self.get(name)?;
+                    let (shape, data) = broadcast_zip((&value.data, value.shape), (&bias.data, bias.shape), |a, b| a + b)
+                        .ok_or_else(|| CompileError::simulation(format!("Bias '{}' does not fit the product", name)))?;
+                    SimMatrix::new(data, shape)
+                }
+                EpilogueStage::Scale(factor) => value.map(|v| v * factor),
+                EpilogueStage::Activation(kind) => value.map(|v| kind.apply(v)),
+                EpilogueStage::Clip { min, max } => value.map(|v| v.clamp(*min, *max)),
+            };
+        }
+        Ok(value)
+    }
+
+    fn get(&self, name: &str) -> CompileResult<&SimMatrix> {
+        self.buffers.get(name)
+            .ok_or_else(|| CompileError::simulation(format!("No data for '{}'", name)))
+    }
+
+    /// Element-wise combination of two equally shaped buffers
+    fn zip(&self, left: &str, right: &str, f: impl Fn(f64, f64) -> f64) -> CompileResult<SimMatrix> {
+        let l = self.get(left)?;
+        let r = self.get(right)?;
+        let (shape, data) = broadcast_zip((&l.data, l.shape), (&r.data, r.shape), f).ok_or_else(|| {
+            CompileError::simulation(format!(
+                "Element-wise operands have different shapes: {:?} and {:?}",
+                l.shape, r.shape
+            ))
+        })?;
+        Ok(SimMatrix::new(data, shape))
+    }
+
+    /// Tile-by-tile integer matmul on quantized operands, clamped to the accumulator range
+    ///
+    /// Also returns how many output elements were clamped.
+    fn matmul(
+        &self,
+        left: &[f64],
+        right: &[f64],
+        init: Option<&[f64]>,
+        (k, n): (usize, usize),
+        output_shape: (usize, usize),
+        tiles: &[MatMulTile],
+    ) -> (SimMatrix, usize) {
+        let a = quantize_matrix(left, 1.0, &self.config);
+        let b = quantize_matrix(right, 1.0, &self.config);
+
+        // A fused residual is the accumulators' starting value
+        let mut acc = match init {
+            Some(init) => init.iter().map(|&v| self.config.rounding.round(v) as i64).collect(),
+            None => vec![0i64; output_shape.0 * output_shape.1],
+        };
+        for tile in tiles {
+            for i in tile.a_row_range.0..tile.a_row_range.1 {
+                for j in tile.b_col_range.0..tile.b_col_range.1 {
+                    let partial: i64 = (tile.a_col_range.0..tile.a_col_range.1)
+                        .map(|kk| a[i * k + kk] * b[kk * n + j])
+                        .sum();
+                    acc[i * output_shape.1 + j] += partial;
+                }
+            }
+        }
+
+        // The accumulator saturates at its signed `acc_width` range
+        let (min, max) = (self.config.acc_min(), self.config.acc_max());
+        let saturated = acc.iter().filter(|&&v| v < min || v > max).count();
+        let data = acc.into_iter().map(|v| v.clamp(min, max) as f64).collect();
+        (SimMatrix::new(data, output_shape), saturated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+    use crate::ast::Activation;
+    use crate::hardware::EpilogueOp;
+    use crate::parser::Parser;
+    use crate::tiling::TilingStrategy;
+
+    fn simulate(source: &str, config: &SystolicConfig) -> Vec<Vec<f64>> {
+        let program = Parser::new(source).parse_program().unwrap();
+        let typed = Analyzer::new().analyze(program).unwrap();
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        Simulator::new(config.clone()).run(&tiled).unwrap().to_rows()
+    }
+
+    #[test]
+    fn test_tiled_matmul() {
+        // 4x4 operands force tiling on a 3x3 array
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate(
+            "C = [[1, 2, 3, 4], [5, 6, 7, 8], [1, 0, 1, 0], [0, 1, 0, 1]] @ np.eye(4)",
+            &config,
+        );
+        assert_eq!(result[1], vec![5.0, 6.0, 7.0, 8.0]);
+        assert_eq!(result[3], vec![0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_reordered_chain_matches() {
+        let source = "D = [[1, 2], [3, 4], [5, 6]] @ [[1, 0, 2], [0, 1, 1]] @ [[1], [2], [3]]";
+        let config = SystolicConfig::new(2, 8, 32);
+        let expected = simulate(source, &config);
+        assert_eq!(expected, vec![vec![17.0], vec![41.0], vec![65.0]]);
+        assert_eq!(simulate(source, &config.with_reorder_matmul_chains(true)), expected);
+    }
+
+    #[test]
+    fn test_division() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let result = simulate("C = [[2, 9], [4, 8]] / [[2, 3], [8, 2]] + [[1, 2], [3, 4]] @ [[2, 0], [0, 2]] / 2", &config);
+        assert_eq!(result, vec![vec![2.0, 5.0], vec![3.5, 8.0]]);
+        
+        let program = Parser::new("C = [[1, 2]] / 0").parse_program().unwrap();
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&Analyzer::new().analyze(program).unwrap()).unwrap();
+        let err = Simulator::new(config).run(&tiled).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid operation: Division by zero in 'C'");
+    }
+
+    #[test]
+    fn test_folded_transpose() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let result = simulate("D = ([[1, 2, 3], [4, 5, 6]] @ [[1, 0, 2], [0, 1, 0], [1, 1, -1]]).T", &config);
+        assert_eq!(result, vec![vec![4.0, 10.0], vec![5.0, 11.0], vec![-1.0, 2.0]]);
+    }
+
+    #[test]
+    fn test_poison_padding() {
+        // Poison the padding past A's rows and B's columns; real outputs never read it
+        let config = SystolicConfig::new(3, 64, 64).with_zero_point(i64::MIN).with_saturate_accumulators(true);
+        let mut program = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", &config).unwrap();
+        assert_eq!(program.passes[0].matrix_a[6..], [i64::MIN; 3]);
+        assert_eq!(program.evaluate().unwrap(), vec![vec![19.0, 22.0], vec![43.0, 50.0]]);
+        
+        // Nonzero padding on both sides of K leaks into real outputs
+        program.passes[0].matrix_a[2] = i64::MIN;
+        program.passes[0].matrix_b[2] = 1;
+        let message = program.evaluate().unwrap_err().to_string();
+        assert!(message.contains("Pass 0 reads padding into output element (0, 0)"), "{}", message);
+    }
+
+    #[test]
+    fn test_pretty_string_alignment() {
+        let matrix = SimMatrix::from_rows(&[vec![1.0, -250.0], vec![-3.5, 42.0]]);
+        assert_eq!(matrix.to_pretty_string(), "   1  -250\n-3.5    42");
+    }
+
+    #[test]
+    fn test_update_reads_prior_value() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate("A = [[1, 2], [3, 4]]; A = A @ A", &config);
+        assert_eq!(result, vec![vec![7.0, 10.0], vec![15.0, 22.0]]);
+    }
+    
+    #[test]
+    fn test_dot_matrix_vector() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate(
+            "A = [[1, 2, 3, 4], [5, 6, 7, 8]]; v = [[1, 0, -1, 2]].flatten(); y = np.dot(A, v)",
+            &config,
+        );
+        // [1 - 3 + 8, 5 - 7 + 16]
+        assert_eq!(result, vec![vec![6.0, 14.0]]);
+        
+        // A 1D literal is a vector, so the product is a length-3 vector too
+        let source = "A = [[1, 2, 3], [4, 5, 6], [7, 8, 10]]; y = A @ [1, 0, 2]";
+        assert_eq!(simulate(source, &config), vec![vec![7.0, 16.0, 27.0]]);
+        let program = crate::compile(source, &config).unwrap();
+        assert_eq!(program.evaluate().unwrap(), vec![vec![7.0], vec![16.0], vec![27.0]]);
+    }
+    
+    #[test]
+    fn test_scalar_broadcast() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate("C = [[1, 2], [3, 4]] + 10", &config);
+        assert_eq!(result, vec![vec![11.0, 12.0], vec![13.0, 14.0]]);
+        
+        let result = simulate("C = 1 - [[1, 2], [3, 4]] * [[3]]", &config);
+        assert_eq!(result, vec![vec![-2.0, -5.0], vec![-8.0, -11.0]]);
+        
+        // A bias row repeats down the rows, a column across the columns
+        let result = simulate("C = [[1, 2], [3, 4]] @ [[1, 0], [0, 1]] + [[10, 20]]", &config);
+        assert_eq!(result, vec![vec![11.0, 22.0], vec![13.0, 24.0]]);
+        let result = simulate("C = [[1, 2], [3, 4]] - [[1], [2]]", &config);
+        assert_eq!(result, vec![vec![0.0, 1.0], vec![1.0, 2.0]]);
+    }
+    
+    #[test]
+    fn test_accumulator_saturation() {
+        // 100 * 100 + 100 * 100 needs more than a 12-bit accumulator
+        let config = SystolicConfig::new(3, 8, 12);
+        let program = Parser::new("C = [[100, 100], [1, 1]] @ [[100, -100], [100, 2]]").parse_program().unwrap();
+        let typed = Analyzer::new().analyze(program).unwrap();
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        
+        let mut simulator = Simulator::new(config);
+        let result = simulator.run(&tiled).unwrap();
+        assert_eq!(result.to_rows(), vec![vec![2047.0, -2048.0], vec![200.0, -98.0]]);
+        assert_eq!(simulator.saturated(), 2);
+        
+        let mut wide = Simulator::new(SystolicConfig::new(3, 8, 32));
+        assert_eq!(wide.run(&tiled).unwrap().to_rows()[0], vec![20000.0, -9800.0]);
+        assert_eq!(wide.saturated(), 0);
+    }
+    
+    #[test]
+    fn test_integer_only() {
+        let config = SystolicConfig::new(3, 8, 32).with_integer_only(true);
+        let result = simulate("C = [[127, -128], [3, 4]] @ [[127, 1], [-128, 1]]", &config);
+        assert_eq!(result, vec![vec![32513.0, -1.0], vec![-131.0, 7.0]]);
+        
+        let run = |source: &str| {
+            let program = Parser::new(source).parse_program().unwrap();
+            let typed = Analyzer::new().analyze(program).unwrap();
+            let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+            Simulator::new(config.clone()).run(&tiled)
+        };
+        let err = run("C = [[1.5, 2], [3, 4]] @ [[1, 0], [0, 1]]").unwrap_err().to_string();
+        assert!(err.contains("Integer-only mode") && err.contains("1.5"), "{}", err);
+        assert!(run("C = [[200, 2], [3, 4]] @ [[1, 0], [0, 1]]").is_err());
+        
+        let err = crate::compile("C = [[1, 2], [3, 4]] @ [[0.5, 0], [0, 1]]", &config).unwrap_err();
+        assert!(err.to_string().contains("contains 0.5"), "{}", err);
+    }
+    
+    #[test]
+    fn test_reverse_rows() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate("B = [[1, 2], [3, 4], [5, 6]][::-1, :]", &config);
+        assert_eq!(result, vec![vec![5.0, 6.0], vec![3.0, 4.0], vec![1.0, 2.0]]);
+        
+        let result = simulate("v = [[1, 2, 3, 4, 5]][0][-1:0:-2]", &config);
+        assert_eq!(result, vec![vec![5.0, 3.0]]);
+    }
+    
+    #[test]
+    fn test_split() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let source = "A = [[1, 2], [3, 4], [5, 6], [7, 8]]";
+        
+        let top = simulate(&format!("{}; P = np.split(A, 2)[0]", source), &config);
+        assert_eq!(top, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let bottom = simulate(&format!("{}; P = np.split(A, 2, axis=0)[-1]", source), &config);
+        assert_eq!(bottom, vec![vec![5.0, 6.0], vec![7.0, 8.0]]);
+        let right = simulate(&format!("{}; P = np.split(A, 2, 1)[1]", source), &config);
+        assert_eq!(right, vec![vec![2.0], vec![4.0], vec![6.0], vec![8.0]]);
+        
+        let left = simulate(&format!("{}; P = np.hsplit(A, 2)[0]", source), &config);
+        assert_eq!(left, vec![vec![1.0], vec![3.0], vec![5.0], vec![7.0]]);
+        let middle = simulate(&format!("{}; P = np.vsplit(A, 4)[2]", source), &config);
+        assert_eq!(middle, vec![vec![5.0, 6.0]]);
+    }
+    
+    #[test]
+    fn test_append() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let source = "A = [[1, 2], [3, 4]]; B = [[5, 6]]";
+        
+        let rows = simulate(&format!("{}; C = np.append(A, B, axis=0)", source), &config);
+        assert_eq!(rows, vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]]);
+        let cols = simulate(&format!("{}; C = np.append(A, B.T, 1)", source), &config);
+        assert_eq!(cols, vec![vec![1.0, 2.0, 5.0], vec![3.0, 4.0, 6.0]]);
+        let flat = simulate(&format!("{}; C = np.append(A, B)", source), &config);
+        assert_eq!(flat, vec![vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]]);
+        
+        // A stacked block matrix is materialized before it feeds the array
+        let stacked = format!("{}; C = np.vstack((A, B, B)) @ [[1, 0], [1, 1]]", source);
+        let expected = vec![vec![3.0, 2.0], vec![7.0, 4.0], vec![11.0, 6.0], vec![11.0, 6.0]];
+        assert_eq!(simulate(&stacked, &config), expected);
+        assert_eq!(crate::compile(&stacked, &config).unwrap().evaluate().unwrap(), expected);
+    }
+    
+    #[test]
+    fn test_matrix_power() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let source = |exponent: u32| format!("A = [[1, 1, 0], [0, 1, 2], [0, 0, 1]]; C = A ** {}", exponent);
+        
+        assert_eq!(simulate(&source(0), &config), vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]]);
+        assert_eq!(simulate(&source(3), &config), vec![vec![1.0, 3.0, 6.0], vec![0.0, 1.0, 6.0], vec![0.0, 0.0, 1.0]]);
+        
+        // A ** 6 = A ** 2 @ A ** 4 needs three matmuls, not five
+        let program = crate::compile(&source(6), &config).unwrap();
+        assert_eq!(program.passes.len(), 3);
+        let expected = vec![vec![1.0, 6.0, 30.0], vec![0.0, 1.0, 12.0], vec![0.0, 0.0, 1.0]];
+        assert_eq!(program.evaluate().unwrap(), expected);
+        assert_eq!(simulate(&source(6), &config), expected);
+    }
+    
+    #[test]
+    fn test_roll() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let source = "A = [[1, 2, 3], [4, 5, 6]]";
+        
+        let rolled = simulate("A = [[1, 2, 3]]; B = np.roll(A, 1, 1)", &config);
+        assert_eq!(rolled, vec![vec![3.0, 1.0, 2.0]]);
+        let rows = simulate(&format!("{}; B = np.roll(A, 3, axis=0)", source), &config);
+        assert_eq!(rows, vec![vec![4.0, 5.0, 6.0], vec![1.0, 2.0, 3.0]]);
+        let left = simulate(&format!("{}; B = np.roll(A, -1, axis=-1)", source), &config);
+        assert_eq!(left, vec![vec![2.0, 3.0, 1.0], vec![5.0, 6.0, 4.0]]);
+        let flat = simulate(&format!("{}; B = np.roll(A, 2)", source), &config);
+        assert_eq!(flat, vec![vec![5.0, 6.0, 1.0], vec![2.0, 3.0, 4.0]]);
+        let vector = simulate(&format!("{}; B = np.roll(A[1], -4)", source), &config);
+        assert_eq!(vector, vec![vec![5.0, 6.0, 4.0]]);
+    }
+    
+    #[test]
+    fn test_norm() {
+        let config = SystolicConfig::new(2, 8, 32);
+        
+        assert_eq!(simulate("v = [[3, 4]]; n = np.linalg.norm(v[0])", &config), vec![vec![5.0]]);
+        // Frobenius: sqrt(1 + 4 + 4 + 16)
+        let source = "A = [[1, 2], [2, 4]]";
+        assert_eq!(simulate(&format!("{}; n = np.linalg.norm(A)", source), &config), vec![vec![5.0]]);
+        let rows = simulate(&format!("{}; n = np.linalg.norm(A, axis=1)", source), &config);
+        assert_eq!(rows, vec![vec![5f64.sqrt(), 20f64.sqrt()]]);
+        
+        // Normalizing the columns of a matmul result
+        let cols = simulate("A = [[3, 0], [4, 1]]; n = np.linalg.norm(A @ np.eye(2), axis=0)", &config);
+        assert_eq!(cols, vec![vec![5.0, 1.0]]);
+    }
+    
+    #[test]
+    fn test_fused_residual() {
+        let source = "A = [[1, 2, 3], [4, 5, 6]]; B = [[1, 0], [2, 1], [0, 3]]; X = [[10, 20], [30, 40]]; Y = A @ B + X";
+        let config = SystolicConfig::new(2, 8, 32);
+        let expected = vec![vec![15.0, 31.0], vec![44.0, 63.0]];
+        
+        assert_eq!(simulate(source, &config), expected);
+        let fused = config.with_fuse_residual(true);
+        assert_eq!(simulate(source, &fused), expected);
+        
+        // The residual rides in the passes' initial accumulators
+        let program = crate::compile(source, &fused).unwrap();
+        assert_eq!(program.passes[0].acc_init, vec![10, 20, 30, 40]);
+        assert!(program.passes[1].acc_init.is_empty());
+        assert_eq!(program.simulate_checked().unwrap(), vec![vec![15, 31, 44, 63]]);
+    }
+    
+    #[test]
+    fn test_fused_epilogue() {
+        let source = "A = [[1, -2], [3, 4]]; B = [[5, 6], [7, 8]]; bias = [[1, 1], [100, 1]]; \
+                      Y = clip(relu(A @ B + bias), 0, 127)";
+        let config = SystolicConfig::new(3, 8, 32);
+        let expected = vec![vec![0.0, 0.0], vec![127.0, 51.0]];
+        
+        assert_eq!(simulate(source, &config), expected);
+        let fused = config.with_fuse_epilogue(true);
+        assert_eq!(simulate(source, &fused), expected);
+        
+        // One pass drains through dequantize, bias, relu and clip; nothing is left for the host
+        let program = crate::compile(source, &fused).unwrap();
+        assert_eq!(program.passes.len(), 1);
+        let pass = &program.passes[0];
+        assert_eq!(pass.epilogue, vec![
+            EpilogueOp::Dequantize { scale: 1.0 },
+            EpilogueOp::Bias(vec![1.0, 1.0, 0.0, 100.0, 1.0, 0.0, 0.0, 0.0, 0.0]),
+            EpilogueOp::Activation(Activation::Relu),
+            EpilogueOp::Clip { min: 0.0, max: 127.0 },
+        ]);
+        assert_eq!(pass.apply_epilogue(&pass.expected_output), vec![0.0, 0.0, 0.0, 127.0, 51.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(program.epilogues.is_empty());
+        
+        // A bias row is repeated down the tile
+        let program = crate::compile("Y = [[1, 2], [3, 4]] @ [[2, 3], [4, 5]] + [[10, 20]]", &fused).unwrap();
+        assert_eq!(program.passes[0].epilogue[1], EpilogueOp::Bias(vec![10.0, 20.0, 0.0, 10.0, 20.0, 0.0, 0.0, 0.0, 0.0]));
+    }
+    
+    #[test]
+    fn test_def_two_matmul_function() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let source = "W1 = [[1, -1], [0, 1]]; W2 = [[2], [1]]; \
+                      def ffn(x) = relu(x @ W1) @ W2; \
+                      Y = ffn([[1, 2], [3, 1]])";
+        
+        // relu([[1, 1], [3, -2]]) @ W2
+        assert_eq!(simulate(source, &config), vec![vec![3.0], vec![6.0]]);
+    }
+    
+    #[test]
+    fn test_let_binding() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let source = "A = [[1, 2], [3, 4]]; B = [[0, 1], [1, 0]]; C = let t = A @ B in t + t * t";
+        
+        assert_eq!(simulate(source, &config), vec![vec![6.0, 2.0], vec![20.0, 12.0]]);
+        assert_eq!(crate::compile(source, &config).unwrap().passes.len(), 1);
+    }
+    
+    #[test]
+    fn test_transposed_matmul_chain() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let data = "A = [[1, 2, 0], [3, -1, 2]]; \
+                    B = [[2, 1], [0, 1], [1, -2], [3, 0]]; \
+                    C = [[1, 0, 2], [0, 1, -1], [2, 2, 0], [-1, 0, 1]]";
+        
+        let cases: [(&str, Vec<Vec<i64>>); 2] = [
+            ("D = A.T @ B.T @ C", vec![vec![-8, -7, 10], vec![5, 7, 13], vec![-6, -6, 2]]),
+            ("D = (A.T @ B.T).T @ A.T", vec![vec![11, 16], vec![1, 14], vec![3, -27], vec![15, 3]]),
+        ];
+        for (expr, expected) in cases {
+            let source = format!("{}; {}", data, expr);
+            let as_f64: Vec<Vec<f64>> = expected.iter()
+                .map(|row| row.iter().map(|&v| v as f64).collect())
+                .collect();
+            assert_eq!(simulate(&source, &config), as_f64, "{}", expr);
+            
+            // The passes must see correctly laid out operands at every stage, too
+            let program = crate::compile(&source, &config).unwrap();
+            let outputs = program.simulate_checked().unwrap();
+            let finals: Vec<_> = program.passes.iter()
+                .filter(|pass| pass.operation == PassOperation::Final)
+                .zip(&outputs)
+                .collect();
+            let (rows, cols) = program.output_shape;
+            let mut assembled = vec![vec![0i64; cols]; rows];
+            // The last matmul's output tiles are the last ones finished
+            let tiles = rows.div_ceil(2) * cols.div_ceil(2);
+            for (pass, tile) in &finals[finals.len() - tiles..] {
+                for i in pass.output_tile.row_range(2, rows) {
+                    for j in pass.output_tile.col_range(2, cols) {
+                        assembled[i][j] = tile[(i % 2) * 2 + j % 2];
+                    }
+                }
+            }
+            assert_eq!(assembled, expected, "{}", expr);
+        }
+    }
+    
+    #[test]
+    fn test_partial_sum_spill() {
+        // K = 10 is five K tiles on a 2x2 array; the accumulators only hold two
+        let source = "C = [[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]] @ [[1], [1], [1], [1], [1], [1], [1], [1], [1], [-1]]";
+        let on_chip = crate::compile(source, &SystolicConfig::new(2, 8, 32)).unwrap();
+        let config = SystolicConfig::new(2, 8, 32).with_accumulator_k_tiles(2);
+        let program = crate::compile(source, &config).unwrap();
+        
+        let flags: Vec<_> = program.passes.iter().map(|pass| (pass.spill, pass.reload)).collect();
+        assert_eq!(flags, vec![(false, false), (true, false), (false, true), (true, false), (false, true)]);
+        // The reload carries the running sum 1 + 2 + 3 + 4
+        assert_eq!(program.passes[2].acc_init, vec![10, 0, 0, 0]);
+        
+        assert_eq!(program.simulate_checked().unwrap(), on_chip.simulate_checked().unwrap());
+        assert_eq!(simulate(source, &config), vec![vec![35.0]]);
+        
+        // Two spills and two reloads of a 2x2 tile of 32-bit partial sums
+        assert_eq!(on_chip.spill_traffic_bytes(), 0);
+        assert_eq!(program.spill_traffic_bytes(), 4 * 4 * 4);
+        assert!(program.summary.contains("Partial-sum spills: 2 (64 bytes of DRAM traffic)"), "{}", program.summary);
+    }
+    
+    #[test]
+    fn test_evaluate_literal_program() {
+        let program = crate::compile("C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", &SystolicConfig::new(3, 8, 32)).unwrap();
+        assert_eq!(program.evaluate().unwrap(), vec![vec![19.0, 22.0], vec![43.0, 50.0]]);
+        
+        // Tiles and K steps reassemble to the reference product, including the ragged edge
+        let source = "A = [[1, 2, 3], [4, 5, 6], [0, 1, 0]]; C = A @ A.T @ np.eye(3)";
+        let config = SystolicConfig::new(2, 8, 32);
+        let program = crate::compile(source, &config).unwrap();
+        assert_eq!(program.evaluate().unwrap(), simulate(source, &config));
+        
+        // Accumulators saturate at acc_width like the hardware
+        let config = SystolicConfig::new(2, 8, 8).with_saturate_accumulators(true);
+        let program = crate::compile("C = [[100, 1]] @ [[100], [1]]", &config).unwrap();
+        assert_eq!(program.evaluate().unwrap(), vec![vec![127.0]]);
+        
+        let empty = crate::compile("C = [[1, 2]]", &SystolicConfig::new(2, 8, 32)).unwrap();
+        assert!(empty.evaluate().is_err());
+    }
+    
+    #[test]
+    fn test_simulate_checked_flags_corrupted_pass() {
+        let config = SystolicConfig::new(3, 8, 32);
+        // K = 4 spans two passes per output tile
+        let source = "C = [[1, 2, 3, 4], [5, 6, 7, 8]] @ [[1, 0], [0, 1], [1, 1], [2, -1]]";
+        let mut program = crate::compile(source, &config).unwrap();
+
+        let outputs = program.simulate_checked().unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0][..2], [12, 1]);
+
+        program.passes[1].expected_output[4] += 1;
+        let message = program.simulate_checked().unwrap_err().to_string();
+        assert!(message.contains("Pass 1 diverges from its expected output at element 4"), "{}", message);
+    }
+
+    #[test]
+    fn test_flatten() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate("v = [[1, 2], [3, 4]].T.flatten()", &config);
+        assert_eq!(result, vec![vec![1.0, 3.0, 2.0, 4.0]]);
+    }
+    
+    #[test]
+    fn test_reshape() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let source = "A = [[1, 2, 3, 4, 5, 6]]; B = np.reshape(A, (3, 2))";
+        assert_eq!(simulate(source, &config), vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]]);
+        let product = simulate(&format!("{} @ [[1], [1]]", source), &config);
+        assert_eq!(product, vec![vec![3.0], vec![7.0], vec![11.0]]);
+    }
+    
+    #[test]
+    fn test_diag() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let built = simulate("D = np.diag([1, 2, 3])", &config);
+        assert_eq!(built, vec![vec![1.0, 0.0, 0.0], vec![0.0, 2.0, 0.0], vec![0.0, 0.0, 3.0]]);
+        
+        let extracted = simulate("d = np.diag([[1, 2, 3], [4, 5, 6]])", &config);
+        assert_eq!(extracted, vec![vec![1.0, 5.0]]);
+        
+        // The built matrix feeds the array like any other operand
+        let scaled = simulate("Y = [[1, 1, 1]] @ np.diag([1, 2, 3])", &config);
+        assert_eq!(scaled, vec![vec![1.0, 2.0, 3.0]]);
+    }
+    
+    #[test]
+    fn test_outer() {
+        let config = SystolicConfig::new(2, 8, 32);
+        let source = "M = np.outer([1, 2, 3], [1, 0, -1, 2])";
+        let expected = vec![vec![1.0, 0.0, -1.0, 2.0], vec![2.0, 0.0, -2.0, 4.0], vec![3.0, 0.0, -3.0, 6.0]];
+        assert_eq!(simulate(source, &config), expected);
+        
+        // Lowered to a K = 1 matmul: one pass per output tile
+        let program = crate::compile(source, &config).unwrap();
+        assert_eq!(program.passes.len(), 4);
+        assert_eq!(program.evaluate().unwrap(), expected);
+    }
+    
+    #[test]
+    fn test_expand_dims_and_squeeze() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let source = "A = [[1, 2], [3, 4]]";
+        
+        // A column from a vector multiplies like any matrix
+        let column = simulate(&format!("{}; y = A @ np.expand_dims(A[1], 1)", source), &config);
+        assert_eq!(column, vec![vec![11.0], vec![25.0]]);
+        let squeezed = simulate(&format!("{}; v = np.squeeze(A[:, 0:1] * 2)", source), &config);
+        assert_eq!(squeezed, vec![vec![2.0, 6.0]]);
+    }
+    
+    #[test]
+    fn test_relu() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate("Y = relu([[1, -2], [3, 4]] @ [[1, 0], [0, -1]])", &config);
+        assert_eq!(result, vec![vec![1.0, 2.0], vec![3.0, 0.0]]);
+    }
+
+    #[test]
+    fn test_sigmoid() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let result = simulate("Y = sigmoid([[0, 2, -1]])", &config);
+        let expected = [0.5, 0.8807970779778823, 0.2689414213699951];
+        for (got, want) in result[0].iter().zip(expected) {
+            assert!((got - want).abs() < 1e-12);
+        }
+    }
+}
@@ -8,9 +8,14 @@
 //! - If N > array_size: tile along columns of B
 //! - If K > array_size: accumulate partial products
 
-use crate::ast::{TypedExpr, TypedExprKind, TypedProgram, TypedStatement};
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Activation, Shape, Subscript, TypedExpr, TypedExprKind, TypedProgram, TypedStatement};
 use crate::error::{CompileError, CompileResult};
 use crate::hardware::SystolicConfig;
+use crate::optimizer::{Rewrite, RewriteKind};
 
 /// Tiling strategy for large matrices
 pub struct TilingStrategy {
@@ -22,18 +27,56 @@ impl TilingStrategy {
         Self { config }
     }
     
+    /// Target configuration
+    pub fn config(&self) -> &SystolicConfig {
+        &self.config
+    }
+    
     /// Tile a complete program
     pub fn tile_program(&self, program: &TypedProgram) -> CompileResult<TiledProgram> {
+        // Tile counts divide by the array size, so reject a zero-size array up front
+        self.config.validate()?;
         let mut operations = Vec::new();
         
-        for stmt in &program.statements {
+        // Inputs given with data load like literals, in name order for stable output
+        let mut inputs: Vec<_> = program.input_data.iter().collect();
+        inputs.sort_by_key(|(name, _)| name.as_str());
+        for (name, rows) in inputs {
+            let cols = rows.first().map_or(0, Vec::len);
+            operations.push(TiledOperation::LoadLiteral {
+                target: name.clone(),
+                data: rows.concat(),
+                shape: (rows.len(), cols),
+            });
+        }
+        
+        let live = if self.config.eliminate_dead_statements {
+            live_statements(&program.statements)
+        } else {
+            vec![true; program.statements.len()]
+        };
+        for (stmt, _) in program.statements.iter().zip(live).filter(|(_, live)| *live) {
             let tiled_ops = self.tile_statement(stmt)?;
             operations.extend(tiled_ops);
         }
         
+        for op in &mut operations {
+            if let TiledOperation::LoadMatrix { source, scale, .. } = op {
+                *scale = program.input_scales.get(source.as_str()).copied();
+            }
+        }
+        
         Ok(TiledProgram { operations })
     }
     
+    /// Tile a program, reusing earlier statements' results for repeated subexpressions
+    ///
+    /// `X = A @ B; Y = A @ B + C` tiles `A @ B` once and has `Y` read `X`. A result
+    /// stops being reused once its name or any variable it reads is reassigned.
+    pub fn tile_program_optimized(&self, program: &TypedProgram) -> CompileResult<TiledProgram> {
+        self.tile_program(&reuse_common_subexpressions(program).0)
+    }
+    
     /// Tile a single statement
     fn tile_statement(&self, stmt: &TypedStatement) -> CompileResult<Vec<TiledOperation>> {
         self.tile_expr(&stmt.value, &stmt.target)
@@ -41,11 +84,29 @@ impl TilingStrategy {
     
     /// Tile an expression recursively
     fn tile_expr(&self, expr: &TypedExpr, target: &str) -> CompileResult<Vec<TiledOperation>> {
+        if self.config.fuse_epilogue {
+            if let Some(ops) = self.tile_fused_epilogue(expr, target)? {
+                return Ok(ops);
+            }
+        }
         match &expr.expr {
             TypedExprKind::MatMul(left, right) => {
                 self.tile_matmul(left, right, target)
             }
+            TypedExprKind::Outer(left, right) => {
+                // A K = 1 matmul of `left` as a column by `right` as a row, both read in place
+                let as_matrix = |vector: &TypedExpr, rows: usize, cols: usize| TypedExpr {
+                    expr: TypedExprKind::Reshape(Box::new(vector.clone())),
+                    shape: Shape::matrix(rows, cols),
+                };
+                let (m, n) = expr.shape.dimensions()
+                    .ok_or_else(|| unknown_shape(expr, &format!("the outer product {}", target)))?;
+                self.tile_matmul(&as_matrix(left, m, 1), &as_matrix(right, 1, n), target)
+            }
             TypedExprKind::Add(left, right) => {
+                if let Some((product, residual)) = self.residual_operands(left, right) {
+                    return self.tile_residual_matmul(product, residual, target);
+                }
                 let mut ops = self.tile_expr(left, &format!("{}_add_left", target))?;
                 ops.extend(self.tile_expr(right, &format!("{}_add_right", target))?);
                 ops.push(TiledOperation::Add {
@@ -53,6 +114,7 @@ impl TilingStrategy {
                     left: format!("{}_add_left", target),
                     right: format!("{}_add_right", target),
                     shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                    broadcast: Broadcast::between(&left.shape, &right.shape, &expr.shape),
                 });
                 Ok(ops)
             }
@@ -64,10 +126,15 @@ impl TilingStrategy {
                     left: format!("{}_sub_left", target),
                     right: format!("{}_sub_right", target),
                     shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                    broadcast: Broadcast::between(&left.shape, &right.shape, &expr.shape),
                 });
                 Ok(ops)
             }
+            TypedExprKind::Grouped(inner) => self.tile_expr(inner, target),
             TypedExprKind::Transpose(inner) => {
+                if let Some(ops) = self.tile_transposed_matmul(inner, target)? {
+                    return Ok(ops);
+                }
                 let mut ops = self.tile_expr(inner, &format!("{}_transpose_inner", target))?;
                 ops.push(TiledOperation::Transpose {
                     target: target.to_string(),
@@ -76,11 +143,153 @@ impl TilingStrategy {
                 });
                 Ok(ops)
             }
+            TypedExprKind::Index(inner, subscripts) => {
+                let (rows, cols) = inner.shape.dimensions()
+                    .ok_or_else(|| CompileError::tiling("Cannot index an operand of unknown shape"))?;
+                
+                // Vectors are stored as a single row, so their only subscript is the column
+                let (row_sub, col_sub) = match (&inner.shape, subscripts.as_slice()) {
+                    (Shape::Vector(_), [col]) => (Subscript::Index(0), col.clone()),
+                    (_, [row]) => (row.clone(), Subscript::full()),
+                    (_, [row, col]) => (row.clone(), col.clone()),
+                    _ => return Err(CompileError::tiling("Invalid subscript count")),
+                };
+                
+                let mut ops = self.tile_expr(inner, &format!("{}_index_source", target))?;
+                ops.push(TiledOperation::Index {
+                    target: target.to_string(),
+                    source: format!("{}_index_source", target),
+                    rows: row_sub.indices(rows),
+                    cols: col_sub.indices(cols),
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Activation(kind, inner) => {
+                // An activation straight off a matmul is always applied on its final passes;
+                // longer chains are fused only with `fuse_epilogue`
+                if let TypedExprKind::MatMul(..) = inner.expr {
+                    if let Some(ops) = self.tile_fused_epilogue(expr, target)? {
+                        return Ok(ops);
+                    }
+                }
+                let mut ops = self.tile_expr(inner, &format!("{}_act_source", target))?;
+                ops.push(TiledOperation::Activation {
+                    target: target.to_string(),
+                    source: format!("{}_act_source", target),
+                    kind: *kind,
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Reshape(inner) => {
+                let mut ops = self.tile_expr(inner, &format!("{}_reshape_source", target))?;
+                ops.push(TiledOperation::Reshape {
+                    target: target.to_string(),
+                    source: format!("{}_reshape_source", target),
+                    source_shape: inner.shape.dimensions().unwrap_or((0, 0)),
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Split { source, axis, sections, part } => {
+                let mut ops = self.tile_expr(source, &format!("{}_split_source", target))?;
+                ops.push(TiledOperation::Split {
+                    target: target.to_string(),
+                    source: format!("{}_split_source", target),
+                    // Vectors are stored as a single row, so their only axis is the column axis
+                    axis: if source.shape.is_vector() { 1 } else { *axis },
+                    sections: *sections,
+                    part: *part,
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Concat { left, right, axis } => {
+                let mut ops = self.tile_expr(left, &format!("{}_concat_left", target))?;
+                ops.extend(self.tile_expr(right, &format!("{}_concat_right", target))?);
+                ops.push(TiledOperation::Concat {
+                    target: target.to_string(),
+                    left: format!("{}_concat_left", target),
+                    right: format!("{}_concat_right", target),
+                    // Vectors are stored as a single row, so they join along the column axis
+                    axis: if expr.shape.is_vector() { 1 } else { *axis },
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Out(buffer, inner) => {
+                // Compute straight into the out= buffer, then alias it as the result
+                let mut ops = self.tile_expr(inner, buffer)?;
+                if buffer != target {
+                    ops.push(TiledOperation::LoadMatrix {
+                        target: target.to_string(),
+                        source: buffer.clone(),
+                        shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                        scale: None,
+                    });
+                }
+                Ok(ops)
+            }
+            TypedExprKind::Roll { source, shift, axis } => {
+                let mut ops = self.tile_expr(source, &format!("{}_roll_source", target))?;
+                ops.push(TiledOperation::Roll {
+                    target: target.to_string(),
+                    source: format!("{}_roll_source", target),
+                    shift: *shift,
+                    // Vectors are stored as a single row, so their only axis is the column axis
+                    axis: axis.map(|axis| if source.shape.is_vector() { 1 } else { axis }),
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Norm { source, axis } => {
+                let mut ops = self.tile_expr(source, &format!("{}_norm_source", target))?;
+                ops.push(TiledOperation::Norm {
+                    target: target.to_string(),
+                    source: format!("{}_norm_source", target),
+                    axis: *axis,
+                    source_shape: source.shape.dimensions().unwrap_or((0, 0)),
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Diag(source) => {
+                let mut ops = self.tile_expr(source, &format!("{}_diag_source", target))?;
+                ops.push(TiledOperation::Diag {
+                    target: target.to_string(),
+                    source: format!("{}_diag_source", target),
+                    source_shape: source.shape.dimensions().unwrap_or((0, 0)),
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Clip { source, min, max } => {
+                let mut ops = self.tile_expr(source, &format!("{}_clip_source", target))?;
+                ops.push(TiledOperation::Clip {
+                    target: target.to_string(),
+                    source: format!("{}_clip_source", target),
+                    min: *min,
+                    max: *max,
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
+            TypedExprKind::Let { name, value, body } => {
+                // Materialize the binding once under a name private to this statement
+                let scoped = format!("{}_let_{}", target, name);
+                let mut ops = self.tile_expr(value, &scoped)?;
+                let mut body = body.as_ref().clone();
+                body.rename(name, &scoped);
+                ops.extend(self.tile_expr(&body, target)?);
+                Ok(ops)
+            }
             TypedExprKind::Variable(name) => {
                 Ok(vec![TiledOperation::LoadMatrix {
                     target: target.to_string(),
                     source: name.clone(),
                     shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                    scale: None,
                 }])
             }
             TypedExprKind::Matrix(data) => {
@@ -112,6 +321,18 @@ impl TilingStrategy {
                 });
                 Ok(ops)
             }
+            TypedExprKind::Div(left, right) => {
+                // Element-wise division (not for systolic array)
+                let mut ops = self.tile_expr(left, &format!("{}_div_left", target))?;
+                ops.extend(self.tile_expr(right, &format!("{}_div_right", target))?);
+                ops.push(TiledOperation::ElementDiv {
+                    target: target.to_string(),
+                    left: format!("{}_div_left", target),
+                    right: format!("{}_div_right", target),
+                    shape: expr.shape.dimensions().unwrap_or((0, 0)),
+                });
+                Ok(ops)
+            }
             TypedExprKind::ScalarMul(scalar, matrix) => {
                 let mut ops = self.tile_expr(matrix, &format!("{}_smul_matrix", target))?;
                 if let TypedExprKind::Scalar(s) = &scalar.expr {
@@ -127,17 +348,216 @@ impl TilingStrategy {
         }
     }
     
+    /// The matmul and residual of `left + right`, if the add can be fused into the matmul
+    fn residual_operands<'a>(&self, left: &'a TypedExpr, right: &'a TypedExpr) -> Option<(&'a TypedExpr, &'a TypedExpr)> {
+        if !self.config.fuse_residual {
+            return None;
+        }
+        let (product, residual) = match (&left.expr, &right.expr) {
+            (TypedExprKind::MatMul(..), _) => (left, right),
+            (_, TypedExprKind::MatMul(..)) => (right, left),
+            _ => return None,
+        };
+        // Only a full-size matrix residual maps onto the accumulator tiles
+        (product.shape.is_matrix() && product.shape == residual.shape).then_some((product, residual))
+    }
+    
+    /// Tile `product + residual` as one matmul whose accumulators start from the residual
+    fn tile_residual_matmul(&self, product: &TypedExpr, residual: &TypedExpr, target: &str) -> CompileResult<Vec<TiledOperation>> {
+        let TypedExprKind::MatMul(left, right) = &product.expr else {
+            return Err(CompileError::tiling("Residual fusion expects a matmul"));
+        };
+        let residual_target = format!("{}_residual", target);
+        let mut ops = self.tile_expr(residual, &residual_target)?;
+        for mut op in self.tile_matmul(left, right, target)? {
+            if let TiledOperation::TiledMatMul { accumulate, .. } = &mut op {
+                *accumulate = Some(residual_target.clone());
+            }
+            ops.push(op);
+        }
+        Ok(ops)
+    }
+    
+    /// Tile `(left @ right).T` as `right.T @ left.T`, reading both operands transposed
+    ///
+    /// The transpose costs nothing: passes just address the operand buffers column-wise.
+    /// Returns `None` unless `expr` is a matrix-by-matrix matmul.
+    fn tile_transposed_matmul(&self, expr: &TypedExpr, target: &str) -> CompileResult<Option<Vec<TiledOperation>>> {
+        let TypedExprKind::MatMul(left, right) = &expr.expr else {
+            return Ok(None);
+        };
+        if !expr.shape.is_matrix() || !right.shape.is_matrix() {
+            return Ok(None);
+        }
+        let mut ops = self.tile_matmul(left, right, target)?;
+        for op in &mut ops {
+            let TiledOperation::TiledMatMul {
+                target: op_target, left_source, right_source, left_shape, right_shape, output_shape, tiles, transposed, ..
+            } = op else {
+                continue;
+            };
+            if op_target != target {
+                continue;
+            }
+            std::mem::swap(left_source, right_source);
+            let (m, k, n) = (left_shape.0, left_shape.1, right_shape.1);
+            (*left_shape, *right_shape, *output_shape) = ((n, k), (k, m), (n, m));
+            // Keep the configured output tile order over the swapped grid; K tiles stay consecutive
+            let grid = (n.div_ceil(self.config.array_size), m.div_ceil(self.config.array_size));
+            let original = std::mem::take(tiles);
+            for (i, j) in self.config.tile_order.output_tiles(grid.0, grid.1) {
+                tiles.extend(original.iter().filter(|tile| (tile.output_row, tile.output_col) == (j, i)).map(MatMulTile::transposed));
+            }
+            *transposed = true;
+        }
+        Ok(Some(ops))
+    }
+    
+    /// Tile an element-wise chain ending in a matmul as that matmul plus an epilogue
+    ///
+    /// Returns `None` if `expr` is not such a chain, so it is tiled normally.
+    fn tile_fused_epilogue(&self, expr: &TypedExpr, target: &str) -> CompileResult<Option<Vec<TiledOperation>>> {
+        let (mut stages, mut biases) = (Vec::new(), Vec::new());
+        let Some((left, right)) = self.epilogue_chain(expr, target, &mut stages, &mut biases) else {
+            return Ok(None);
+        };
+        if stages.is_empty() {
+            return Ok(None);
+        }
+        let mut ops = Vec::new();
+        for (name, bias) in biases {
+            ops.extend(self.tile_expr(bias, &name)?);
+        }
+        for mut op in self.tile_matmul(left, right, target)? {
+            if let TiledOperation::TiledMatMul { epilogue, .. } = &mut op {
+                *epilogue = stages.clone();
+            }
+            ops.push(op);
+        }
+        Ok(Some(ops))
+    }
+    
+    /// Peel shape-preserving element-wise steps off `expr` down to a matrix-output matmul
+    ///
+    /// Returns the matmul operands; `stages` gets the steps innermost first and
+    /// `biases` the operands of bias adds, under the buffer names the stages read.
+    fn epilogue_chain<'a>(
+        &self,
+        expr: &'a TypedExpr,
+        target: &str,
+        stages: &mut Vec<EpilogueStage>,
+        biases: &mut Vec<(String, &'a TypedExpr)>,
+    ) -> Option<(&'a TypedExpr, &'a TypedExpr)> {
+        let (inner, stage) = match &expr.expr {
+            TypedExprKind::MatMul(left, right) => {
+                return (expr.shape.is_matrix() && right.shape.is_matrix()).then_some((left, right));
+            }
+            TypedExprKind::Activation(kind, inner) => (inner, EpilogueStage::Activation(*kind)),
+            TypedExprKind::Clip { source, min, max } => (source, EpilogueStage::Clip { min: *min, max: *max }),
+            TypedExprKind::ScalarMul(scalar, inner) => match scalar.expr {
+                TypedExprKind::Scalar(s) => (inner, EpilogueStage::Scale(s)),
+                _ => return None,
+            },
+            TypedExprKind::Add(left, right) => {
+                for (chain, bias) in [(left, right), (right, left)] {
+                    // A bias must line up with the output tiles, or broadcast a row, column or element
+                    let fits = bias.shape.stretches_to(&expr.shape);
+                    let (stage_count, bias_count) = (stages.len(), biases.len());
+                    if let Some(operands) = fits.then(|| self.epilogue_chain(chain, target, stages, biases)).flatten() {
+                        let name = format!("{}_bias{}", target, biases.len());
+                        stages.push(EpilogueStage::Bias(name.clone()));
+                        biases.push((name, bias));
+                        return Some(operands);
+                    }
+                    stages.truncate(stage_count);
+                    biases.truncate(bias_count);
+                }
+                return None;
+            }
+            _ => return None,
+        };
+        let operands = self.epilogue_chain(inner, target, stages, biases)?;
+        stages.push(stage);
+        Some(operands)
+    }
+    
+    /// Tile a matmul operand, fusing away a reshape feeding it
+    ///
+    /// Reshapes keep row-major order, and passes index their operands by the
+    /// matmul's shapes, so the reshape's source can be read in place.
+    fn tile_operand(&self, expr: &TypedExpr, target: &str) -> CompileResult<Vec<TiledOperation>> {
+        if let TypedExprKind::Reshape(inner) = &expr.expr {
+            let elements = |shape: &Shape| shape.dimensions().map(|(rows, cols)| rows * cols);
+            if elements(&inner.shape).is_some() && elements(&inner.shape) == elements(&expr.shape) {
+                return self.tile_operand(inner, target);
+            }
+        }
+        self.tile_expr(expr, target)
+    }
+    
     /// Tile a matrix multiplication operation
     fn tile_matmul(
         &self,
         left: &TypedExpr,
         right: &TypedExpr,
         target: &str,
+    ) -> CompileResult<Vec<TiledOperation>> {
+        if self.config.reorder_matmul_chains {
+            if let Some(TypedExprKind::MatMul(left, right)) = self.reorder_chain(left, right).map(|root| root.expr) {
+                return self.tile_matmul_in_order(&left, &right, target);
+            }
+        }
+        self.tile_matmul_in_order(left, right, target)
+    }
+    
+    /// Regroup the `@` chain `left @ right` into the association needing the fewest tiles
+    ///
+    /// Classic matrix-chain-order DP, costing each product by its passes rather than its
+    /// multiplies. Returns `None` unless the chain has at least three matrix operands.
+    fn reorder_chain(&self, left: &TypedExpr, right: &TypedExpr) -> Option<TypedExpr> {
+        let mut operands = Vec::new();
+        chain_operands(left, &mut operands);
+        chain_operands(right, &mut operands);
+        if operands.len() < 3 || !operands.iter().all(|operand| operand.shape.is_matrix()) {
+            return None;
+        }
+        let mut dims = vec![operands[0].shape.dimensions()?.0];
+        dims.extend(operands.iter().map(|operand| operand.shape.dimensions().map_or(0, |(_, cols)| cols)));
+        
+        let n = self.config.array_size;
+        let tiles = |i: usize, k: usize, j: usize| dims[i].div_ceil(n) * dims[k].div_ceil(n) * dims[j].div_ceil(n);
+        let count = operands.len();
+        // cost[i][j]: fewest tiles for operands i..=j; split[i][j]: where that product divides
+        let mut cost = vec![vec![0usize; count]; count];
+        let mut split = vec![vec![0usize; count]; count];
+        for len in 2..=count {
+            for i in 0..=count - len {
+                let j = i + len - 1;
+                let (best, at) = (i..j)
+                    .map(|s| (cost[i][s] + cost[s + 1][j] + tiles(i, s + 1, j + 1), s))
+                    .min()?;
+                cost[i][j] = best;
+                split[i][j] = at;
+            }
+        }
+        Some(chain_product(&operands, &split, 0, count - 1))
+    }
+    
+    /// Tile `left @ right` as written
+    fn tile_matmul_in_order(
+        &self,
+        left: &TypedExpr,
+        right: &TypedExpr,
+        target: &str,
     ) -> CompileResult<Vec<TiledOperation>> {
         let (m, k1) = left.shape.dimensions()
-            .ok_or_else(|| CompileError::tiling("Unknown left operand shape"))?;
-        let (k2, n) = right.shape.dimensions()
-            .ok_or_else(|| CompileError::tiling("Unknown right operand shape"))?;
+            .ok_or_else(|| unknown_shape(left, &format!("the left operand of {}", target)))?;
+        let (k2, n) = match right.shape {
+            // A vector right operand is a single column; its row-major data is unchanged
+            Shape::Vector(len) => (len, 1),
+            _ => right.shape.dimensions()
+                .ok_or_else(|| unknown_shape(right, &format!("the right operand of {}", target)))?,
+        };
         
         if k1 != k2 {
             return Err(CompileError::tiling(format!(
@@ -150,48 +570,75 @@ impl TilingStrategy {
         let tile_size = self.config.array_size;
         
         // Calculate number of tiles needed
-        let m_tiles = (m + tile_size - 1) / tile_size;
-        let n_tiles = (n + tile_size - 1) / tile_size;
-        let k_tiles = (k + tile_size - 1) / tile_size;
+        let m_tiles = m.div_ceil(tile_size);
+        let n_tiles = n.div_ceil(tile_size);
+        let k_tiles = k.div_ceil(tile_size);
+        
+        // Refuse before allocating anything: a tiny array on a large matmul would never finish
+        let passes = m_tiles.checked_mul(n_tiles).and_then(|tiles| tiles.checked_mul(k_tiles));
+        if self.config.max_passes > 0 && passes.is_none_or(|passes| passes > self.config.max_passes) {
+            return Err(CompileError::tiling(format!(
+                "{} ({}x{} @ {}x{}) would need {} passes on a {}x{} array, exceeding the limit of {}",
+                target,
+                m,
+                k,
+                k,
+                n,
+                passes.map_or_else(|| "more than usize::MAX".to_string(), |passes| passes.to_string()),
+                tile_size,
+                tile_size,
+                self.config.max_passes
+            )));
+        }
+        // K steps summed on chip before the partial sums go to DRAM
+        let k_group = match self.config.accumulator_k_tiles {
+            0 => k_tiles,
+            limit => limit,
+        };
         
         let mut operations = Vec::new();
         
-        // First, process operands
-        let left_ops = self.tile_expr(left, &format!("{}_left", target))?;
-        let right_ops = self.tile_expr(right, &format!("{}_right", target))?;
+        // First, process operands. A nested matmul (as in `A @ B @ C`) becomes its own
+        // group writing `{target}_left`, so codegen feeds its computed tiles to this one
+        let left_ops = self.tile_operand(left, &format!("{}_left", target))?;
+        let right_ops = self.tile_operand(right, &format!("{}_right", target))?;
         operations.extend(left_ops);
         operations.extend(right_ops);
         
         // Generate tiled matrix multiplication
         let mut tiles = Vec::new();
         
-        for i in 0..m_tiles {
-            for j in 0..n_tiles {
-                for kk in 0..k_tiles {
-                    let tile_m_start = i * tile_size;
-                    let tile_m_end = ((i + 1) * tile_size).min(m);
-                    let tile_n_start = j * tile_size;
-                    let tile_n_end = ((j + 1) * tile_size).min(n);
-                    let tile_k_start = kk * tile_size;
-                    let tile_k_end = ((kk + 1) * tile_size).min(k);
-                    
-                    tiles.push(MatMulTile {
-                        output_row: i,
-                        output_col: j,
-                        k_index: kk,
-                        a_row_range: (tile_m_start, tile_m_end),
-                        a_col_range: (tile_k_start, tile_k_end),
-                        b_row_range: (tile_k_start, tile_k_end),
-                        b_col_range: (tile_n_start, tile_n_end),
-                        is_first_k: kk == 0,
-                        is_last_k: kk == k_tiles - 1,
-                    });
-                }
+        for (i, j) in self.config.tile_order.output_tiles(m_tiles, n_tiles) {
+            for kk in 0..k_tiles {
+                let tile_m_start = i * tile_size;
+                let tile_m_end = ((i + 1) * tile_size).min(m);
+                let tile_n_start = j * tile_size;
+                let tile_n_end = ((j + 1) * tile_size).min(n);
+                let tile_k_start = kk * tile_size;
+                let tile_k_end = ((kk + 1) * tile_size).min(k);
+                
+                tiles.push(MatMulTile {
+                    output_row: i,
+                    output_col: j,
+                    k_index: kk,
+                    a_row_range: (tile_m_start, tile_m_end),
+                    a_col_range: (tile_k_start, tile_k_end),
+                    b_row_range: (tile_k_start, tile_k_end),
+                    b_col_range: (tile_n_start, tile_n_end),
+                    is_first_k: kk == 0,
+                    is_last_k: kk == k_tiles - 1,
+                    spill: kk + 1 < k_tiles && (kk + 1) % k_group == 0,
+                    reload: kk > 0 && kk % k_group == 0,
+                });
             }
         }
         
+        // A matrix-vector product comes off the array as a column; view it as a vector
+        let column_target = format!("{}_column", target);
+        let matmul_target = if right.shape.is_vector() { &column_target } else { target };
+        
         operations.push(TiledOperation::TiledMatMul {
-            target: target.to_string(),
+            target: matmul_target.to_string(),
             left_source: format!("{}_left", target),
             right_source: format!("{}_right", target),
             left_shape: (m, k),
@@ -199,26 +646,63 @@ impl TilingStrategy {
             output_shape: (m, n),
             tiles,
             tile_size,
+            accumulate: None,
+            epilogue: Vec::new(),
+            transposed: false,
         });
         
+        if right.shape.is_vector() {
+            operations.push(TiledOperation::Reshape {
+                target: target.to_string(),
+                source: column_target,
+                source_shape: (m, 1),
+                shape: (1, m),
+            });
+        }
+        
         Ok(operations)
     }
 }
 
 /// A tiled program ready for code generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TiledProgram {
     pub operations: Vec<TiledOperation>,
 }
 
+impl TiledProgram {
+    /// Save to JSON, e.g. to cache tiling between builds
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+    
+    /// Load a program saved with `to_json`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+    
+    /// Number of systolic passes code generation will emit, one per matmul tile
+    pub fn estimated_passes(&self) -> usize {
+        self.operations
+            .iter()
+            .map(|op| match op {
+                TiledOperation::TiledMatMul { tiles, .. } => tiles.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
 /// A single tiled operation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TiledOperation {
     /// Load a matrix from memory/variable
     LoadMatrix {
         target: String,
         source: String,
         shape: (usize, usize),
+        /// Quantization scale the source was defined with
+        scale: Option<f64>,
     },
     /// Load a literal matrix
     LoadLiteral {
@@ -236,6 +720,14 @@ pub enum TiledOperation {
         output_shape: (usize, usize),
         tiles: Vec<MatMulTile>,
         tile_size: usize,
+        /// Buffer the accumulators start from instead of zero (a fused residual add)
+        accumulate: Option<String>,
+        /// Element-wise steps applied as the final passes drain, in order
+        epilogue: Vec<EpilogueStage>,
+        /// Each operand buffer holds the transpose of its shape here, so passes read it
+        /// column-wise (a folded `(A @ B).T`)
+        #[serde(default)]
+        transposed: bool,
     },
     /// Element-wise addition
     Add {
@@ -243,6 +735,7 @@ pub enum TiledOperation {
         left: String,
         right: String,
         shape: (usize, usize),
+        broadcast: Option<Broadcast>,
     },
     /// Element-wise subtraction
     Sub {
@@ -250,6 +743,7 @@ pub enum TiledOperation {
         left: String,
         right: String,
         shape: (usize, usize),
+        broadcast: Option<Broadcast>,
     },
     /// Element-wise multiplication
     ElementMul {
@@ -258,6 +752,13 @@ pub enum TiledOperation {
         right: String,
         shape: (usize, usize),
     },
+    /// Element-wise division
+    ElementDiv {
+        target: String,
+        left: String,
+        right: String,
+        shape: (usize, usize),
+    },
     /// Scalar multiplication
     ScalarMul {
         target: String,
@@ -271,10 +772,394 @@ pub enum TiledOperation {
         source: String,
         shape: (usize, usize),
     },
+    /// Reinterpret row-major data with a new shape (metadata only)
+    Reshape {
+        target: String,
+        source: String,
+        source_shape: (usize, usize),
+        shape: (usize, usize),
+    },
+    /// Element-wise activation, run on the host as an epilogue
+    Activation {
+        target: String,
+        source: String,
+        kind: Activation,
+        shape: (usize, usize),
+    },
+    /// Part `part` of `sections` equal blocks of the source along `axis`
+    Split {
+        target: String,
+        source: String,
+        axis: usize,
+        sections: usize,
+        part: usize,
+        shape: (usize, usize),
+    },
+    /// `left` followed by `right` along `axis`
+    Concat {
+        target: String,
+        left: String,
+        right: String,
+        axis: usize,
+        shape: (usize, usize),
+    },
+    /// Cyclic shift by `shift` along `axis`, or over the flattened data when `None`
+    Roll {
+        target: String,
+        source: String,
+        shift: i64,
+        axis: Option<usize>,
+        shape: (usize, usize),
+    },
+    /// Square root of the sum of squares along `axis`, or of every element when `None`
+    Norm {
+        target: String,
+        source: String,
+        axis: Option<usize>,
+        source_shape: (usize, usize),
+        shape: (usize, usize),
+    },
+    /// Diagonal matrix built from a one-row source, or the diagonal extracted into one row
+    Diag {
+        target: String,
+        source: String,
+        source_shape: (usize, usize),
+        shape: (usize, usize),
+    },
+    /// Element-wise clamp into `[min, max]`
+    Clip {
+        target: String,
+        source: String,
+        min: f64,
+        max: f64,
+        shape: (usize, usize),
+    },
+    /// Gather the selected rows/columns, in order (indexing or slicing)
+    Index {
+        target: String,
+        source: String,
+        rows: Vec<usize>,
+        cols: Vec<usize>,
+        shape: (usize, usize),
+    },
+}
+
+impl TiledOperation {
+    /// Name of the buffer this operation produces
+    pub fn target(&self) -> &str {
+        match self {
+            TiledOperation::LoadMatrix { target, .. }
+            | TiledOperation::LoadLiteral { target, .. }
+            | TiledOperation::TiledMatMul { target, .. }
+            | TiledOperation::Add { target, .. }
+            | TiledOperation::Sub { target, .. }
+            | TiledOperation::ElementMul { target, .. }
+            | TiledOperation::ElementDiv { target, .. }
+            | TiledOperation::ScalarMul { target, .. }
+            | TiledOperation::Transpose { target, .. }
+            | TiledOperation::Reshape { target, .. }
+            | TiledOperation::Activation { target, .. }
+            | TiledOperation::Index { target, .. }
+            | TiledOperation::Split { target, .. }
+            | TiledOperation::Concat { target, .. }
+            | TiledOperation::Roll { target, .. }
+            | TiledOperation::Norm { target, .. }
+            | TiledOperation::Diag { target, .. }
+            | TiledOperation::Clip { target, .. } => target,
+        }
+    }
+    
+    /// Names of the buffers this operation reads
+    pub fn sources(&self) -> Vec<&str> {
+        match self {
+            TiledOperation::LoadLiteral { .. } => vec![],
+            TiledOperation::TiledMatMul { left_source, right_source, accumulate, epilogue, .. } => {
+                let mut sources = vec![left_source.as_str(), right_source.as_str()];
+                sources.extend(accumulate.as_deref());
+                sources.extend(epilogue.iter().filter_map(|stage| match stage {
+                    EpilogueStage::Bias(name) => Some(name.as_str()),
+                    _ => None,
+                }));
+                sources
+            }
+            TiledOperation::Add { left, right, .. }
+            | TiledOperation::Sub { left, right, .. }
+            | TiledOperation::ElementMul { left, right, .. }
+            | TiledOperation::ElementDiv { left, right, .. }
+            | TiledOperation::Concat { left, right, .. } => vec![left, right],
+            TiledOperation::LoadMatrix { source, .. }
+            | TiledOperation::ScalarMul { source, .. }
+            | TiledOperation::Transpose { source, .. }
+            | TiledOperation::Reshape { source, .. }
+            | TiledOperation::Activation { source, .. }
+            | TiledOperation::Index { source, .. }
+            | TiledOperation::Split { source, .. }
+            | TiledOperation::Roll { source, .. }
+            | TiledOperation::Norm { source, .. }
+            | TiledOperation::Diag { source, .. }
+            | TiledOperation::Clip { source, .. } => vec![source],
+        }
+    }
+}
+
+/// Error for an operand whose shape the analyzer never inferred, blaming the first
+/// variable of unknown shape inside it, or else `what`
+fn unknown_shape(operand: &TypedExpr, what: &str) -> CompileError {
+    let mut unknown = None;
+    operand.visit(&mut |expr| {
+        if let (TypedExprKind::Variable(name), Shape::Unknown) = (&expr.expr, &expr.shape) {
+            unknown.get_or_insert_with(|| name.clone());
+        }
+    });
+    match unknown {
+        Some(name) => CompileError::tiling(format!("shape of {} is unknown; define it with --shape {}=RxC", name, name)),
+        None => CompileError::tiling(format!("shape of {} is unknown", what)),
+    }
+}
+
+/// Operands of an `@` chain in order, stopping at parenthesized groups
+fn chain_operands<'a>(expr: &'a TypedExpr, operands: &mut Vec<&'a TypedExpr>) {
+    match &expr.expr {
+        TypedExprKind::MatMul(left, right) => {
+            chain_operands(left, operands);
+            chain_operands(right, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+/// Product of `operands[i..=j]`, grouped as `split` says
+fn chain_product(operands: &[&TypedExpr], split: &[Vec<usize>], i: usize, j: usize) -> TypedExpr {
+    if i == j {
+        return operands[i].clone();
+    }
+    let left = chain_product(operands, split, i, split[i][j]);
+    let right = chain_product(operands, split, split[i][j] + 1, j);
+    let shape = match (left.shape.dimensions(), right.shape.dimensions()) {
+        (Some((rows, _)), Some((_, cols))) => Shape::matrix(rows, cols),
+        _ => Shape::Unknown,
+    };
+    TypedExpr { expr: TypedExprKind::MatMul(Box::new(left), Box::new(right)), shape }
+}
+
+/// Have statements read an earlier statement's result instead of recomputing it
+///
+/// Also returns each subexpression replaced, as a rewrite to the variable read.
+pub(crate) fn reuse_common_subexpressions(program: &TypedProgram) -> (TypedProgram, Vec<Rewrite>) {
+    // Statement values computed so far, with the buffer holding each
+    let mut available: Vec<(TypedExpr, String)> = Vec::new();
+    let mut statements = Vec::new();
+    let mut rewrites = Vec::new();
+    
+    for stmt in &program.statements {
+        let mut value = stmt.value.clone();
+        value.replace_with(&mut |expr| {
+            let (_, name) = available.iter().find(|(computed, name)| computed == expr && *name != stmt.target)?;
+            let read = TypedExpr { expr: TypedExprKind::Variable(name.clone()), shape: expr.shape.clone() };
+            rewrites.push(Rewrite {
+                statement: stmt.target.clone(),
+                kind: RewriteKind::CommonSubexpressionElimination,
+                before: expr.clone(),
+                after: read.clone(),
+            });
+            Some(read)
+        });
+        
+        let assigned = assigned_names(stmt);
+        available.retain(|(computed, name)| {
+            !assigned.iter().any(|var| var == name || computed.references(var))
+        });
+        let is_leaf = matches!(
+            stmt.value.expr,
+            TypedExprKind::Variable(_) | TypedExprKind::Scalar(_) | TypedExprKind::Matrix(_)
+        );
+        if !is_leaf && !assigned.iter().any(|var| stmt.value.references(var)) {
+            available.push((stmt.value.clone(), stmt.target.clone()));
+        }
+        
+        statements.push(TypedStatement { target: stmt.target.clone(), value });
+    }
+    
+    let program = TypedProgram {
+        statements,
+        input_scales: program.input_scales.clone(),
+        input_data: program.input_data.clone(),
+    };
+    (program, rewrites)
+}
+
+/// The target and any out= buffers a statement overwrites
+fn assigned_names(stmt: &TypedStatement) -> Vec<String> {
+    let mut assigned = vec![stmt.target.clone()];
+    stmt.value.visit(&mut |expr| {
+        if let TypedExprKind::Out(buffer, _) = &expr.expr {
+            assigned.push(buffer.clone());
+        }
+    });
+    assigned
+}
+
+/// Which statements produce a value the final statement depends on
+///
+/// Walks backwards from the last statement, which is always live. Statements are
+/// pure, so one whose assigned names are all unread before being reassigned is dead.
+pub(crate) fn live_statements(statements: &[TypedStatement]) -> Vec<bool> {
+    let mut needed: HashSet<String> = statements.last().map(|stmt| stmt.target.clone()).into_iter().collect();
+    let mut live = vec![false; statements.len()];
+    for (i, stmt) in statements.iter().enumerate().rev() {
+        let assigned = assigned_names(stmt);
+        if !assigned.iter().any(|name| needed.contains(name)) {
+            continue;
+        }
+        live[i] = true;
+        assigned.iter().for_each(|name| {
+            needed.remove(name);
+        });
+        stmt.value.visit(&mut |expr| {
+            if let TypedExprKind::Variable(name) = &expr.expr {
+                needed.insert(name.clone());
+            }
+        });
+    }
+    live
+}
+
+/// An element-wise operand repeated to fill the result shape
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Broadcast {
+    /// The right operand is repeated rather than the left
+    pub right: bool,
+    /// Axis the operand repeats along, or `None` when a single element fills the result
+    pub axis: Option<usize>,
+}
+
+impl Broadcast {
+    /// How `left` or `right` stretches to `result`, or `None` if both already match it
+    pub fn between(left: &Shape, right: &Shape, result: &Shape) -> Option<Broadcast> {
+        let (rows, cols) = result.dimensions()?;
+        let (right_side, (from_rows, from_cols)) = match (left.dimensions()?, right.dimensions()?) {
+            (_, dims) if dims != (rows, cols) => (true, dims),
+            (dims, _) if dims != (rows, cols) => (false, dims),
+            _ => return None,
+        };
+        let axis = match (from_rows < rows, from_cols < cols) {
+            (true, true) => None,
+            (true, false) => Some(0),
+            (false, true) => Some(1),
+            (false, false) => return None,
+        };
+        Some(Broadcast { right: right_side, axis })
+    }
+}
+
+/// One element-wise step fused onto the final passes of a matmul
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EpilogueStage {
+    /// Add the named buffer, broadcasting a row, column or single element
+    Bias(String),
+    /// Multiply by a constant
+    Scale(f64),
+    Activation(Activation),
+    /// Clamp into `[min, max]`
+    Clip { min: f64, max: f64 },
+}
+
+/// Source rows and columns of split part `part`, whose blocks have shape `shape`
+pub fn split_block(axis: usize, part: usize, shape: (usize, usize)) -> (Vec<usize>, Vec<usize>) {
+    let (rows, cols) = shape;
+    if axis == 0 {
+        ((part * rows..(part + 1) * rows).collect(), (0..cols).collect())
+    } else {
+        ((0..rows).collect(), (part * cols..(part + 1) * cols).collect())
+    }
+}
+
+/// Row-major data of `left` followed by `right` along `axis`
+///
+/// Along axis 0 the rows stack; along axis 1 each output row joins the two input rows.
+pub fn concat_data(left: &[f64], right: &[f64], axis: usize, shape: (usize, usize)) -> Vec<f64> {
+    if axis == 0 {
+        return left.iter().chain(right).copied().collect();
+    }
+    let rows = shape.0.max(1);
+    let (left_cols, right_cols) = (left.len() / rows, right.len() / rows);
+    (0..shape.0)
+        .flat_map(|i| {
+            left[i * left_cols..(i + 1) * left_cols]
+                .iter()
+                .chain(&right[i * right_cols..(i + 1) * right_cols])
+                .copied()
+        })
+        .collect()
+}
+
+/// Row-major data of a `shape` matrix cyclically shifted by `shift` along `axis`
+///
+/// Elements pushed off the end wrap around to the start; a negative shift moves them
+/// the other way. Without an axis the flattened data is shifted.
+pub fn roll_data(data: &[f64], shift: i64, axis: Option<usize>, shape: (usize, usize)) -> Vec<f64> {
+    let (rows, cols) = shape;
+    let back = |i: usize, len: usize| (i as i64 - shift).rem_euclid(len as i64) as usize;
+    (0..rows * cols)
+        .map(|idx| {
+            let (i, j) = (idx / cols, idx % cols);
+            let from = match axis {
+                None => back(idx, rows * cols),
+                Some(0) => back(i, rows) * cols + j,
+                Some(_) => i * cols + back(j, cols),
+            };
+            data[from]
+        })
+        .collect()
+}
+
+/// L2 norms of row-major `data` along `axis` (0 gives one per column), or of all of it
+pub fn norm_data(data: &[f64], axis: Option<usize>, shape: (usize, usize)) -> Vec<f64> {
+    let (rows, cols) = shape;
+    let norm = |values: &mut dyn Iterator<Item = f64>| values.map(|v| v * v).sum::<f64>().sqrt();
+    match axis {
+        None => vec![norm(&mut data.iter().copied())],
+        Some(0) => (0..cols).map(|j| norm(&mut (0..rows).map(|i| data[i * cols + j]))).collect(),
+        Some(_) => data.chunks(cols).map(|row| norm(&mut row.iter().copied())).collect(),
+    }
+}
+
+/// `np.diag` over row-major `data`: a `(1, n)` result takes the diagonal of the source,
+/// anything larger places the source row on the diagonal
+pub fn diag_data(data: &[f64], source_shape: (usize, usize), shape: (usize, usize)) -> Vec<f64> {
+    let (rows, cols) = shape;
+    if rows == 1 {
+        return (0..cols).map(|i| data[i * source_shape.1 + i]).collect();
+    }
+    let mut result = vec![0.0; rows * cols];
+    for (i, value) in data.iter().enumerate().take(rows) {
+        result[i * cols + i] = *value;
+    }
+    result
+}
+
+/// Combine two row-major operands element-wise, stretching size-1 axes as in NumPy
+///
+/// Returns the result shape and data, or `None` if the shapes are incompatible.
+pub fn broadcast_zip(
+    (left, left_shape): (&[f64], (usize, usize)),
+    (right, right_shape): (&[f64], (usize, usize)),
+    f: impl Fn(f64, f64) -> f64,
+) -> Option<((usize, usize), Vec<f64>)> {
+    let axis = |l: usize, r: usize| (l == r || l == 1 || r == 1).then(|| l.max(r));
+    let shape = (axis(left_shape.0, right_shape.0)?, axis(left_shape.1, right_shape.1)?);
+    let at = |data: &[f64], (rows, cols): (usize, usize), i: usize, j: usize| {
+        data[if rows == 1 { 0 } else { i } * cols + if cols == 1 { 0 } else { j }]
+    };
+    let data = (0..shape.0)
+        .flat_map(|i| (0..shape.1).map(move |j| (i, j)))
+        .map(|(i, j)| f(at(left, left_shape, i, j), at(right, right_shape, i, j)))
+        .collect();
+    Some((shape, data))
 }
 
 /// Information about a single tile in a tiled matrix multiplication
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MatMulTile {
     /// Which tile of the output this contributes to (row)
     pub output_row: usize,
@@ -294,12 +1179,29 @@ pub struct MatMulTile {
     pub is_first_k: bool,
     /// Is this the last tile along K?
     pub is_last_k: bool,
+    /// Write the partial sums to DRAM after this tile (see `accumulator_k_tiles`)
+    pub spill: bool,
+    /// Read the spilled partial sums back before this tile
+    pub reload: bool,
+}
+
+impl MatMulTile {
+    /// The same tile of the transposed product: `(A @ B).T = B.T @ A.T`
+    fn transposed(&self) -> Self {
+        MatMulTile {
+            output_row: self.output_col,
+            output_col: self.output_row,
+            a_row_range: self.b_col_range,
+            b_col_range: self.a_row_range,
+            ..self.clone()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::Shape;
+    use crate::hardware::TileOrder;
     
     #[test]
     fn test_small_matmul_no_tiling() {
@@ -328,6 +1230,263 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_reshape_fused_into_matmul() {
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("x", (3, 1));
+        let program = crate::parser::Parser::new("y = A @ x.flatten()").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiled = TilingStrategy::new(SystolicConfig::new(3, 8, 32)).tile_program(&typed).unwrap();
+        
+        // Only the view of the product column as a vector remains
+        let reshapes: Vec<_> = tiled.operations.iter()
+            .filter_map(|op| match op {
+                TiledOperation::Reshape { source, .. } => Some(source.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reshapes, ["y_column"]);
+        assert!(tiled.operations.iter().any(|op| matches!(
+            op,
+            TiledOperation::LoadMatrix { target, source, shape: (3, 1), .. } if target == "y_right" && source == "x"
+        )));
+    }
+    
+    #[test]
+    fn test_let_binding_tiled_once() {
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("A", (2, 3));
+        analyzer.define_matrix("B", (3, 2));
+        let program = crate::parser::Parser::new("C = let t = A @ B in t + t").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiled = TilingStrategy::new(SystolicConfig::new(3, 8, 32)).tile_program(&typed).unwrap();
+        
+        let matmuls: Vec<_> = tiled.operations.iter()
+            .filter(|op| matches!(op, TiledOperation::TiledMatMul { .. }))
+            .map(|op| op.target())
+            .collect();
+        assert_eq!(matmuls, ["C_let_t"]);
+        let reads = tiled.operations.iter()
+            .filter(|op| matches!(op, TiledOperation::LoadMatrix { source, .. } if source == "C_let_t"))
+            .count();
+        assert_eq!(reads, 2);
+    }
+    
+    #[test]
+    fn test_dead_statements_eliminated() {
+        let matmuls = |source: &str, eliminate: bool| {
+            let mut analyzer = crate::analyzer::Analyzer::new();
+            for name in ["A", "B", "C", "D"] {
+                analyzer.define_matrix(name, (2, 2));
+            }
+            let typed = analyzer.analyze(crate::parser::Parser::new(source).parse_program().unwrap()).unwrap();
+            let config = SystolicConfig::new(2, 8, 32).with_eliminate_dead_statements(eliminate);
+            let tiled = TilingStrategy::new(config).tile_program(&typed).unwrap();
+            tiled.operations.iter()
+                .filter(|op| matches!(op, TiledOperation::TiledMatMul { .. }))
+                .map(|op| op.target().to_string())
+                .collect::<Vec<_>>()
+        };
+        
+        assert_eq!(matmuls("X = A @ B; Y = C @ D", false), ["X", "Y"]);
+        assert_eq!(matmuls("X = A @ B; Y = C @ D", true), ["Y"]);
+        // Read later, or overwritten before any read
+        assert_eq!(matmuls("X = A @ B; Y = X @ D", true), ["X", "Y"]);
+        assert_eq!(matmuls("X = A @ B; X = C @ D; Y = X + A", true), ["X"]);
+    }
+    
+    #[test]
+    fn test_broadcast_recorded() {
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("X", (3, 4));
+        analyzer.define_matrix("row", (1, 4));
+        analyzer.define_matrix("col", (3, 1));
+        let program = crate::parser::Parser::new("a = X + row; b = col - X; c = X + X; d = X + 1")
+            .parse_program()
+            .unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiled = TilingStrategy::new(SystolicConfig::new(4, 8, 32)).tile_program(&typed).unwrap();
+        let broadcasts: Vec<_> = tiled.operations.iter()
+            .filter_map(|op| match op {
+                TiledOperation::Add { broadcast, .. } | TiledOperation::Sub { broadcast, .. } => Some(*broadcast),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(broadcasts, [
+            Some(Broadcast { right: true, axis: Some(0) }),
+            Some(Broadcast { right: false, axis: Some(1) }),
+            None,
+            Some(Broadcast { right: true, axis: None }),
+        ]);
+    }
+    
+    #[test]
+    fn test_common_subexpressions_tiled_once() {
+        let matmuls = |source: &str| {
+            let mut analyzer = crate::analyzer::Analyzer::new();
+            for name in ["A", "B", "C"] {
+                analyzer.define_matrix(name, (3, 3));
+            }
+            let typed = analyzer.analyze(crate::parser::Parser::new(source).parse_program().unwrap()).unwrap();
+            let tiler = TilingStrategy::new(SystolicConfig::new(3, 8, 32));
+            let count = |program: TiledProgram| program.operations.iter()
+                .filter(|op| matches!(op, TiledOperation::TiledMatMul { .. }))
+                .count();
+            (count(tiler.tile_program(&typed).unwrap()), count(tiler.tile_program_optimized(&typed).unwrap()))
+        };
+        
+        assert_eq!(matmuls("X = A @ B; Y = A @ B + C"), (2, 1));
+        assert_eq!(matmuls("X = A @ B; Y = relu(A @ B) @ (A @ B)"), (4, 2));
+        
+        // Reassigning an operand or the holder makes the earlier result stale
+        assert_eq!(matmuls("X = A @ B; A = C; Y = A @ B + C"), (2, 2));
+        assert_eq!(matmuls("X = A @ B; X = C; Y = A @ B + C"), (2, 2));
+    }
+    
+    #[test]
+    fn test_reorder_matmul_chains() {
+        let tiles = |source: &str, shapes: [(usize, usize); 3], reorder: bool| {
+            let mut analyzer = crate::analyzer::Analyzer::new();
+            for (name, shape) in ["A", "B", "C"].into_iter().zip(shapes) {
+                analyzer.define_matrix(name, shape);
+            }
+            let typed = analyzer.analyze(crate::parser::Parser::new(source).parse_program().unwrap()).unwrap();
+            let config = SystolicConfig::new(4, 8, 32).with_reorder_matmul_chains(reorder);
+            TilingStrategy::new(config).tile_program(&typed).unwrap().operations.iter()
+                .map(|op| match op {
+                    TiledOperation::TiledMatMul { tiles, .. } => tiles.len(),
+                    _ => 0,
+                })
+                .sum::<usize>()
+        };
+        
+        // (50x5 @ 5x100) @ 100x10 builds a 50x100 intermediate; A @ (B @ C) never does
+        let wide = [(50, 5), (5, 100), (100, 10)];
+        assert_eq!(tiles("D = A @ B @ C", wide, false), 1625);
+        assert_eq!(tiles("D = A @ B @ C", wide, true), 228);
+        
+        // Parentheses pin the grouping
+        assert_eq!(tiles("D = (A @ B) @ C", wide, true), 1625);
+        
+        // Already the cheapest order
+        let tall = [(10, 100), (100, 5), (5, 50)];
+        assert_eq!(tiles("D = A @ B @ C", tall, true), tiles("D = A @ B @ C", tall, false));
+    }
+    
+    #[test]
+    fn test_fold_transposed_matmul() {
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("A", (5, 3));
+        analyzer.define_matrix("B", (3, 2));
+        let typed = analyzer.analyze(crate::parser::Parser::new("D = (A @ B).T").parse_program().unwrap()).unwrap();
+        let ops = TilingStrategy::new(SystolicConfig::new(2, 8, 32)).tile_program(&typed).unwrap().operations;
+        
+        assert!(!ops.iter().any(|op| matches!(op, TiledOperation::Transpose { .. })));
+        let Some(TiledOperation::TiledMatMul { left_source, right_source, left_shape, right_shape, output_shape, tiles, transposed, .. }) = ops.last() else {
+            panic!("expected a matmul, got {:?}", ops.last());
+        };
+        assert!(*transposed);
+        assert_eq!((left_source.as_str(), right_source.as_str()), ("D_right", "D_left"));
+        assert_eq!((*left_shape, *right_shape, *output_shape), ((2, 3), (3, 5), (2, 5)));
+        
+        // One row of output tiles over the swapped grid, K tiles consecutive
+        let coords: Vec<_> = tiles.iter().map(|tile| (tile.output_row, tile.output_col, tile.k_index)).collect();
+        assert_eq!(coords, vec![(0, 0, 0), (0, 0, 1), (0, 1, 0), (0, 1, 1), (0, 2, 0), (0, 2, 1)]);
+        assert_eq!((tiles[4].a_row_range, tiles[4].b_col_range), ((0, 2), (4, 5)));
+    }
+    
+    #[test]
+    fn test_json_round_trip() {
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("A", (2, 5));
+        analyzer.define_matrix("B", (5, 2));
+        let source = "C = A @ B; D = relu(C + [[0.1, 1e-300], [0.30000000000000004, -2.5e17]])";
+        let typed = analyzer.analyze(crate::parser::Parser::new(source).parse_program().unwrap()).unwrap();
+        let tiled = TilingStrategy::new(SystolicConfig::new(3, 8, 32)).tile_program(&typed).unwrap();
+        
+        let restored = TiledProgram::from_json(&tiled.to_json().unwrap()).unwrap();
+        assert_eq!(restored, tiled);
+        
+        // Literal data comes back bit for bit, not just approximately
+        let literal = |program: &TiledProgram| program.operations.iter()
+            .find_map(|op| match op {
+                TiledOperation::LoadLiteral { data, .. } => Some(data.iter().map(|v| v.to_bits()).collect::<Vec<_>>()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(literal(&restored), literal(&tiled));
+        
+        assert!(TiledProgram::from_json("{\"operations\": 3}").is_err());
+    }
+    
+    #[test]
+    fn test_estimated_passes_matches_codegen() {
+        let config = SystolicConfig::new(3, 8, 32);
+        let mut analyzer = crate::analyzer::Analyzer::new();
+        analyzer.define_matrix("A", (7, 5));
+        analyzer.define_matrix("B", (5, 4));
+        let program = crate::parser::Parser::new("C = A @ B; D = C.T @ A").parse_program().unwrap();
+        let typed = analyzer.analyze(program).unwrap();
+        
+        let tiled = TilingStrategy::new(config.clone()).tile_program(&typed).unwrap();
+        let estimate = tiled.estimated_passes();
+        let hw = crate::codegen::CodeGenerator::new(config).generate(tiled).unwrap();
+        
+        // 3x2 output tiles x 2 K tiles, then 2x2 output tiles x 3 K tiles
+        assert_eq!(estimate, 12 + 12);
+        assert_eq!(estimate, hw.passes.len());
+    }
+    
+    #[test]
+    fn test_unknown_shape_names_operand() {
+        let operand = |name: &str, shape: Shape| TypedExpr { expr: TypedExprKind::Variable(name.to_string()), shape };
+        let tiler = TilingStrategy::new(SystolicConfig::new(2, 8, 32));
+        
+        let message = tiler.tile_matmul(&operand("A", Shape::matrix(2, 2)), &operand("B", Shape::Unknown), "C")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(message, "Tiling error: shape of B is unknown; define it with --shape B=RxC");
+        
+        // A transposed unknown is blamed on the variable underneath
+        let transposed = TypedExpr {
+            expr: TypedExprKind::Transpose(Box::new(operand("A", Shape::Unknown))),
+            shape: Shape::Unknown,
+        };
+        let message = tiler.tile_matmul(&transposed, &operand("B", Shape::Unknown), "C").unwrap_err().to_string();
+        assert!(message.contains("shape of A is unknown"), "{}", message);
+        
+        let literal = TypedExpr { expr: TypedExprKind::Matrix(vec![]), shape: Shape::Unknown };
+        let message = tiler.tile_matmul(&literal, &operand("B", Shape::matrix(2, 2)), "C").unwrap_err().to_string();
+        assert_eq!(message, "Tiling error: shape of the left operand of C is unknown");
+    }
+    
+    #[test]
+    fn test_max_passes() {
+        let operand = |name: &str, rows, cols| TypedExpr {
+            expr: TypedExprKind::Variable(name.to_string()),
+            shape: Shape::matrix(rows, cols),
+        };
+        let (left, right) = (operand("A", 2000, 2000), operand("B", 2000, 2000));
+        
+        // 1000^3 tiles on a 2x2 array; the guard fires before any are built
+        let tiler = TilingStrategy::new(SystolicConfig::new(2, 8, 32));
+        let message = tiler.tile_matmul(&left, &right, "C").unwrap_err().to_string();
+        assert!(message.contains("would need 1000000000 passes on a 2x2 array, exceeding the limit of 1000000"), "{}", message);
+        
+        // 6x6 @ 6x6 on a 3x3 array is 8 passes
+        let (left, right) = (operand("A", 6, 6), operand("B", 6, 6));
+        let tiler = TilingStrategy::new(SystolicConfig::new(3, 8, 32).with_max_passes(8));
+        assert!(tiler.tile_matmul(&left, &right, "C").is_ok());
+        let tiler = TilingStrategy::new(SystolicConfig::new(3, 8, 32).with_max_passes(7));
+        assert!(matches!(tiler.tile_matmul(&left, &right, "C"), Err(CompileError::TilingError { .. })));
+        let tiler = TilingStrategy::new(SystolicConfig::new(1, 8, 32).with_max_passes(0));
+        assert!(tiler.tile_matmul(&left, &right, "C").is_ok());
+    }
+    
     #[test]
     fn test_large_matmul_tiling() {
         let config = SystolicConfig::new(3, 8, 32);
@@ -352,4 +1511,29 @@ mod tests {
             panic!("Expected TiledMatMul");
         }
     }
+    
+    #[test]
+    fn test_col_major_tile_order() {
+        let config = SystolicConfig::new(3, 8, 32).with_tile_order(TileOrder::ColMajor);
+        let tiler = TilingStrategy::new(config);
+        
+        // 6x6 @ 6x6 - 2x2 output tiles, 2 along K
+        let operand = |name: &str| TypedExpr {
+            expr: TypedExprKind::Variable(name.to_string()),
+            shape: Shape::matrix(6, 6),
+        };
+        
+        let ops = tiler.tile_matmul(&operand("A"), &operand("B"), "C").unwrap();
+        let TiledOperation::TiledMatMul { tiles, .. } = &ops[2] else {
+            panic!("Expected TiledMatMul");
+        };
+        
+        let order: Vec<_> = tiles.iter().map(|t| (t.output_row, t.output_col, t.k_index)).collect();
+        assert_eq!(order, vec![
+            (0, 0, 0), (0, 0, 1),
+            (1, 0, 0), (1, 0, 1),
+            (0, 1, 0), (0, 1, 1),
+            (1, 1, 0), (1, 1, 1),
+        ]);
+    }
 }
@@ -0,0 +1,191 @@
+//! End-to-end tests of the `numpy2systolic` binary
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_numpy2systolic"))
+        .args(args)
+        .output()
+        .expect("failed to run numpy2systolic")
+}
+
+#[test]
+fn test_rejects_undefined_variable() {
+    let output = run(&["C = A @ B", "--shape", "A=2x3"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Undefined variable: B"), "{}", stderr);
+
+    // With no shapes at all, the first operand is the one reported
+    let output = run(&["C = A @ B"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Undefined variable: A\nDefine it with --shape A=RxC"), "{}", stderr);
+}
+
+#[test]
+fn test_rejects_invalid_config() {
+    for (args, message) in [
+        (["--array-size", "0"], "Array size must be at least 1"),
+        (["--data-width", "1"], "Data width must be between 2 and 128 bits"),
+        (["--acc-width", "4"], "Accumulator width (4 bits) must be at least the data width (8 bits)"),
+    ] {
+        let output = run(&[&["C = A @ B", "--shape", "A=2x2", "--shape", "B=2x2"], &args[..]].concat());
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Configuration error"), "{}", stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_lenient_reports_suppressed_warnings() {
+    let output = run(&["C = A @ B; C = A.T", "--shape", "A=2x3", "--shape", "B=3x2", "--json"]);
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("'C' redefined with shape (3, 2) (was (2, 2))"), "{}", stderr);
+    assert!(stderr.contains("1 warning(s) suppressed"), "{}", stderr);
+}
+
+#[test]
+fn test_simulate_pretty_matrix() {
+    let output = run(&["C = [[1, -20], [300, 4]] @ np.eye(2)", "--simulate", "--pretty-matrix"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "  1  -20\n300    4\n");
+}
+
+#[test]
+fn test_profile_reports_utilization() {
+    let output = run(&["C = A @ B", "-s", "A=3x3", "-s", "B=3x2", "--profile"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "Utilization over 1 pass(es): min 66.7%, mean 66.7%, max 66.7%\n  Pass 0: 66.7%\n");
+}
+
+#[test]
+fn test_max_passes_rejects_large_matmul() {
+    let output = run(&[
+        "C = A @ B", "--shape", "A=120x120", "--shape", "B=120x120", "--max-passes", "100",
+    ]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // A single matmul over the budget is caught while tiling, before its tiles are built
+    assert!(stderr.contains("C (120x120 @ 120x120) would need 64000 passes on a 3x3 array"), "{}", stderr);
+    assert!(stderr.contains("exceeding the limit of 100"), "{}", stderr);
+
+    // Several matmuls can each fit and still overrun it together
+    let output = run(&[
+        "C = A @ B; D = C @ B", "--shape", "A=12x12", "--shape", "B=12x12", "--max-passes", "100",
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("program needs 128 passes, more than --max-passes 100"), "{}", stderr);
+    assert!(stderr.contains("larger --array-size"), "{}", stderr);
+}
+
+#[test]
+fn test_batch_emits_one_program_per_statement() {
+    let output = run(&["C = A @ B; D = B @ A", "--shape", "A=2x3", "--shape", "B=3x2", "--batch"]);
+
+    assert!(output.status.success());
+    let programs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let programs = programs.as_array().unwrap();
+    assert_eq!(programs.len(), 2);
+    assert_eq!(programs[0]["output_shape"], serde_json::json!([2, 2]));
+    assert_eq!(programs[1]["output_shape"], serde_json::json!([3, 3]));
+
+    // Batch programs are held to the same limits as a single one
+    let output = run(&["C = A @ A; D = A @ A @ A", "--shape", "A=12x12", "--max-passes", "100", "--batch"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("program needs 128 passes, more than the limit of 100"), "{}", stderr);
+
+    let output = run(&["C = A; A = np.eye(3)", "--shape", "A=2x2", "--strict", "--batch"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("'A' redefined with shape"), "{}", stderr);
+}
+
+#[test]
+fn test_mem_packs_big_endian_words() {
+    let output = run(&["C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", "-n", "2", "--mem", "--pack", "4", "--big-endian"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with("\n01020304\n05070608\n"), "{}", stdout);
+}
+
+#[test]
+fn test_explain_narrates_tiling() {
+    let output = run(&["C = A @ B", "--shape", "A=4x4", "--shape", "B=4x4", "-n", "2", "--explain"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("3. Tiling\n   C: (4, 4) @ (4, 4) on a 2x2 array is 2x2 output tiles with 2 K step(s) each, 8 pass(es)"), "{}", stdout);
+    assert!(stdout.contains("4. Code generation\n   Pass 0:"), "{}", stdout);
+}
+
+#[test]
+fn test_verilog_output() {
+    let output = run(&["C = [[-1, 2], [3, 4]] @ [[1, 1], [0, 1]]", "--verilog", "-n", "2"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("// file: systolic_vectors.hex\n// Pass 0: "), "{}", stdout);
+    assert!(stdout.contains("@0\nff\n"), "{}", stdout);
+    assert!(stdout.contains("module systolic_driver #("), "{}", stdout);
+}
+
+#[test]
+fn test_numpy_reference_output() {
+    let output = run(&["C = [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]", "--numpy"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Auto-generated NumPy reference"), "{}", stdout);
+    assert!(stdout.contains("C = C_a @ C_b\nnp.testing.assert_allclose(C, "), "{}", stdout);
+}
+
+#[test]
+fn test_dot_output() {
+    let output = run(&["C = A @ B.T", "-s", "A=2x3", "-s", "B=4x3", "--dot"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("digraph typed_program {"), "{}", stdout);
+    assert!(stdout.contains("[label=\".T\\n(4, 3) -> (3, 4)\"]"), "{}", stdout);
+}
+
+#[test]
+fn test_data_feeds_named_matrices() {
+    let output = run(&[
+        "C = A @ B", "-s", "A=2x2", "-s", "B=2x2", "--data", "A=1,2,3,4", "--data", "B=5, 6, 7, 8", "--chisel",
+    ]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1.S, 2.S, 0.S, \n  3.S, 4.S"), "{}", stdout);
+
+    let output = run(&["C = A @ A", "-s", "A=2x2", "--data", "A=1,2,3"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("expected 4 values for 'A' (2x2), got 3 values"), "{}", stderr);
+
+    // Batch programs and the REPL take the same data
+    let output = run(&["C = A @ A; D = A.T", "-s", "A=2x2", "--data", "A=1,2,3,4", "--batch"]);
+    assert!(output.status.success());
+    let programs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let matrix_a = programs[0]["passes"][0]["matrix_a"].as_array().unwrap();
+    assert_eq!(matrix_a[..3], [1, 2, 0]);
+
+    let output = run(&["repl", "-s", "A=2x2", "--data", "A=1,2,3"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("expected 4 values for 'A' (2x2), got 3 values"), "{}", stderr);
+}